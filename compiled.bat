@@ -1,3 +0,0 @@
-C:\VulkanSDK\1.3.216.0\Bin\glslc.exe shaders\shader.vert -o shaders\shader.vert.spv
-C:\VulkanSDK\1.3.216.0\Bin\glslc.exe shaders\shader.frag -o shaders\shader.frag.spv
-pause
\ No newline at end of file
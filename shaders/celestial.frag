@@ -0,0 +1,30 @@
+#version 450
+
+layout(location=0)in vec2 fragCorner;
+layout(location=1)flat in uint fragBody;
+layout(location=0)out vec4 outColor;
+
+layout(push_constant)uniform Push
+{
+    mat4 projView;
+    vec4 cameraPosition;
+    // xyz: towards the sun, w: daylight
+    vec4 sunDirection;
+}push;
+
+void main(){
+    float fromCenter = length(fragCorner);
+    if (fragBody == 0u) {
+        // a bright disc in a glow that fades out at the quad's edge
+        float disc = smoothstep(0.42, 0.38, fromCenter);
+        float glow = pow(max(1.0 - fromCenter, 0.0), 3.0) * 0.6;
+        outColor = vec4(1.0, 0.94, 0.78, max(disc, glow));
+    } else {
+        // a pale disc with darker patches, faded out by day
+        float disc = smoothstep(0.62, 0.56, fromCenter);
+        float patches = smoothstep(0.22, 0.18, length(fragCorner - vec2(0.18, 0.12)))
+            + smoothstep(0.16, 0.12, length(fragCorner - vec2(-0.2, -0.18)));
+        vec3 color = vec3(0.86, 0.88, 0.95) * (1.0 - 0.25 * min(patches, 1.0));
+        outColor = vec4(color, disc * (1.0 - 0.8 * push.sunDirection.w));
+    }
+}
@@ -0,0 +1,45 @@
+#version 450
+
+// Draws the sun (instance 0) and the moon (instance 1) as quads facing the
+// camera on the far plane, as a 4 vertex triangle strip. No vertex buffer
+// is bound.
+
+layout(push_constant)uniform Push
+{
+    mat4 projView;
+    vec4 cameraPosition;
+    // xyz: towards the sun, w: daylight
+    vec4 sunDirection;
+}push;
+
+layout(location=0)out vec2 fragCorner;
+layout(location=1)flat out uint fragBody;
+
+// Half the width of each quad, per unit of distance.
+const float SUN_SIZE = 0.09;
+const float MOON_SIZE = 0.06;
+// Any distance inside the far plane; the depth is moved onto it.
+const float DISTANCE = 100.0;
+
+void main(){
+    uint vertex = uint(gl_VertexIndex);
+    uint body = uint(gl_InstanceIndex);
+    vec3 direction = push.sunDirection.xyz;
+    float size = SUN_SIZE;
+    if (body == 1u) {
+        direction = -direction;
+        size = MOON_SIZE;
+    }
+    vec3 up = vec3(0.0, 1.0, 0.0);
+    if (abs(direction.y) > 0.99) {
+        up = vec3(0.0, 0.0, 1.0);
+    }
+    vec3 right = normalize(cross(up, direction));
+    up = cross(direction, right);
+    vec2 corner = vec2(float(vertex & 1u), float((vertex >> 1u) & 1u)) * 2.0 - 1.0;
+    vec3 offset = (direction + (right * corner.x + up * corner.y) * size) * DISTANCE;
+    vec4 position = push.projView * vec4(push.cameraPosition.xyz + offset, 1.0);
+    gl_Position = vec4(position.xy, position.w, position.w);
+    fragCorner = corner;
+    fragBody = body;
+}
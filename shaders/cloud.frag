@@ -0,0 +1,40 @@
+#version 450
+
+layout(location=0)in float fragShade;
+layout(location=1)in vec3 fragWorldPosition;
+layout(location=0)out vec4 outColor;
+
+// The start of the terrain lighting uniform in shader.frag; only the light
+// and fog are read.
+layout(set = 0, binding = 0) uniform Shadows
+{
+    mat4 lightProjView[3];
+    vec4 splits;
+    vec4 sunDirection;
+    vec4 cameraPosition;
+    vec4 cameraForward;
+    // x: ambient light, y: full sunlight
+    vec4 light;
+    // x: 0 for linear fog from y to z, 1 for exponential fog of density y
+    vec4 fog;
+    vec4 fogColor;
+    // x: seconds since the renderer started
+    vec4 time;
+} shadows;
+
+const float CLOUD_ALPHA = 0.8;
+
+float fogAmount(vec3 worldPosition){
+    float distance = length(worldPosition - shadows.cameraPosition.xyz);
+    if (shadows.fog.x > 0.5) {
+        return 1.0 - exp(-shadows.fog.y * distance);
+    }
+    return clamp((distance - shadows.fog.y) / (shadows.fog.z - shadows.fog.y), 0.0, 1.0);
+}
+
+void main(){
+    vec3 color = vec3(fragShade * shadows.light.y);
+    float fog = fogAmount(fragWorldPosition);
+    // clouds fade out into the sky rather than turning fog coloured
+    outColor = vec4(color, CLOUD_ALPHA * (1.0 - fog));
+}
@@ -0,0 +1,25 @@
+#version 450
+
+// Draws the cloud map as a 3x3 grid of copies, one per instance, so the
+// layer reaches past the fog in every direction.
+
+layout(location=0)in vec3 position;
+layout(location=1)in vec3 color;
+layout(location=0)out float fragShade;
+layout(location=1)out vec3 fragWorldPosition;
+
+layout(push_constant)uniform Push
+{
+    mat4 projView;
+    // xyz: where the first copy starts, w: how far apart copies are
+    vec4 origin;
+}push;
+
+void main(){
+    uint instance = uint(gl_InstanceIndex);
+    vec2 copy = vec2(float(instance % 3u), float(instance / 3u));
+    vec3 world = push.origin.xyz + position + vec3(copy.x, 0.0, copy.y) * push.origin.w;
+    gl_Position = push.projView * vec4(world, 1.0);
+    fragShade = color.r;
+    fragWorldPosition = world;
+}
@@ -0,0 +1,116 @@
+#version 450
+
+// Fragments more transparent than this are discarded, not blended.
+const float ALPHA_CUTOFF = 0.5;
+
+layout (location =0) out vec4 outColor;
+layout(location = 0) in vec3 fragColor;
+layout(location = 1) in vec2 fragUv;
+layout(location = 2) flat in uint fragTextureLayer;
+layout(location = 3) in vec3 fragNormal;
+layout(location = 5) in vec3 fragWorldPosition;
+layout(set = 0, binding = 0) uniform texture2DArray blockTextures;
+layout(set = 0, binding = 1) uniform sampler blockSampler;
+
+// Cascaded shadow maps side by side in one atlas, matching shadow_map.rs.
+const int CASCADE_COUNT = 3;
+const float CASCADE_SIZE = 1024.0;
+// Pushes the lookup off the surface along its normal to avoid acne.
+const float NORMAL_OFFSET = 0.05;
+
+layout(set = 1, binding = 0) uniform Shadows
+{
+    mat4 lightProjView[CASCADE_COUNT];
+    vec4 splits;
+    vec4 sunDirection;
+    vec4 cameraPosition;
+    vec4 cameraForward;
+    // x: ambient light, y: full sunlight
+    vec4 light;
+    // x: 0 for linear fog from y to z, 1 for exponential fog of density y
+    vec4 fog;
+    vec4 fogColor;
+    // x: seconds since the renderer started
+    vec4 time;
+} shadows;
+layout(set = 1, binding = 1) uniform texture2D shadowMap;
+layout(set = 1, binding = 2) uniform samplerShadow shadowSampler;
+// The blurred screen-space ambient occlusion, 1 where nothing is occluded
+// or SSAO is off.
+layout(set = 1, binding = 3) uniform texture2D ambientOcclusion;
+layout(set = 1, binding = 4) uniform sampler ambientOcclusionSampler;
+
+// Frame count in x and seconds per frame in y for every array layer,
+// matching texture_animation_table in block_textures.rs.
+const int TEXTURE_LAYERS = 64;
+layout(set = 0, binding = 2) uniform TextureAnimations
+{
+    vec4 animations[TEXTURE_LAYERS];
+} textureAnimations;
+
+// The layer showing the current frame of the texture starting at `layer`.
+float animatedLayer(uint layer){
+    vec4 animation = textureAnimations.animations[layer];
+    return float(layer) + mod(floor(shadows.time.x / animation.y), animation.x);
+}
+
+// 1 where the sun reaches the surface, 0 in full shadow, filtered over a
+// 3x3 texel neighbourhood.
+float sunVisibility(vec3 worldPosition, vec3 normal){
+    float viewDepth = dot(worldPosition - shadows.cameraPosition.xyz, shadows.cameraForward.xyz);
+    int cascade = 0;
+    if (viewDepth > shadows.splits.x) {
+        cascade = 1;
+    }
+    if (viewDepth > shadows.splits.y) {
+        cascade = 2;
+    }
+    if (viewDepth > shadows.splits.z) {
+        return 1.0;
+    }
+    vec4 lightPosition = shadows.lightProjView[cascade] * vec4(worldPosition + normal * NORMAL_OFFSET, 1.0);
+    vec3 ndc = lightPosition.xyz / lightPosition.w;
+    vec2 uv = ndc.xy * 0.5 + 0.5;
+    if (uv.x < 0.0 || uv.x > 1.0 || uv.y < 0.0 || uv.y > 1.0 || ndc.z > 1.0) {
+        return 1.0;
+    }
+    float halfTexel = 0.5 / CASCADE_SIZE;
+    float visibility = 0.0;
+    for (int y = -1; y <= 1; y++) {
+        for (int x = -1; x <= 1; x++) {
+            vec2 tap = clamp(uv + vec2(float(x), float(y)) / CASCADE_SIZE, vec2(halfTexel), vec2(1.0 - halfTexel));
+            vec2 atlasUv = vec2((tap.x + float(cascade)) / float(CASCADE_COUNT), tap.y);
+            visibility += texture(sampler2DShadow(shadowMap, shadowSampler), vec3(atlasUv, ndc.z));
+        }
+    }
+    return visibility / 9.0;
+}
+
+float sunLight(vec3 worldPosition, vec3 normal){
+    vec3 n = normalize(normal);
+    float ambient = shadows.light.x;
+    if (dot(n, shadows.sunDirection.xyz) <= 0.0) {
+        return ambient;
+    }
+    return mix(ambient, shadows.light.y, sunVisibility(worldPosition, n));
+}
+
+// How much of the fog colour covers a point at `worldPosition`.
+float fogAmount(vec3 worldPosition){
+    float distance = length(worldPosition - shadows.cameraPosition.xyz);
+    if (shadows.fog.x > 0.5) {
+        return 1.0 - exp(-shadows.fog.y * distance);
+    }
+    return clamp((distance - shadows.fog.y) / (shadows.fog.z - shadows.fog.y), 0.0, 1.0);
+}
+
+void main(){
+    vec4 texel = texture(sampler2DArray(blockTextures, blockSampler), vec3(fragUv, animatedLayer(fragTextureLayer)));
+    if (texel.a < ALPHA_CUTOFF) {
+        discard;
+    }
+    float occlusion = texelFetch(sampler2D(ambientOcclusion, ambientOcclusionSampler), ivec2(gl_FragCoord.xy), 0).r;
+    float light = sunLight(fragWorldPosition, fragNormal) * occlusion;
+    vec3 color = mix(fragColor * texel.rgb * light, shadows.fogColor.rgb, fogAmount(fragWorldPosition));
+    outColor = vec4(color, 1.0);
+}
@@ -0,0 +1,27 @@
+#version 450
+
+// Draws the 12 edges of a box as a 24 vertex line list. No vertex buffer
+// is bound.
+
+layout(push_constant)uniform Push
+{
+    mat4 proj_view;
+    vec4 minCorner;
+    vec4 maxCorner;
+    vec4 color;
+}push;
+
+layout(location=0)out vec4 fragColor;
+
+void main(){
+    uint edge = uint(gl_VertexIndex) / 2u;
+    // four edges run along each of x, y and z
+    uint axis = edge / 4u;
+    vec3 alongAxis = vec3(equal(uvec3(axis), uvec3(0u, 1u, 2u)));
+    vec3 corner = alongAxis * float(uint(gl_VertexIndex) % 2u)
+        + alongAxis.zxy * float(edge & 1u)
+        + alongAxis.yzx * float((edge >> 1u) & 1u);
+    vec3 position = mix(push.minCorner.xyz, push.maxCorner.xyz, corner);
+    fragColor = push.color;
+    gl_Position = push.proj_view * vec4(position, 1.0);
+}
@@ -0,0 +1,41 @@
+#version 450
+
+// Debug views selected with F4, matching DebugView in main_render_system.rs.
+const uint DEBUG_VIEW_NORMALS = 2;
+const uint DEBUG_VIEW_DEPTH = 3;
+const uint DEBUG_VIEW_CHUNK_COLOUR = 4;
+
+layout (location =0) out vec4 outColor;
+layout(location = 0) in vec3 fragColor;
+layout(location = 1) in vec2 fragUv;
+layout(location = 2) flat in uint fragTextureLayer;
+layout(location = 3) in vec3 fragNormal;
+layout(location = 4) flat in uint fragDrawId;
+
+layout(push_constant)uniform Push
+{
+    mat4 proj_view;
+    uint draw_id;
+    uint debug_view;
+    float near;
+    float far;
+}push;
+
+vec3 idColour(uint id){
+    uint h = id * 2654435761u;
+    return vec3(float((h >> 8u) & 255u), float((h >> 16u) & 255u), float((h >> 24u) & 255u)) / 255.0;
+}
+
+void main(){
+    if (push.debug_view == DEBUG_VIEW_NORMALS) {
+        outColor = vec4(normalize(fragNormal) * 0.5 + 0.5, 1.0);
+    } else if (push.debug_view == DEBUG_VIEW_DEPTH) {
+        // undo perspective_lh_zo to get the view-space distance
+        float z = gl_FragCoord.z;
+        float linearDepth = push.near * push.far / (push.far - z * (push.far - push.near));
+        float shade = 1.0 - clamp(linearDepth / 100.0, 0.0, 1.0);
+        outColor = vec4(vec3(shade), 1.0);
+    } else {
+        outColor = vec4(idColour(fragDrawId), 1.0);
+    }
+}
@@ -0,0 +1,40 @@
+#version 450
+
+// Draws a game object's bounding box as a solid 36 vertex triangle list,
+// two triangles per face. No vertex buffer is bound.
+
+layout(push_constant)uniform Push
+{
+    mat4 proj_view;
+    vec4 minCorner;
+    vec4 maxCorner;
+    // rgb: colour, a: ambient light
+    vec4 color;
+    // xyz: towards the sun, w: full sunlight
+    vec4 sunDirection;
+}push;
+
+layout(location=0)out vec3 fragColor;
+
+void main(){
+    uint face = uint(gl_VertexIndex) / 6u;
+    uint vertex = uint(gl_VertexIndex) % 6u;
+    // faces come in pairs along x, y and z, the far side second
+    uint axis = face / 2u;
+    float side = float(face % 2u);
+    vec3 normalAxis = vec3(equal(uvec3(axis), uvec3(0u, 1u, 2u)));
+    vec3 uAxis = normalAxis.zxy;
+    vec3 vAxis = normalAxis.yzx;
+    // the quad corners (0,0) (1,0) (1,1) and (0,0) (1,1) (0,1)
+    float u = float((0x16u >> vertex) & 1u);
+    float v = float((0x34u >> vertex) & 1u);
+    vec3 corner = normalAxis * side + uAxis * u + vAxis * v;
+    vec3 position = mix(push.minCorner.xyz, push.maxCorner.xyz, corner);
+    vec3 normal = normalAxis * (side * 2.0 - 1.0);
+
+    float ambient = push.color.a;
+    float sunlight = push.sunDirection.w;
+    float light = mix(ambient, sunlight, max(dot(normal, push.sunDirection.xyz), 0.0));
+    fragColor = push.color.rgb * light;
+    gl_Position = push.proj_view * vec4(position, 1.0);
+}
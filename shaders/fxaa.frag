@@ -0,0 +1,58 @@
+#version 450
+
+// Fast approximate anti-aliasing: blurs along the edge through each pixel,
+// found from the luma of its diagonal neighbours. Runs on gamma-encoded
+// colour, where luma differences match what the eye sees.
+
+layout(location=0)in vec2 fragUv;
+layout(location=0)out vec4 outColor;
+
+layout(set = 0, binding = 0) uniform texture2D source;
+layout(set = 0, binding = 1) uniform sampler sourceSampler;
+
+// Longest blur along an edge, in pixels.
+const float SPAN_MAX = 8.0;
+const float REDUCE_MUL = 1.0 / 8.0;
+const float REDUCE_MIN = 1.0 / 128.0;
+
+vec3 fetch(vec2 uv){
+    return texture(sampler2D(source, sourceSampler), uv).rgb;
+}
+
+float luma(vec3 color){
+    return dot(color, vec3(0.299, 0.587, 0.114));
+}
+
+void main(){
+    vec2 texel = 1.0 / vec2(textureSize(sampler2D(source, sourceSampler), 0));
+    vec3 center = fetch(fragUv);
+    float lumaNW = luma(fetch(fragUv + vec2(-1.0, -1.0) * texel));
+    float lumaNE = luma(fetch(fragUv + vec2(1.0, -1.0) * texel));
+    float lumaSW = luma(fetch(fragUv + vec2(-1.0, 1.0) * texel));
+    float lumaSE = luma(fetch(fragUv + vec2(1.0, 1.0) * texel));
+    float lumaM = luma(center);
+    float lumaMin = min(lumaM, min(min(lumaNW, lumaNE), min(lumaSW, lumaSE)));
+    float lumaMax = max(lumaM, max(max(lumaNW, lumaNE), max(lumaSW, lumaSE)));
+
+    // across the luma gradient is along the edge
+    vec2 direction = vec2(
+        (lumaSW + lumaSE) - (lumaNW + lumaNE),
+        (lumaNW + lumaSW) - (lumaNE + lumaSE)
+    );
+    float reduce = max((lumaNW + lumaNE + lumaSW + lumaSE) * 0.25 * REDUCE_MUL, REDUCE_MIN);
+    float scale = 1.0 / (min(abs(direction.x), abs(direction.y)) + reduce);
+    direction = clamp(direction * scale, vec2(-SPAN_MAX), vec2(SPAN_MAX)) * texel;
+
+    vec3 near = 0.5 * (
+        fetch(fragUv + direction * (1.0 / 3.0 - 0.5)) +
+        fetch(fragUv + direction * (2.0 / 3.0 - 0.5))
+    );
+    vec3 far = near * 0.5 + 0.25 * (
+        fetch(fragUv - direction * 0.5) +
+        fetch(fragUv + direction * 0.5)
+    );
+    // the wider blur crossed another edge, keep the narrow one
+    float lumaFar = luma(far);
+    vec3 color = (lumaFar < lumaMin || lumaFar > lumaMax) ? near : far;
+    outColor = vec4(color, 1.0);
+}
@@ -0,0 +1,22 @@
+#version 450
+
+// The swap chain stores colour as it is, so encoding for the display
+// happens here.
+
+layout(location=0)in vec2 fragUv;
+layout(location=0)out vec4 outColor;
+
+layout(set = 0, binding = 0) uniform texture2D source;
+layout(set = 0, binding = 1) uniform sampler sourceSampler;
+
+layout(push_constant)uniform Push
+{
+    // x: display gamma
+    vec4 params;
+    vec4 tint;
+}push;
+
+void main(){
+    vec3 color = texture(sampler2D(source, sourceSampler), fragUv).rgb;
+    outColor = vec4(pow(max(color, vec3(0.0)), vec3(1.0 / push.params.x)), 1.0);
+}
@@ -0,0 +1,8 @@
+#version 450
+
+// Colour writes are masked off, only the depth test matters.
+layout (location = 0) out vec4 outColor;
+
+void main(){
+    outColor = vec4(0.0);
+}
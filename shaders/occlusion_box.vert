@@ -0,0 +1,22 @@
+#version 450
+
+// Draws a chunk's bounding box as a 14 vertex triangle strip for an
+// occlusion query. No vertex buffer is bound.
+
+layout(push_constant)uniform Push
+{
+    mat4 proj_view;
+    vec4 minCorner;
+    vec4 maxCorner;
+}push;
+
+void main(){
+    uint vertex = uint(gl_VertexIndex);
+    vec3 corner = vec3(
+        float((0x287Au >> vertex) & 1u),
+        float((0x02AFu >> vertex) & 1u),
+        float((0x31E3u >> vertex) & 1u)
+    );
+    vec3 position = mix(push.minCorner.xyz, push.maxCorner.xyz, corner);
+    gl_Position = push.proj_view * vec4(position, 1.0);
+}
@@ -0,0 +1,14 @@
+#version 450
+
+layout(location=0)in vec2 fragCorner;
+layout(location=1)in vec4 fragColor;
+layout(location=0)out vec4 outColor;
+
+void main(){
+    // round, with a soft edge
+    float edge = 1.0 - smoothstep(0.6, 1.0, length(fragCorner));
+    if (edge <= 0.0) {
+        discard;
+    }
+    outColor = vec4(fragColor.rgb, fragColor.a * edge);
+}
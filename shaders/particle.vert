@@ -0,0 +1,73 @@
+#version 450
+
+// Draws each live particle of the ring as a quad facing the camera, one
+// instance per slot, as a 4 vertex triangle strip. Rain is stretched along
+// the world's up axis instead. No vertex buffer is bound.
+
+const uint KIND_DEBRIS = 0u;
+const uint KIND_RAIN = 1u;
+const uint KIND_SMOKE = 2u;
+const uint KIND_FLAME = 3u;
+const uint KIND_SNOW = 4u;
+
+struct Particle {
+    vec4 positionAge;
+    vec4 velocityLife;
+    uvec4 kind;
+};
+
+layout(set = 0, binding = 0) readonly buffer Particles {
+    Particle particles[];
+};
+
+layout(push_constant) uniform Push {
+    mat4 projView;
+    vec4 cameraRight;
+    // w: sunlight
+    vec4 cameraUp;
+} push;
+
+layout(location=0)out vec2 fragCorner;
+layout(location=1)out vec4 fragColor;
+
+void main(){
+    Particle particle = particles[gl_InstanceIndex];
+    float lifetime = particle.velocityLife.w;
+    if (lifetime <= 0.0) {
+        // outside the clip volume, so the quad is dropped
+        gl_Position = vec4(2.0, 2.0, 2.0, 1.0);
+        fragCorner = vec2(0.0);
+        fragColor = vec4(0.0);
+        return;
+    }
+    uint vertex = uint(gl_VertexIndex);
+    vec2 corner = vec2(float(vertex & 1u), float((vertex >> 1u) & 1u)) * 2.0 - 1.0;
+    float life = particle.positionAge.w / lifetime;
+    uint kind = particle.kind.x;
+    vec3 up = push.cameraUp.xyz;
+    vec2 size = vec2(0.1);
+    vec4 color = vec4(0.45, 0.32, 0.2, 1.0);
+    float light = push.cameraUp.w;
+    if (kind == KIND_RAIN) {
+        up = vec3(0.0, 1.0, 0.0);
+        size = vec2(0.02, 0.35);
+        color = vec4(0.6, 0.7, 0.85, 0.5);
+    } else if (kind == KIND_SMOKE) {
+        size = vec2(mix(0.2, 0.8, life));
+        color = vec4(0.55, 0.55, 0.55, 0.6 * (1.0 - life));
+    } else if (kind == KIND_SNOW) {
+        size = vec2(0.05);
+        color = vec4(0.95, 0.97, 1.0, 0.9 * min(1.0, (lifetime - particle.positionAge.w) * 2.0));
+    } else if (kind == KIND_FLAME) {
+        size = vec2(mix(0.14, 0.04, life));
+        color = vec4(mix(vec3(1.0, 0.85, 0.3), vec3(1.0, 0.3, 0.05), life), 1.0);
+        // flames give off their own light
+        light = 1.0;
+    }
+    vec3 position = particle.positionAge.xyz
+        + push.cameraRight.xyz * corner.x * size.x
+        + up * corner.y * size.y;
+    gl_Position = push.projView * vec4(position, 1.0);
+    fragCorner = corner;
+    fragColor = vec4(color.rgb * light, color.a);
+}
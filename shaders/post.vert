@@ -0,0 +1,12 @@
+#version 450
+
+// Covers the screen with one triangle for a post-process step. No vertex
+// buffer is bound.
+
+layout(location=0)out vec2 fragUv;
+
+void main(){
+    uint vertex = uint(gl_VertexIndex);
+    fragUv = vec2(float((vertex << 1u) & 2u), float(vertex & 2u));
+    gl_Position = vec4(fragUv * 2.0 - 1.0, 0.0, 1.0);
+}
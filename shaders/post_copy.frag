@@ -0,0 +1,13 @@
+#version 450
+
+// Passes the previous step's output on unchanged, for switched-off steps.
+
+layout(location=0)in vec2 fragUv;
+layout(location=0)out vec4 outColor;
+
+layout(set = 0, binding = 0) uniform texture2D source;
+layout(set = 0, binding = 1) uniform sampler sourceSampler;
+
+void main(){
+    outColor = texture(sampler2D(source, sourceSampler), fragUv);
+}
@@ -2,16 +2,34 @@
 
 layout(location=0)in vec3 position;
 layout(location=1)in vec3 color;
+layout(location=2)in vec2 uv;
+layout(location=3)in uint textureLayer;
+layout(location=4)in vec3 normal;
 layout(location=0)out vec3 fragColor;
+layout(location=1)out vec2 fragUv;
+layout(location=2)flat out uint fragTextureLayer;
+layout(location=3)out vec3 fragNormal;
+layout(location=4)flat out uint fragDrawId;
+layout(location=5)out vec3 fragWorldPosition;
 
 layout(push_constant)uniform Push
 {
     mat4 proj_view;
+    uint draw_id;
+    uint debug_view;
+    float near;
+    float far;
 }push;
 
 void main(){
     
     gl_Position=push.proj_view*vec4(position,1.0);
     fragColor=color;
+    fragUv=uv;
+    fragTextureLayer=textureLayer;
+    fragNormal=normal;
+    fragWorldPosition=position;
+    // indirect draws pass the id as first_instance, direct draws push it
+    fragDrawId=push.draw_id+uint(gl_InstanceIndex);
 }
 
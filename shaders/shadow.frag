@@ -0,0 +1,15 @@
+#version 450
+
+// Matches cutout.frag, so leaves and plants cast shadows with holes.
+const float ALPHA_CUTOFF = 0.5;
+
+layout(location = 0) in vec2 fragUv;
+layout(location = 1) flat in uint fragTextureLayer;
+layout(set = 0, binding = 0) uniform texture2DArray blockTextures;
+layout(set = 0, binding = 1) uniform sampler blockSampler;
+void main(){
+    float alpha = texture(sampler2DArray(blockTextures, blockSampler), vec3(fragUv, float(fragTextureLayer))).a;
+    if (alpha < ALPHA_CUTOFF) {
+        discard;
+    }
+}
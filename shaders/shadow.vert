@@ -0,0 +1,18 @@
+#version 450
+
+layout(location=0)in vec3 position;
+layout(location=2)in vec2 uv;
+layout(location=3)in uint textureLayer;
+layout(location=0)out vec2 fragUv;
+layout(location=1)flat out uint fragTextureLayer;
+
+layout(push_constant)uniform Push
+{
+    mat4 light_proj_view;
+}push;
+
+void main(){
+    gl_Position=push.light_proj_view*vec4(position,1.0);
+    fragUv=uv;
+    fragTextureLayer=textureLayer;
+}
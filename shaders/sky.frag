@@ -0,0 +1,51 @@
+#version 450
+
+layout(location=0)in vec2 fragNdc;
+layout(location=0)out vec4 outColor;
+
+layout(push_constant)uniform Push
+{
+    mat4 inverseProjView;
+    vec4 cameraPosition;
+    // xyz: towards the sun, w: daylight
+    vec4 sunDirection;
+    vec4 zenithColor;
+    vec4 horizonColor;
+}push;
+
+// Cells per unit of direction; each may hold one star.
+const float STAR_GRID = 220.0;
+const float STAR_CHANCE = 0.004;
+
+float hash(vec3 p){
+    p = fract(p * 0.3183099 + 0.1);
+    p *= 17.0;
+    return fract(p.x * p.y * p.z * (p.x + p.y + p.z));
+}
+
+// Stars are fixed to the sky, which turns with the sun about the z axis.
+float stars(vec3 direction){
+    float angle = atan(push.sunDirection.y, push.sunDirection.x);
+    float c = cos(angle);
+    float s = sin(angle);
+    vec3 sky = vec3(c * direction.x + s * direction.y, c * direction.y - s * direction.x, direction.z);
+    vec3 cell = floor(sky * STAR_GRID);
+    if (hash(cell) > STAR_CHANCE) {
+        return 0.0;
+    }
+    float fromCenter = length(fract(sky * STAR_GRID) - 0.5);
+    return smoothstep(0.5, 0.1, fromCenter) * (0.4 + 0.6 * hash(cell + 7.0));
+}
+
+void main(){
+    vec4 far = push.inverseProjView * vec4(fragNdc, 1.0, 1.0);
+    vec3 direction = normalize(far.xyz / far.w - push.cameraPosition.xyz);
+    float height = clamp(direction.y, 0.0, 1.0);
+    vec3 color = mix(push.horizonColor.rgb, push.zenithColor.rgb, sqrt(height));
+    // the sky brightens towards the sun
+    float towardsSun = max(dot(direction, push.sunDirection.xyz), 0.0);
+    color += push.horizonColor.rgb * pow(towardsSun, 8.0) * 0.4;
+    float night = 1.0 - push.sunDirection.w;
+    color += vec3(stars(direction) * night * smoothstep(0.0, 0.15, direction.y));
+    outColor = vec4(color, 1.0);
+}
@@ -0,0 +1,13 @@
+#version 450
+
+// Covers the screen with one triangle on the far plane. No vertex buffer is
+// bound.
+
+layout(location=0)out vec2 fragNdc;
+
+void main(){
+    uint vertex = uint(gl_VertexIndex);
+    vec2 corner = vec2(float((vertex << 1u) & 2u), float(vertex & 2u));
+    fragNdc = corner * 2.0 - 1.0;
+    gl_Position = vec4(fragNdc, 1.0, 1.0);
+}
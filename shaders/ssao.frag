@@ -0,0 +1,78 @@
+#version 450
+
+// Screen-space ambient occlusion: how much of the hemisphere over each
+// surface the depth buffer says is blocked. 1 is fully open.
+
+const int KERNEL_SIZE = 16;
+const float GOLDEN_ANGLE = 2.39996323;
+// Keeps flat surfaces from occluding themselves.
+const float DEPTH_BIAS = 0.03;
+
+layout(location=0)in vec2 fragUv;
+layout(location=0)out vec4 outColor;
+
+layout(set = 0, binding = 0) uniform texture2D source;
+layout(set = 0, binding = 1) uniform sampler sourceSampler;
+
+layout(push_constant)uniform Push
+{
+    mat4 projection;
+    // x: sampling radius in blocks, y: near plane, z: far plane,
+    // w: how strongly occlusion darkens
+    vec4 params;
+}push;
+
+// The view-space position drawn at `uv`, from the depth buffer of a
+// left-handed zero-to-one perspective projection.
+vec3 viewPosition(vec2 uv){
+    float depth = textureLod(sampler2D(source, sourceSampler), uv, 0.0).r;
+    float near = push.params.y;
+    float far = push.params.z;
+    float z = near * far / (far - depth * (far - near));
+    vec2 ndc = uv * 2.0 - 1.0;
+    return vec3(ndc.x * z / push.projection[0][0], ndc.y * z / push.projection[1][1], z);
+}
+
+// Changes from pixel to pixel, so neighbouring pixels turn the kernel
+// differently and the blur can average the banding away.
+float noise(vec2 pixel){
+    return fract(52.9829189 * fract(dot(pixel, vec2(0.06711056, 0.00583715))));
+}
+
+void main(){
+    vec3 position = viewPosition(fragUv);
+    if (position.z >= push.params.z * 0.99) {
+        // the sky
+        outColor = vec4(1.0);
+        return;
+    }
+    vec3 normal = normalize(cross(dFdy(position), dFdx(position)));
+    if (dot(normal, position) > 0.0) {
+        normal = -normal;
+    }
+    vec3 helper = abs(normal.y) < 0.99 ? vec3(0.0, 1.0, 0.0) : vec3(1.0, 0.0, 0.0);
+    vec3 tangent = normalize(cross(helper, normal));
+    vec3 bitangent = cross(normal, tangent);
+    float radius = push.params.x;
+    float rotation = noise(gl_FragCoord.xy) * 6.28318531;
+    float occlusion = 0.0;
+    for (int i = 0; i < KERNEL_SIZE; i++) {
+        float t = (float(i) + 0.5) / float(KERNEL_SIZE);
+        // spread over the hemisphere, denser towards the surface point
+        float angle = float(i) * GOLDEN_ANGLE + rotation;
+        float height = sqrt(1.0 - t);
+        float spread = sqrt(1.0 - height * height);
+        vec3 direction = tangent * cos(angle) * spread + bitangent * sin(angle) * spread + normal * height;
+        float scale = mix(0.1, 1.0, t * t);
+        vec3 samplePosition = position + direction * radius * scale;
+        vec4 clip = push.projection * vec4(samplePosition, 1.0);
+        vec2 sampleUv = clip.xy / clip.w * 0.5 + 0.5;
+        float sceneDepth = viewPosition(sampleUv).z;
+        float inRange = smoothstep(0.0, 1.0, radius / abs(position.z - sceneDepth));
+        if (sceneDepth <= samplePosition.z - DEPTH_BIAS) {
+            occlusion += inRange;
+        }
+    }
+    float openness = 1.0 - occlusion / float(KERNEL_SIZE);
+    outColor = vec4(vec3(pow(openness, push.params.w)), 1.0);
+}
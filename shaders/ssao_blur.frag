@@ -0,0 +1,22 @@
+#version 450
+
+// Averages the ambient occlusion over 4x4 pixels, so the noise left by
+// turning the kernel from pixel to pixel disappears.
+
+layout(location=0)in vec2 fragUv;
+layout(location=0)out vec4 outColor;
+
+layout(set = 0, binding = 0) uniform texture2D source;
+layout(set = 0, binding = 1) uniform sampler sourceSampler;
+
+void main(){
+    vec2 texel = 1.0 / vec2(textureSize(sampler2D(source, sourceSampler), 0));
+    float sum = 0.0;
+    for (int y = -2; y < 2; y++) {
+        for (int x = -2; x < 2; x++) {
+            vec2 offset = vec2(float(x), float(y)) * texel;
+            sum += texture(sampler2D(source, sourceSampler), fragUv + offset).r;
+        }
+    }
+    outColor = vec4(vec3(sum / 16.0), 1.0);
+}
@@ -0,0 +1,25 @@
+#version 450
+
+layout(location=0)in vec2 fragUv;
+layout(location=0)out vec4 outColor;
+
+layout(set = 0, binding = 0) uniform texture2D source;
+layout(set = 0, binding = 1) uniform sampler sourceSampler;
+
+layout(push_constant)uniform Push
+{
+    // x: exposure
+    vec4 params;
+    vec4 tint;
+}push;
+
+// Narkowicz's fit of the ACES filmic curve: a gentle toe, a shoulder that
+// rolls bright light off towards white instead of clipping it.
+vec3 aces(vec3 color){
+    return clamp(color * (2.51 * color + 0.03) / (color * (2.43 * color + 0.59) + 0.14), 0.0, 1.0);
+}
+
+void main(){
+    vec3 color = texture(sampler2D(source, sourceSampler), fragUv).rgb;
+    outColor = vec4(aces(max(color, vec3(0.0)) * push.params.x), 1.0);
+}
@@ -0,0 +1,17 @@
+#version 450
+
+layout(location=0)in vec2 fragUv;
+layout(location=1)in vec4 fragColor;
+layout(location=0)out vec4 outColor;
+
+layout(set = 0, binding = 0) uniform texture2DArray font;
+layout(set = 0, binding = 1) uniform sampler fontSampler;
+
+void main(){
+    // the atlas is white, so glyphs take the vertex colour
+    float coverage = textureLod(sampler2DArray(font, fontSampler), vec3(fragUv, 0.0), 0.0).a;
+    if (coverage <= 0.0) {
+        discard;
+    }
+    outColor = vec4(fragColor.rgb, fragColor.a * coverage);
+}
@@ -0,0 +1,21 @@
+#version 450
+
+// Places screen-space UI quads, given in pixels from the top left.
+
+layout(location=0)in vec2 position;
+layout(location=1)in vec2 uv;
+layout(location=2)in vec4 color;
+
+layout(location=0)out vec2 fragUv;
+layout(location=1)out vec4 fragColor;
+
+layout(push_constant) uniform Push {
+    // orthographic, one unit per pixel
+    mat4 projection;
+} push;
+
+void main(){
+    gl_Position = push.projection * vec4(position, 0.0, 1.0);
+    fragUv = uv;
+    fragColor = color;
+}
@@ -0,0 +1,31 @@
+#version 450
+
+layout(location=0)in vec2 fragUv;
+layout(location=0)out vec4 outColor;
+
+layout(set = 0, binding = 0) uniform texture2D source;
+layout(set = 0, binding = 1) uniform sampler sourceSampler;
+
+layout(push_constant)uniform Push
+{
+    // x: strength, y: seconds for the ripple
+    vec4 params;
+    // the water fog colour
+    vec4 tint;
+}push;
+
+const float RIPPLE_SIZE = 0.003;
+
+void main(){
+    float time = push.params.y;
+    vec2 ripple = vec2(
+        sin(fragUv.y * 23.0 + time * 1.7),
+        cos(fragUv.x * 19.0 + time * 1.3)
+    ) * RIPPLE_SIZE;
+    vec3 color = texture(sampler2D(source, sourceSampler), fragUv + ripple).rgb;
+    // the tint is linear and the picture is already gamma encoded
+    vec3 tint = pow(max(push.tint.rgb, vec3(0.0)), vec3(1.0 / 2.2));
+    // filter towards the water's hue, keeping its brightest channel
+    vec3 hue = tint / max(max(tint.r, tint.g), max(tint.b, 0.001));
+    outColor = vec4(mix(color, color * hue, push.params.x), 1.0);
+}
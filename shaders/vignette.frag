@@ -0,0 +1,22 @@
+#version 450
+
+layout(location=0)in vec2 fragUv;
+layout(location=0)out vec4 outColor;
+
+layout(set = 0, binding = 0) uniform texture2D source;
+layout(set = 0, binding = 1) uniform sampler sourceSampler;
+
+layout(push_constant)uniform Push
+{
+    // x: how much the corners are darkened
+    vec4 params;
+    vec4 tint;
+}push;
+
+void main(){
+    vec3 color = texture(sampler2D(source, sourceSampler), fragUv).rgb;
+    // 0 at the centre, 1 in the corners
+    float distance = length(fragUv - 0.5) * 1.41421356;
+    float darkening = push.params.x * smoothstep(0.35, 1.0, distance);
+    outColor = vec4(color * (1.0 - darkening), 1.0);
+}
@@ -20,5 +20,47 @@ impl Command {
             command_buffers,
         }
     }
+    pub fn begin_single_time_commands(&self, core: &Core) -> vk::CommandBuffer {
+        let allocate_info = vk::CommandBufferAllocateInfo::builder()
+            .command_buffer_count(1)
+            .command_pool(self.command_pool)
+            .level(vk::CommandBufferLevel::PRIMARY)
+            .build();
+        let begin_info = vk::CommandBufferBeginInfo::builder()
+            .flags(vk::CommandBufferUsageFlags::ONE_TIME_SUBMIT)
+            .build();
+        unsafe {
+            let command_buffer = core
+                .logical_device
+                .allocate_command_buffers(&allocate_info)
+                .expect("Failed to allocate command buffer")[0];
+            core.logical_device
+                .begin_command_buffer(command_buffer, &begin_info)
+                .expect("Failed to begin command buffer");
+            command_buffer
+        }
+    }
+    pub fn end_single_time_commands(&self, core: &Core, command_buffer: vk::CommandBuffer) {
+        let command_buffers = [command_buffer];
+        let submit_info = vk::SubmitInfo::builder()
+            .command_buffers(&command_buffers)
+            .build();
+        unsafe {
+            core.logical_device
+                .end_command_buffer(command_buffer)
+                .expect("Failed to end command buffer");
+            core.logical_device
+                .queue_submit(
+                    core.queue_families.graphics_queue,
+                    &[submit_info],
+                    vk::Fence::null(),
+                )
+                .expect("Failed to submit command buffer");
+            core.logical_device
+                .queue_wait_idle(core.queue_families.graphics_queue)
+                .expect("Failed to wait for queue");
+            core.logical_device
+                .free_command_buffers(self.command_pool, &command_buffers);
+        }
+    }
 }
-
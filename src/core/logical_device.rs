@@ -11,6 +11,7 @@ pub fn create_logical_device(
     instance: &ash::Instance,
     physical_device: &vk::PhysicalDevice,
     surface: &Surface,
+    optional_extensions: &[&str],
 ) -> ash::Device {
     let mut queue_create_infos: Vec<vk::DeviceQueueCreateInfo> = vec![];
     let unique_indices =
@@ -24,8 +25,12 @@ pub fn create_logical_device(
     }
     let queue_create_infos = queue_create_infos;
 
+    let supported_features = unsafe { instance.get_physical_device_features(*physical_device) };
     let device_features = vk::PhysicalDeviceFeatures::builder()
         .sampler_anisotropy(true)
+        .fill_mode_non_solid(supported_features.fill_mode_non_solid == vk::TRUE)
+        .multi_draw_indirect(supported_features.multi_draw_indirect == vk::TRUE)
+        .draw_indirect_first_instance(supported_features.draw_indirect_first_instance == vk::TRUE)
         .build();
 
     let requred_validation_layer_raw_names: Vec<CString> = LAYER_NAME
@@ -39,6 +44,7 @@ pub fn create_logical_device(
 
     let device_extensions_raw_names: Vec<CString> = DEVICE_EXTENSIONS
         .iter()
+        .chain(optional_extensions.iter())
         .map(|extension| CString::new(*extension).unwrap())
         .collect();
     let device_extensions_names: Vec<*const i8> = device_extensions_raw_names
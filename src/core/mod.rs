@@ -5,19 +5,23 @@ mod logical_device;
 mod instance;
 mod debug;
 mod surface;
+mod pipeline_cache;
 
 const DEVICE_EXTENSIONS: [&'static str; 1] = ["VK_KHR_swapchain"];
+const DRAW_INDIRECT_COUNT_EXTENSION: &str = "VK_KHR_draw_indirect_count";
 
 use logical_device::create_logical_device;
 use crate::window::Window;
 use ash::extensions::ext::DebugUtils;
+use ash::extensions::khr::DrawIndirectCount;
 use ash::vk;
 use ash::Instance;
 
 use debug::Debug;
 use instance::create_instance;
-use physical_device::pick_physical_device;
+use physical_device::{is_device_extension_supported, pick_physical_device};
 use queue::{QueueFamilies, QueueFamilyIndices};
+use pipeline_cache::PipelineCache;
 use surface::Surface;
 use swap_chain_support::SwapChainSupportDetails;
 use crate::IS_VALIDATION_LAYERS_ENABLED;
@@ -30,6 +34,10 @@ pub struct Core {
     pub logical_device: ash::Device,
     pub queue_families: QueueFamilies,
     pub debug: Option<Debug>,
+    pub pipeline_cache: PipelineCache,
+    /// Loaded when the device can draw a GPU-written number of indexed
+    /// indirect commands, each with its own `first_instance`.
+    pub draw_indirect_count: Option<DrawIndirectCount>,
 }
 impl Core {
     pub fn new(window: &Window) -> Self{
@@ -51,8 +59,25 @@ impl Core {
         let surface = Surface::new(&entry, &instance, &window.window);
 
         let physical_device = pick_physical_device(&instance, &surface, &mut indices);
-        let logical_device = create_logical_device(&instance, &physical_device, &surface);
+        let supported_features = unsafe { instance.get_physical_device_features(physical_device) };
+        let supports_draw_indirect_count =
+            is_device_extension_supported(&instance, &physical_device, DRAW_INDIRECT_COUNT_EXTENSION)
+                && supported_features.multi_draw_indirect == vk::TRUE
+                && supported_features.draw_indirect_first_instance == vk::TRUE;
+        let optional_extensions: &[&str] = if supports_draw_indirect_count {
+            &[DRAW_INDIRECT_COUNT_EXTENSION]
+        } else {
+            &[]
+        };
+        let logical_device =
+            create_logical_device(&instance, &physical_device, &surface, optional_extensions);
+        let draw_indirect_count = if supports_draw_indirect_count {
+            Some(DrawIndirectCount::new(&instance, &logical_device))
+        } else {
+            None
+        };
         let queue_families = QueueFamilies::from(indices, &logical_device);
+        let pipeline_cache = PipelineCache::new(&instance, &physical_device, &logical_device);
             Core {
                 entry,
                 instance,
@@ -62,6 +87,8 @@ impl Core {
                 logical_device,
                 queue_families,
                 surface,
+                pipeline_cache,
+                draw_indirect_count,
             }
 
     }
@@ -109,13 +136,26 @@ impl Core {
             })
             .map(|(index, _)| index as u32)
     }
+    /// The sample counts both colour and depth attachments support.
+    pub fn supported_sample_counts(&self) -> vk::SampleCountFlags {
+        let limits = unsafe {
+            self.instance
+                .get_physical_device_properties(self.physical_device)
+                .limits
+        };
+        limits.framebuffer_color_sample_counts & limits.framebuffer_depth_sample_counts
+    }
 
 }
 
 impl Drop for Core {
     fn drop(&mut self) {
         println!("dropping core");
+        self.pipeline_cache
+            .save(&self.instance, &self.physical_device, &self.logical_device);
         unsafe {
+            self.logical_device
+                .destroy_pipeline_cache(self.pipeline_cache.handle, None);
             self.logical_device.destroy_device(None);
              
             match self.debug {
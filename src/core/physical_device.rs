@@ -52,16 +52,17 @@ fn is_device_is_suitable(instance:&Instance,physical_device:&vk::PhysicalDevice,
     false
 }
 fn check_device_extension_support(instance:&Instance,physical_device:&vk::PhysicalDevice)->bool{
+    DEVICE_EXTENSIONS.iter()
+        .all(|extension| is_device_extension_supported(instance,physical_device,extension))
+}
+pub fn is_device_extension_supported(instance:&Instance,physical_device:&vk::PhysicalDevice,extension:&str)->bool{
     let available_extensions = unsafe{
         instance.enumerate_device_extension_properties(*physical_device)
             .expect("Failed to enumerate device extensions")
     };
-    DEVICE_EXTENSIONS.iter()
-        .all(|extension| {
-            available_extensions.iter().any(|&i| {
-                *extension ==  unsafe{ CStr::from_ptr(i.extension_name.as_ptr())}
-                .to_str()
-                .unwrap()
-            })
-        })
-}
\ No newline at end of file
+    available_extensions.iter().any(|&i| {
+        extension ==  unsafe{ CStr::from_ptr(i.extension_name.as_ptr())}
+        .to_str()
+        .unwrap()
+    })
+}
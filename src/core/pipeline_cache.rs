@@ -0,0 +1,147 @@
+use ash::vk;
+use std::path::PathBuf;
+
+const PIPELINE_CACHE_FILE: &str = "pipeline_cache.bin";
+/// Size of `VkPipelineCacheHeaderVersionOne`.
+const VULKAN_HEADER_SIZE: usize = 32;
+
+/// A `vk::PipelineCache` that is loaded from disk at startup and written back
+/// on shutdown. The file starts with the driver version followed by the data
+/// returned by `get_pipeline_cache_data`.
+pub struct PipelineCache {
+    pub handle: vk::PipelineCache,
+}
+impl PipelineCache {
+    pub fn new(
+        instance: &ash::Instance,
+        physical_device: &vk::PhysicalDevice,
+        logical_device: &ash::Device,
+    ) -> Self {
+        let properties = unsafe { instance.get_physical_device_properties(*physical_device) };
+        let initial_data = match std::fs::read(cache_path()) {
+            Ok(file) => match validate_cache_file(&file, &properties) {
+                Ok(data) => data.to_vec(),
+                Err(reason) => {
+                    println!("Discarding pipeline cache: {}", reason);
+                    vec![]
+                }
+            },
+            Err(_) => vec![],
+        };
+        let create_info = vk::PipelineCacheCreateInfo::builder()
+            .initial_data(&initial_data)
+            .build();
+        let handle = unsafe {
+            logical_device
+                .create_pipeline_cache(&create_info, None)
+                .expect("Failed to create pipeline cache")
+        };
+        PipelineCache { handle }
+    }
+    pub fn save(
+        &self,
+        instance: &ash::Instance,
+        physical_device: &vk::PhysicalDevice,
+        logical_device: &ash::Device,
+    ) {
+        let properties = unsafe { instance.get_physical_device_properties(*physical_device) };
+        let data = match unsafe { logical_device.get_pipeline_cache_data(self.handle) } {
+            Ok(data) => data,
+            Err(err) => {
+                eprintln!("Failed to read pipeline cache data: {:?}", err);
+                return;
+            }
+        };
+        let mut file = properties.driver_version.to_le_bytes().to_vec();
+        file.extend(data);
+        if let Err(err) = std::fs::write(cache_path(), file) {
+            eprintln!("Failed to save pipeline cache: {}", err);
+        }
+    }
+}
+
+fn cache_path() -> PathBuf {
+    match std::env::current_exe() {
+        Ok(exe) => exe.with_file_name(PIPELINE_CACHE_FILE),
+        Err(_) => PathBuf::from(PIPELINE_CACHE_FILE),
+    }
+}
+
+fn read_u32(bytes: &[u8], offset: usize) -> u32 {
+    u32::from_le_bytes([
+        bytes[offset],
+        bytes[offset + 1],
+        bytes[offset + 2],
+        bytes[offset + 3],
+    ])
+}
+
+/// Checks a saved cache file against the current device and returns the
+/// Vulkan cache data inside it.
+pub fn validate_cache_file<'a>(
+    file: &'a [u8],
+    properties: &vk::PhysicalDeviceProperties,
+) -> Result<&'a [u8], &'static str> {
+    if file.len() < 4 + VULKAN_HEADER_SIZE {
+        return Err("file is too short");
+    }
+    if read_u32(file, 0) != properties.driver_version {
+        return Err("driver version changed");
+    }
+    let data = &file[4..];
+    if (read_u32(data, 0) as usize) < VULKAN_HEADER_SIZE {
+        return Err("header is too short");
+    }
+    if read_u32(data, 4) != vk::PipelineCacheHeaderVersion::ONE.as_raw() as u32 {
+        return Err("unknown header version");
+    }
+    if read_u32(data, 8) != properties.vendor_id || read_u32(data, 12) != properties.device_id {
+        return Err("device changed");
+    }
+    if data[16..32] != properties.pipeline_cache_uuid {
+        return Err("pipeline cache UUID changed");
+    }
+    Ok(data)
+}
+
+#[cfg(test)]
+mod pipeline_cache_tests {
+    use super::*;
+    fn properties() -> vk::PhysicalDeviceProperties {
+        vk::PhysicalDeviceProperties {
+            driver_version: 7,
+            vendor_id: 0x10de,
+            device_id: 0x2204,
+            pipeline_cache_uuid: [3; vk::UUID_SIZE],
+            ..Default::default()
+        }
+    }
+    fn cache_file(properties: &vk::PhysicalDeviceProperties) -> Vec<u8> {
+        let mut file = properties.driver_version.to_le_bytes().to_vec();
+        file.extend(32_u32.to_le_bytes());
+        file.extend(1_u32.to_le_bytes());
+        file.extend(properties.vendor_id.to_le_bytes());
+        file.extend(properties.device_id.to_le_bytes());
+        file.extend(properties.pipeline_cache_uuid);
+        file.extend([0xab; 8]);
+        file
+    }
+    #[test]
+    fn accepts_cache_from_same_device() {
+        let properties = properties();
+        let file = cache_file(&properties);
+        assert_eq!(validate_cache_file(&file, &properties), Ok(&file[4..]));
+    }
+    #[test]
+    fn rejects_cache_from_other_driver_or_device() {
+        let properties = properties();
+        let file = cache_file(&properties);
+        let mut new_driver = properties;
+        new_driver.driver_version += 1;
+        assert!(validate_cache_file(&file, &new_driver).is_err());
+        let mut new_uuid = properties;
+        new_uuid.pipeline_cache_uuid[0] = 9;
+        assert!(validate_cache_file(&file, &new_uuid).is_err());
+        assert!(validate_cache_file(&file[..20], &properties).is_err());
+    }
+}
@@ -0,0 +1,198 @@
+pub const TEXTURE_SIZE: u32 = 16;
+/// Array layers the texture animation table has room for, matching
+/// `TEXTURE_LAYERS` in the block shaders.
+pub const MAX_TEXTURE_LAYERS: u32 = 64;
+
+/// How an animated texture cycles through its frames, which lie in
+/// consecutive array layers starting at `BlockTexture::layer`.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct TextureAnimation {
+    pub frames: u32,
+    /// Seconds each frame is shown.
+    pub frame_time: f32,
+}
+
+/// Every block texture, in array-layer order.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum BlockTexture {
+    Stone,
+    Dirt,
+    GrassTop,
+    GrassSide,
+    Water,
+    Glass,
+    Ice,
+    Leaves,
+    TallGrass,
+    Flower,
+    Lava,
+    Fire,
+}
+impl BlockTexture {
+    pub const ALL: [BlockTexture; 12] = [
+        BlockTexture::Stone,
+        BlockTexture::Dirt,
+        BlockTexture::GrassTop,
+        BlockTexture::GrassSide,
+        BlockTexture::Water,
+        BlockTexture::Glass,
+        BlockTexture::Ice,
+        BlockTexture::Leaves,
+        BlockTexture::TallGrass,
+        BlockTexture::Flower,
+        BlockTexture::Lava,
+        BlockTexture::Fire,
+    ];
+    /// The frame strip of an animated texture, `None` for a still one.
+    pub fn animation(&self) -> Option<TextureAnimation> {
+        match self {
+            BlockTexture::Water => Some(TextureAnimation {
+                frames: 8,
+                frame_time: 0.15,
+            }),
+            BlockTexture::Lava => Some(TextureAnimation {
+                frames: 8,
+                frame_time: 0.3,
+            }),
+            BlockTexture::Fire => Some(TextureAnimation {
+                frames: 8,
+                frame_time: 0.08,
+            }),
+            _ => None,
+        }
+    }
+    pub fn frame_count(&self) -> u32 {
+        self.animation().map_or(1, |animation| animation.frames)
+    }
+    /// The array layer of the first frame. Frames of the textures before
+    /// this one come first.
+    pub fn layer(&self) -> u32 {
+        BlockTexture::ALL[..*self as usize]
+            .iter()
+            .map(|texture| texture.frame_count())
+            .sum()
+    }
+    /// Generates the RGBA8 pixels for one frame of this texture. There are
+    /// no texture assets yet, so every texture is a tinted noise pattern;
+    /// animated ones scroll or flicker from frame to frame.
+    pub fn pixels(&self, frame: u32) -> Vec<u8> {
+        let seed = *self as u32;
+        let mut pixels = Vec::with_capacity((TEXTURE_SIZE * TEXTURE_SIZE * 4) as usize);
+        for y in 0..TEXTURE_SIZE {
+            for x in 0..TEXTURE_SIZE {
+                let noise = match self {
+                    // ripples drift down the texture, two rows a frame
+                    BlockTexture::Water => {
+                        hash(x, (y + TEXTURE_SIZE - frame * 2) % TEXTURE_SIZE, seed)
+                    }
+                    BlockTexture::Lava => hash(x / 2 + frame, y / 2, seed),
+                    BlockTexture::Fire => hash(x, y / 3, seed + frame),
+                    _ => hash(x, y, seed),
+                } as f32
+                    / 255.0;
+                let base = match self {
+                    BlockTexture::Stone => [120, 120, 120],
+                    BlockTexture::Dirt => [121, 85, 58],
+                    BlockTexture::GrassTop => [95, 159, 53],
+                    BlockTexture::GrassSide => {
+                        if y < 4 + hash(x, 0, 7) as u32 % 3 {
+                            [95, 159, 53]
+                        } else {
+                            [121, 85, 58]
+                        }
+                    }
+                    BlockTexture::Water => [38, 92, 196],
+                    BlockTexture::Glass => [200, 225, 230],
+                    BlockTexture::Ice => [150, 190, 240],
+                    BlockTexture::Leaves | BlockTexture::TallGrass => [60, 130, 40],
+                    BlockTexture::Flower if y < 6 => [220, 60, 50],
+                    BlockTexture::Flower => [60, 130, 40],
+                    BlockTexture::Lava if noise > 0.7 => [255, 200, 60],
+                    BlockTexture::Lava => [207, 74, 16],
+                    // yellow at the base, orange at the tips
+                    BlockTexture::Fire if y > 10 => [255, 210, 80],
+                    BlockTexture::Fire => [250, 120, 20],
+                };
+                let edge = x == 0 || y == 0 || x == TEXTURE_SIZE - 1 || y == TEXTURE_SIZE - 1;
+                let alpha = match self {
+                    BlockTexture::Water => 150,
+                    // a solid frame around a nearly clear pane
+                    BlockTexture::Glass if edge => 255,
+                    BlockTexture::Glass => 40,
+                    BlockTexture::Ice => 190,
+                    BlockTexture::Leaves if noise < 0.3 => 0,
+                    BlockTexture::TallGrass if blade(x, y, 3) => 255,
+                    BlockTexture::Flower if y < 6 && (5..11).contains(&x) => 255,
+                    BlockTexture::Flower if (7..9).contains(&x) => 255,
+                    BlockTexture::TallGrass | BlockTexture::Flower => 0,
+                    // tongues of flame lick up from the bottom edge
+                    BlockTexture::Fire if y >= flame_height(x, frame) => 255,
+                    BlockTexture::Fire => 0,
+                    _ => 255,
+                };
+                let shade = 0.8 + 0.2 * noise;
+                pixels.extend(base.iter().map(|c| (*c as f32 * shade) as u8));
+                pixels.push(alpha);
+            }
+        }
+        pixels
+    }
+}
+/// The pixels of every frame of every texture, one entry per array layer.
+pub fn all_texture_pixels() -> Vec<Vec<u8>> {
+    BlockTexture::ALL
+        .iter()
+        .flat_map(|texture| (0..texture.frame_count()).map(|frame| texture.pixels(frame)))
+        .collect()
+}
+/// The frame count and frame time of every array layer, laid out as
+/// `TextureAnimations` in the block shaders. Only the first layer of each
+/// texture is read, and still textures have a single frame.
+pub fn texture_animation_table() -> Vec<[f32; 4]> {
+    let mut table = vec![[1.0, 1.0, 0.0, 0.0]; MAX_TEXTURE_LAYERS as usize];
+    for texture in BlockTexture::ALL.iter() {
+        if let Some(animation) = texture.animation() {
+            table[texture.layer() as usize] =
+                [animation.frames as f32, animation.frame_time, 0.0, 0.0];
+        }
+    }
+    table
+}
+/// The lowest row of a flame in column `x`, changing every frame.
+fn flame_height(x: u32, frame: u32) -> u32 {
+    let centre = x.abs_diff(TEXTURE_SIZE / 2);
+    2 + centre / 2 + hash(x, frame, 11) as u32 % 4
+}
+/// Whether `(x, y)` lies on one of the thin blades of a grass texture, each
+/// column reaching a different height. `every` spaces the blades out.
+fn blade(x: u32, y: u32, every: u32) -> bool {
+    x % every == 1 && y >= 4 + hash(x, 1, 9) as u32 % 8
+}
+fn hash(x: u32, y: u32, seed: u32) -> u8 {
+    let mut h = x.wrapping_mul(374761393) ^ y.wrapping_mul(668265263) ^ seed.wrapping_mul(2246822519);
+    h = (h ^ (h >> 13)).wrapping_mul(1274126177);
+    (h ^ (h >> 16)) as u8
+}
+
+#[cfg(test)]
+mod block_textures_tests {
+    use super::*;
+    #[test]
+    fn frames_follow_each_other_in_the_array() {
+        let layers = all_texture_pixels();
+        let total: u32 = BlockTexture::ALL
+            .iter()
+            .map(|texture| texture.frame_count())
+            .sum();
+        assert_eq!(layers.len(), total as usize);
+        assert!(total <= MAX_TEXTURE_LAYERS);
+        assert_eq!(BlockTexture::Stone.layer(), 0);
+        assert_eq!(BlockTexture::Glass.layer(), BlockTexture::Water.layer() + 8);
+        let water = BlockTexture::Water.layer() as usize;
+        assert_eq!(layers[water], BlockTexture::Water.pixels(0));
+        assert_ne!(layers[water], layers[water + 1]);
+        let table = texture_animation_table();
+        assert_eq!(table[water], [8.0, 0.15, 0.0, 0.0]);
+        assert_eq!(table[BlockTexture::Stone.layer() as usize][0], 1.0);
+    }
+}
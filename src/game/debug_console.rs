@@ -0,0 +1,217 @@
+use super::settings::{CloudMode, Msaa, PostEffect};
+use super::world::weather::Weather;
+use super::world_time::TimeOfDay;
+use crate::render_systems::particle_system::ParticleKind;
+use std::io::{self, BufRead};
+use std::str::FromStr;
+use std::sync::mpsc;
+use std::thread;
+
+/// Particles spawned by `particles` when no count is given.
+const DEFAULT_PARTICLE_COUNT: u32 = 64;
+
+/// A command typed into the debug console.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum DebugCommand {
+    /// `time set <dawn|noon|dusk|midnight>`
+    SetTime(TimeOfDay),
+    /// `clouds <off|fast|fancy>`
+    SetClouds(CloudMode),
+    /// `weather <clear|rain|thunder|snow>`, until the next change of weather
+    SetWeather(Weather),
+    /// `particles <debris|rain|smoke|flame|snow> [count]`
+    SpawnParticles(ParticleKind, u32),
+    /// `post <tonemap|gamma|fxaa|vignette|underwater> <on|off>`
+    SetPostEffect(PostEffect, bool),
+    /// `exposure <multiplier>`
+    SetExposure(f32),
+    /// `msaa <off|2x|4x|8x>`
+    SetMsaa(Msaa),
+    /// `ssao <on|off>`
+    SetSsao(bool),
+    /// `ssao radius <blocks>`
+    SetSsaoRadius(f32),
+    /// `render distance <blocks>`
+    SetRenderDistance(f32),
+}
+impl FromStr for DebugCommand {
+    type Err = String;
+    fn from_str(line: &str) -> Result<Self, Self::Err> {
+        let words: Vec<&str> = line.split_whitespace().collect();
+        match words.as_slice() {
+            ["time", "set", time_of_day] => time_of_day.parse().map(DebugCommand::SetTime),
+            ["clouds", mode] => mode.parse().map(DebugCommand::SetClouds),
+            ["weather", weather] => weather.parse().map(DebugCommand::SetWeather),
+            ["particles", kind] => kind
+                .parse()
+                .map(|kind| DebugCommand::SpawnParticles(kind, DEFAULT_PARTICLE_COUNT)),
+            ["particles", kind, count] => {
+                let count = count
+                    .parse()
+                    .map_err(|_| format!("Invalid particle count '{}'", count))?;
+                kind.parse()
+                    .map(|kind| DebugCommand::SpawnParticles(kind, count))
+            }
+            ["post", effect, switch] => {
+                let enabled = parse_switch(switch)?;
+                effect
+                    .parse()
+                    .map(|effect| DebugCommand::SetPostEffect(effect, enabled))
+            }
+            ["exposure", exposure] => match exposure.parse::<f32>() {
+                Ok(exposure) if exposure > 0.0 => Ok(DebugCommand::SetExposure(exposure)),
+                _ => Err(format!("Invalid exposure '{}'", exposure)),
+            },
+            ["msaa", msaa] => msaa.parse().map(DebugCommand::SetMsaa),
+            ["ssao", "radius", radius] => match radius.parse::<f32>() {
+                Ok(radius) if radius > 0.0 => Ok(DebugCommand::SetSsaoRadius(radius)),
+                _ => Err(format!("Invalid SSAO radius '{}'", radius)),
+            },
+            ["ssao", switch] => parse_switch(switch).map(DebugCommand::SetSsao),
+            ["render", "distance", blocks] => match blocks.parse::<f32>() {
+                Ok(blocks) if blocks > 0.0 => Ok(DebugCommand::SetRenderDistance(blocks)),
+                _ => Err(format!("Invalid render distance '{}'", blocks)),
+            },
+            _ => Err(format!("Unknown command '{}'", line.trim())),
+        }
+    }
+}
+
+fn parse_switch(switch: &str) -> Result<bool, String> {
+    match switch {
+        "on" => Ok(true),
+        "off" => Ok(false),
+        _ => Err(format!("Expected on or off, not '{}'", switch)),
+    }
+}
+
+/// Reads debug commands, one per line, from the terminal the game was
+/// started from. Lines are read on a background thread so the game loop
+/// never blocks on stdin.
+pub struct DebugConsole {
+    lines: mpsc::Receiver<String>,
+}
+impl DebugConsole {
+    pub fn from_stdin() -> Self {
+        let (sender, lines) = mpsc::channel();
+        thread::spawn(move || {
+            for line in io::stdin().lock().lines().map_while(Result::ok) {
+                if sender.send(line).is_err() {
+                    break;
+                }
+            }
+        });
+        DebugConsole { lines }
+    }
+    /// The commands entered since the last poll. Lines that are not a
+    /// command are reported and skipped.
+    pub fn poll(&self) -> Vec<DebugCommand> {
+        self.lines
+            .try_iter()
+            .filter(|line| !line.trim().is_empty())
+            .filter_map(|line| match line.parse() {
+                Ok(command) => Some(command),
+                Err(error) => {
+                    eprintln!("{}", error);
+                    None
+                }
+            })
+            .collect()
+    }
+}
+
+#[cfg(test)]
+mod debug_console_tests {
+    use super::*;
+    #[test]
+    fn parses_time_set() {
+        assert_eq!(
+            "time set noon".parse(),
+            Ok(DebugCommand::SetTime(TimeOfDay::Noon))
+        );
+        assert_eq!(
+            "  time   set midnight ".parse(),
+            Ok(DebugCommand::SetTime(TimeOfDay::Midnight))
+        );
+        assert!("time set".parse::<DebugCommand>().is_err());
+        assert!("time set elevenses".parse::<DebugCommand>().is_err());
+        assert!("sky clear".parse::<DebugCommand>().is_err());
+    }
+    #[test]
+    fn parses_clouds() {
+        assert_eq!(
+            "clouds fast".parse(),
+            Ok(DebugCommand::SetClouds(CloudMode::Fast))
+        );
+        assert!("clouds thick".parse::<DebugCommand>().is_err());
+    }
+    #[test]
+    fn parses_weather() {
+        assert_eq!(
+            "weather thunder".parse(),
+            Ok(DebugCommand::SetWeather(Weather::Thunder))
+        );
+        assert!("weather hail".parse::<DebugCommand>().is_err());
+    }
+    #[test]
+    fn parses_particles() {
+        assert_eq!(
+            "particles smoke".parse(),
+            Ok(DebugCommand::SpawnParticles(
+                ParticleKind::Smoke,
+                DEFAULT_PARTICLE_COUNT
+            ))
+        );
+        assert_eq!(
+            "particles rain 500".parse(),
+            Ok(DebugCommand::SpawnParticles(ParticleKind::Rain, 500))
+        );
+        assert!("particles rain lots".parse::<DebugCommand>().is_err());
+        assert!("particles glitter 5".parse::<DebugCommand>().is_err());
+    }
+    #[test]
+    fn parses_post_effects_and_exposure() {
+        assert_eq!(
+            "post fxaa off".parse(),
+            Ok(DebugCommand::SetPostEffect(PostEffect::Fxaa, false))
+        );
+        assert_eq!(
+            "post underwater on".parse(),
+            Ok(DebugCommand::SetPostEffect(
+                PostEffect::UnderwaterTint,
+                true
+            ))
+        );
+        assert!("post fxaa maybe".parse::<DebugCommand>().is_err());
+        assert!("post bloom on".parse::<DebugCommand>().is_err());
+        assert_eq!("exposure 1.5".parse(), Ok(DebugCommand::SetExposure(1.5)));
+        assert!("exposure -1".parse::<DebugCommand>().is_err());
+    }
+    #[test]
+    fn parses_msaa() {
+        assert_eq!("msaa 8x".parse(), Ok(DebugCommand::SetMsaa(Msaa::X8)));
+        assert_eq!("msaa off".parse(), Ok(DebugCommand::SetMsaa(Msaa::Off)));
+        assert!("msaa 16x".parse::<DebugCommand>().is_err());
+    }
+    #[test]
+    fn parses_ssao() {
+        assert_eq!("ssao on".parse(), Ok(DebugCommand::SetSsao(true)));
+        assert_eq!("ssao off".parse(), Ok(DebugCommand::SetSsao(false)));
+        assert_eq!(
+            "ssao radius 2.5".parse(),
+            Ok(DebugCommand::SetSsaoRadius(2.5))
+        );
+        assert!("ssao radius 0".parse::<DebugCommand>().is_err());
+        assert!("ssao radius wide".parse::<DebugCommand>().is_err());
+        assert!("ssao maybe".parse::<DebugCommand>().is_err());
+    }
+    #[test]
+    fn parses_render_distance() {
+        assert_eq!(
+            "render distance 96".parse(),
+            Ok(DebugCommand::SetRenderDistance(96.0))
+        );
+        assert!("render distance -1".parse::<DebugCommand>().is_err());
+        assert!("render distance far".parse::<DebugCommand>().is_err());
+    }
+}
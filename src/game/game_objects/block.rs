@@ -1,6 +1,7 @@
-use ash::vk;
+use nalgebra_glm as glm;
+use super::frustum::Aabb;
 use super::GameObject;
-struct Block{
+pub struct Block{
 
 }
 impl GameObject for Block{
@@ -8,9 +9,11 @@ impl GameObject for Block{
         Block{
         }
     }
-    fn bind(&self,command_buffer:& vk::CommandBuffer){
+    fn bounding_box(&self)->Aabb{
+        Aabb::new(glm::vec3(-0.5, -0.5, -0.5), glm::vec3(0.5, 0.5, 0.5))
     }
-    fn draw(&self){
+    fn color(&self)->glm::Vec3{
+        glm::vec3(0.8, 0.3, 0.2)
     }
     fn update(&self){
     }
@@ -0,0 +1,124 @@
+use nalgebra_glm as glm;
+
+/// Axis-aligned bounding box in world space.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Aabb {
+    pub min: glm::Vec3,
+    pub max: glm::Vec3,
+}
+impl Aabb {
+    pub fn new(min: glm::Vec3, max: glm::Vec3) -> Self {
+        Aabb { min, max }
+    }
+    /// Whether `point` lies inside the box grown by `margin` on every side.
+    pub fn contains_point(&self, point: &glm::Vec3, margin: f32) -> bool {
+        (0..3).all(|axis| {
+            point[axis] >= self.min[axis] - margin && point[axis] <= self.max[axis] + margin
+        })
+    }
+}
+
+/// The six planes of a view frustum, each stored as `(normal, distance)` in
+/// a `Vec4` with the normal pointing into the frustum.
+#[derive(Debug, Clone, Copy)]
+pub struct Frustum {
+    pub planes: [glm::Vec4; 6],
+}
+impl Frustum {
+    /// Extracts the planes from `projection * view` (Gribb-Hartmann). The
+    /// near plane assumes the zero-to-one depth range of `perspective_lh_zo`.
+    pub fn from_matrix(proj_view: &glm::Mat4) -> Self {
+        let row = |i: usize| -> glm::Vec4 { proj_view.row(i).transpose() };
+        let (r0, r1, r2, r3) = (row(0), row(1), row(2), row(3));
+        let mut planes = [r3 + r0, r3 - r0, r3 + r1, r3 - r1, r2, r3 - r2];
+        for plane in planes.iter_mut() {
+            let length = glm::length(&plane.xyz());
+            *plane /= length;
+        }
+        Frustum { planes }
+    }
+    fn distance(plane: &glm::Vec4, point: &glm::Vec3) -> f32 {
+        plane.x * point.x + plane.y * point.y + plane.z * point.z + plane.w
+    }
+    pub fn contains_point(&self, point: &glm::Vec3) -> bool {
+        self.planes
+            .iter()
+            .all(|plane| Frustum::distance(plane, point) >= 0.0)
+    }
+    /// Conservative test: false only when the box is completely outside one
+    /// of the planes.
+    pub fn intersects_aabb(&self, aabb: &Aabb) -> bool {
+        self.planes.iter().all(|plane| {
+            // the corner furthest along the plane normal
+            let positive = glm::vec3(
+                if plane.x >= 0.0 { aabb.max.x } else { aabb.min.x },
+                if plane.y >= 0.0 { aabb.max.y } else { aabb.min.y },
+                if plane.z >= 0.0 { aabb.max.z } else { aabb.min.z },
+            );
+            Frustum::distance(plane, &positive) >= 0.0
+        })
+    }
+}
+
+/// How many drawables passed and failed culling in the last frame.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct CullingStats {
+    pub drawn: u32,
+    pub culled: u32,
+}
+
+#[cfg(test)]
+mod frustum_tests {
+    use super::*;
+    // matches Game::reset_perspective and Camera::set_view_direction
+    fn frustum(position: glm::Vec3, target: glm::Vec3) -> Frustum {
+        let projection = glm::perspective_lh_zo(800.0 / 600.0, 50_f32.to_radians(), 0.1, 100.0);
+        let view = glm::look_at_lh(&position, &target, &glm::vec3(0.0, 1.0, 0.0));
+        Frustum::from_matrix(&(projection * view))
+    }
+    fn unit_box(center: glm::Vec3) -> Aabb {
+        Aabb::new(
+            center - glm::vec3(0.5, 0.5, 0.5),
+            center + glm::vec3(0.5, 0.5, 0.5),
+        )
+    }
+    #[test]
+    fn sees_what_the_camera_looks_at() {
+        let frustum = frustum(glm::vec3(0.0, 0.0, 0.0), glm::vec3(0.0, 0.0, 1.0));
+        assert!(frustum.contains_point(&glm::vec3(0.0, 0.0, 10.0)));
+        assert!(frustum.intersects_aabb(&unit_box(glm::vec3(0.0, 0.0, 10.0))));
+        assert!(!frustum.contains_point(&glm::vec3(0.0, 0.0, -10.0)));
+        assert!(!frustum.intersects_aabb(&unit_box(glm::vec3(0.0, 0.0, -10.0))));
+    }
+    #[test]
+    fn culls_beyond_near_far_and_sides() {
+        let frustum = frustum(glm::vec3(0.0, 0.0, 0.0), glm::vec3(0.0, 0.0, 1.0));
+        assert!(!frustum.contains_point(&glm::vec3(0.0, 0.0, 0.05)));
+        assert!(!frustum.contains_point(&glm::vec3(0.0, 0.0, 150.0)));
+        // 50 degrees vertical field of view: 45 degrees up is outside
+        assert!(!frustum.contains_point(&glm::vec3(0.0, 10.0, 10.0)));
+        assert!(!frustum.contains_point(&glm::vec3(20.0, 0.0, 10.0)));
+        assert!(!frustum.contains_point(&glm::vec3(-20.0, 0.0, 10.0)));
+        assert!(frustum.contains_point(&glm::vec3(0.0, 4.0, 10.0)));
+    }
+    #[test]
+    fn boxes_straddling_a_plane_are_kept() {
+        let frustum = frustum(glm::vec3(0.0, 0.0, 0.0), glm::vec3(0.0, 0.0, 1.0));
+        let straddling = Aabb::new(glm::vec3(-50.0, -1.0, 5.0), glm::vec3(-1.0, 1.0, 6.0));
+        assert!(frustum.intersects_aabb(&straddling));
+    }
+    #[test]
+    fn follows_the_camera_direction() {
+        let frustum = frustum(glm::vec3(10.0, 5.0, 10.0), glm::vec3(20.0, 5.0, 10.0));
+        assert!(frustum.intersects_aabb(&unit_box(glm::vec3(30.0, 5.0, 10.0))));
+        assert!(!frustum.intersects_aabb(&unit_box(glm::vec3(0.0, 5.0, 10.0))));
+        assert!(!frustum.intersects_aabb(&unit_box(glm::vec3(10.0, 5.0, 30.0))));
+    }
+    #[test]
+    fn aabb_contains_points_within_the_margin() {
+        let aabb = unit_box(glm::vec3(0.0, 0.0, 0.0));
+        assert!(aabb.contains_point(&glm::vec3(0.4, -0.4, 0.0), 0.0));
+        assert!(!aabb.contains_point(&glm::vec3(0.0, 1.2, 0.0), 0.0));
+        assert!(aabb.contains_point(&glm::vec3(0.0, 1.2, 0.0), 1.0));
+    }
+}
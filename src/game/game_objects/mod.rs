@@ -1,6 +1,7 @@
 pub mod camera;
 pub mod key_event;
 pub mod block;
+pub mod frustum;
 use crate::offset_of;
 use nalgebra_glm as glm;
 use ash::vk;
@@ -11,10 +12,25 @@ pub (super) use super::STATIC_MOVE_SPEED;
 pub struct Vertex {
     pub position: glm::Vec3,
     pub color: glm::Vec3,
+    pub uv: glm::Vec2,
+    pub texture_layer: u32,
+    pub normal: glm::Vec3,
 }
 impl Vertex {
-    pub fn new(position: glm::Vec3, color: glm::Vec3) -> Self {
-        Vertex { position, color }
+    pub fn new(
+        position: glm::Vec3,
+        color: glm::Vec3,
+        uv: glm::Vec2,
+        texture_layer: u32,
+        normal: glm::Vec3,
+    ) -> Self {
+        Vertex {
+            position,
+            color,
+            uv,
+            texture_layer,
+            normal,
+        }
     }
     pub fn get_binding_description() -> vk::VertexInputBindingDescription {
         vk::VertexInputBindingDescription::builder()
@@ -37,14 +53,33 @@ impl Vertex {
                 .format(vk::Format::R32G32B32_SFLOAT)
                 .offset(offset_of!(Vertex, color) as u32)
                 .build(),
+            vk::VertexInputAttributeDescription::builder()
+                .binding(0)
+                .location(2)
+                .format(vk::Format::R32G32_SFLOAT)
+                .offset(offset_of!(Vertex, uv) as u32)
+                .build(),
+            vk::VertexInputAttributeDescription::builder()
+                .binding(0)
+                .location(3)
+                .format(vk::Format::R32_UINT)
+                .offset(offset_of!(Vertex, texture_layer) as u32)
+                .build(),
+            vk::VertexInputAttributeDescription::builder()
+                .binding(0)
+                .location(4)
+                .format(vk::Format::R32G32B32_SFLOAT)
+                .offset(offset_of!(Vertex, normal) as u32)
+                .build(),
         ]
     }
 }
 
 pub trait GameObject{
     fn new()->Self where Self: Sized;
-    fn bind(&self,command_buffer:& vk::CommandBuffer);
-    fn draw(&self);
+    fn bounding_box(&self)->frustum::Aabb;
+    /// Shaded by the sun and ambient light where the box is drawn.
+    fn color(&self)->glm::Vec3;
     fn update(&self);
 }
 
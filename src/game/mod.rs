@@ -1,14 +1,45 @@
+pub mod block_textures;
+pub mod debug_console;
+pub mod font;
 pub mod game_objects;
-use self::game_objects::{camera::Camera, GameObject, Vertex};
+pub mod settings;
+pub mod world;
+pub mod world_time;
+use self::debug_console::{DebugCommand, DebugConsole};
+use self::game_objects::block::Block;
+use self::game_objects::camera::Camera;
+use self::game_objects::frustum::{CullingStats, Frustum};
+use self::game_objects::GameObject;
+use self::settings::{CloudMode, Msaa, PostEffect, Settings};
+use self::world::block::BlockKind;
+use self::world::clouds::{CloudMap, CLOUD_SPEED};
+use self::world::mesher::{mesh_cutout, mesh_translucent, mesh_water, mesh_world};
+use self::world::visibility::{visible_chunks, ChunkVisibility};
+use self::world::weather::{Weather, WeatherSchedule};
+use self::world::World;
+use self::world_time::{TimeOfDay, WorldTime, TICKS_PER_DAY, TICK_DURATION};
 use crate::core::Core;
 use crate::memory::Memory;
-use crate::render_systems::main_render_system::PushConstant;
-use crate::render_systems::MainRenderSystem;
+use crate::render_systems::block_material::BlockMaterial;
+use crate::render_systems::cloud_render_system::CloudRenderSystem;
+use crate::render_systems::cutout_render_system::CutoutRenderSystem;
+use crate::render_systems::debug_lines_system::DebugLinesSystem;
+use crate::render_systems::entity_render_system::EntityRenderSystem;
+use crate::render_systems::particle_system::{ParticleKind, ParticleSystem};
+use crate::render_systems::post_process_system::PostProcessSystem;
+use crate::render_systems::shadow_map::ShadowMap;
+use crate::render_systems::shadow_render_system::ShadowRenderSystem;
+use crate::render_systems::sky_render_system::SkyRenderSystem;
+use crate::render_systems::ssao_system::{SsaoDepthSystem, SsaoStep, SsaoSystem};
+use crate::render_systems::terrain_render_system::TerrainRenderSystem;
+use crate::render_systems::translucent_render_system::TranslucentRenderSystem;
+use crate::render_systems::ui_render_system::{TextStyle, UiRenderSystem};
+use crate::render_systems::main_render_system::supports_wireframe;
+use crate::render_systems::{CameraInfo, DebugView, Fog, FogMode, Lighting};
 use crate::renderer::Renderer;
 use crate::window::Window;
 use ash::vk;
 use nalgebra_glm as glm;
-use std::mem;
 use std::rc::Rc;
 use std::time;
 use winit::event::{ElementState, Event, KeyboardInput, VirtualKeyCode, WindowEvent};
@@ -17,100 +48,226 @@ use winit::{
     platform::run_return::EventLoopExtRunReturn,
 };
 pub const STATIC_MOVE_SPEED: f32 = 1000.0;
-use crate::memory::AllocationType;
-use crate::render_systems::RenderSystem;
+pub const NEAR_PLANE: f32 = 0.1;
+pub const WORLD_SEED: u32 = 1337;
+pub const UNDERWATER_FOG_DENSITY: f32 = 0.12;
+/// Water fog colour in full daylight.
+pub const UNDERWATER_FOG_COLOR: [f32; 3] = [0.06, 0.22, 0.38];
+/// How much of each new frame time goes into the smoothed one the HUD
+/// shows, so the frame rate is readable instead of flickering.
+pub const FRAME_TIME_SMOOTHING: f32 = 0.05;
+/// Pixels between the HUD and the edges of the window.
+pub const HUD_MARGIN: f32 = 8.0;
+/// Outline colours when `Settings::bounding_boxes` is on.
+pub const CHUNK_BOX_COLOR: [f32; 4] = [0.2, 1.0, 0.2, 1.0];
+pub const ENTITY_BOX_COLOR: [f32; 4] = [1.0, 0.9, 0.1, 1.0];
 use game_objects::key_event::{handle_key_event, key_handler};
 
 pub struct Game {
     core: Rc<Core>,
     pub window: Window,
     pub renderer: Renderer,
-    render_system: MainRenderSystem,
     memory: Memory,
     camera: Camera,
     pub delta_time: time::Duration,
     pub time: time::Instant,
     key_handler: key_handler,
-    index_count: u32,
-    game_objects: Vec<Box<dyn GameObject>>,
+    world: World,
+    chunk_visibility: Vec<ChunkVisibility>,
+    pub culling_stats: CullingStats,
+    pub world_time: WorldTime,
+    pub weather: WeatherSchedule,
+    /// `World::surface_heights`, where precipitation stops.
+    surface_heights: Vec<f32>,
+    pub settings: Settings,
+    /// How every block render system shades, switched with F4.
+    debug_view: DebugView,
+    /// When the last game tick was due.
+    last_tick: time::Instant,
+    debug_console: DebugConsole,
+    /// When the last frame was drawn.
+    last_frame: time::Instant,
+    /// Seconds per frame, smoothed over recent frames.
+    frame_time: f32,
 }
 impl Game {
     pub fn new(event_loop: &event_loop::EventLoop<()>) -> Self {
         let mut window = Window::new(event_loop);
         let core = Rc::new(Core::new(&mut window));
-        let renderer = Renderer::new(core.clone(), window.get_window_extent());
-        let mut memory = Memory::new(core.clone());
-        let render_system = MainRenderSystem::new(
+        let settings = Settings::default();
+        let mut renderer = Renderer::new(
             core.clone(),
-            &renderer.get_render_pass(),
-            &Vertex::get_attribute_descriptions(),
-            &vec![Vertex::get_binding_description()],
+            window.get_window_extent(),
+            settings.msaa.samples(),
+        );
+        let mut memory = Memory::new(core.clone());
+        memory.create_allocator(
+            64 * 1024 * 1024,
+            vk::MemoryPropertyFlags::DEVICE_LOCAL,
+            256,
         );
-        // create a cube
-
-        let vertices = vec![
-            Vertex::new(glm::vec3(-0.5, -0.5, -0.5), glm::vec3(1.0, 0.0, 0.0)),
-            Vertex::new(glm::vec3(-0.5, 0.5, 0.5), glm::vec3(0.0, 1.0, 0.0)),
-            Vertex::new(glm::vec3(-0.5, -0.5, 0.5), glm::vec3(0.0, 0.0, 1.0)),
-            Vertex::new(glm::vec3(-0.5, 0.5, -0.5), glm::vec3(1.0, 1.0, 1.0)),
-            Vertex::new(glm::vec3(0.5, -0.5, -0.5), glm::vec3(1.0, 0.0, 0.0)),
-            Vertex::new(glm::vec3(0.5, 0.5, 0.5), glm::vec3(0.0, 1.0, 0.0)),
-            Vertex::new(glm::vec3(0.5, -0.5, 0.5), glm::vec3(0.0, 0.0, 1.0)),
-            Vertex::new(glm::vec3(0.5, 0.5, -0.5), glm::vec3(1.0, 1.0, 1.0)),
-        ];
-        let indices = vec![
-            0, 1, 2, 0, 3, 1, 4, 5, 6, 4, 7, 5, 0, 6, 2, 0, 4, 6, 3, 5, 1, 3, 7, 5, 2, 5, 1, 2, 6,
-            5, 0, 7, 3, 0, 4, 7,
-        ];
-        let index_count = indices.len() as u32;
         memory.create_allocator(
-            10000,
+            32 * 1024 * 1024,
             vk::MemoryPropertyFlags::HOST_VISIBLE | vk::MemoryPropertyFlags::HOST_COHERENT,
             256,
         );
-        let vertex_buffer_index = memory.create_buffer(
-            vertices.len() as u64 * std::mem::size_of::<Vertex>() as u64,
-            AllocationType::Buffer,
-            vk::BufferUsageFlags::VERTEX_BUFFER,
-            vk::MemoryPropertyFlags::HOST_VISIBLE,
-        );
-        let index_buffer_index = memory.create_buffer(
-            (indices.len() * mem::size_of::<u32>()) as u64,
-            AllocationType::Buffer,
-            vk::BufferUsageFlags::INDEX_BUFFER,
-            vk::MemoryPropertyFlags::HOST_VISIBLE,
-        );
-        memory.copy_memory(
-            None,
-            vertex_buffer_index,
-            0,
-            (vertices.len() * mem::size_of::<Vertex>()) as u64,
-            vertices.as_ptr() as *const u8,
-        );
-        memory.copy_memory(
-            None,
-            index_buffer_index,
-            0,
-            (indices.len() * mem::size_of::<u32>()) as u64,
-            indices.as_ptr() as *const u8,
+        let world = World::generate(WORLD_SEED);
+        let (vertices, indices, chunk_meshes) = mesh_world(&world);
+        let chunk_visibility: Vec<ChunkVisibility> =
+            world.chunks.iter().map(ChunkVisibility::compute).collect();
+        let block_material = Rc::new(BlockMaterial::new(
+            core.clone(),
+            &mut memory,
+            &renderer.command,
+        ));
+        let shadow_map = Rc::new(ShadowMap::new(
+            core.clone(),
+            &mut memory,
+            &renderer.render_graph,
+        ));
+        let terrain_render_system = TerrainRenderSystem::new(
+            core.clone(),
+            &mut memory,
+            block_material.clone(),
+            shadow_map.clone(),
+            &renderer.render_graph,
+            &vertices,
+            &indices,
+            chunk_meshes,
+            world.chunks.len(),
+        );
+        let (cutout_vertices, cutout_indices) = mesh_cutout(&world);
+        let cutout_render_system = CutoutRenderSystem::new(
+            core.clone(),
+            &mut memory,
+            block_material.clone(),
+            shadow_map.clone(),
+            &renderer.render_graph,
+            &cutout_vertices,
+            &cutout_indices,
         );
+        let shadow_casters: Vec<_> = std::iter::once(terrain_render_system.shadow_caster())
+            .chain(cutout_render_system.shadow_caster())
+            .collect();
+        let ssao_depth_system = SsaoDepthSystem::new(
+            core.clone(),
+            &renderer.render_graph,
+            block_material.clone(),
+            shadow_casters.clone(),
+            &settings,
+        );
+        let shadow_render_system = ShadowRenderSystem::new(
+            core.clone(),
+            &renderer.render_graph,
+            block_material.clone(),
+            shadow_map.clone(),
+            shadow_casters,
+        );
+        renderer
+            .render_systems
+            .register(Box::new(shadow_render_system));
+        renderer
+            .render_systems
+            .register(Box::new(ssao_depth_system));
+        for step in [SsaoStep::Occlusion, SsaoStep::Blur] {
+            let ssao_system =
+                SsaoSystem::new(core.clone(), &renderer.render_graph, step, &settings);
+            renderer.render_systems.register(Box::new(ssao_system));
+        }
+        renderer
+            .render_systems
+            .register(Box::new(terrain_render_system));
+        renderer
+            .render_systems
+            .register(Box::new(cutout_render_system));
+        let game_objects: Vec<Box<dyn GameObject>> = vec![Box::new(Block::new())];
+        renderer
+            .render_systems
+            .register(Box::new(EntityRenderSystem::new(
+                core.clone(),
+                &renderer.render_graph,
+                game_objects,
+            )));
+        let sky_render_system = SkyRenderSystem::new(core.clone(), &renderer.render_graph);
+        renderer
+            .render_systems
+            .register(Box::new(sky_render_system));
+        let translucent_render_system = TranslucentRenderSystem::new(
+            core.clone(),
+            &mut memory,
+            block_material,
+            shadow_map.clone(),
+            &renderer.render_graph,
+            mesh_water(&world),
+            mesh_translucent(&world),
+        );
+        renderer
+            .render_systems
+            .register(Box::new(translucent_render_system));
+        let cloud_render_system = CloudRenderSystem::new(
+            core.clone(),
+            &mut memory,
+            shadow_map,
+            &renderer.render_graph,
+            &CloudMap::generate(WORLD_SEED),
+            settings.clouds,
+        );
+        renderer
+            .render_systems
+            .register(Box::new(cloud_render_system));
+        let surface_heights = world.surface_heights();
+        let particle_system = ParticleSystem::new(
+            core.clone(),
+            &mut memory,
+            &renderer.render_graph,
+            &surface_heights,
+            World::size() as u32,
+        );
+        renderer.render_systems.register(Box::new(particle_system));
+        let debug_lines_system = DebugLinesSystem::new(core.clone(), &renderer.render_graph);
+        renderer
+            .render_systems
+            .register(Box::new(debug_lines_system));
+        for effect in PostEffect::ALL {
+            let post_process_system =
+                PostProcessSystem::new(core.clone(), &renderer.render_graph, effect, &settings);
+            renderer
+                .render_systems
+                .register(Box::new(post_process_system));
+        }
+        let ui_render_system = UiRenderSystem::new(
+            core.clone(),
+            &mut memory,
+            &renderer.command,
+            &renderer.render_graph,
+        );
+        renderer.render_systems.register(Box::new(ui_render_system));
         let mut camera = Camera::new();
 
         Game {
             core,
             window,
             renderer,
-            render_system,
             memory,
             camera,
             delta_time: time::Duration::new(0, 0),
             time: time::Instant::now(),
             key_handler: key_handler {
-                position: glm::vec3(-5.0, -1.0, 2.0),
-                target: glm::vec3(0.0, 0.0, 0.0),
+                position: glm::vec3(-10.0, 50.0, -10.0),
+                target: glm::vec3(64.0, 20.0, 64.0),
             },
-            index_count,
-            game_objects: vec![],
+            world,
+            chunk_visibility,
+            culling_stats: CullingStats::default(),
+            world_time: WorldTime::default(),
+            weather: WeatherSchedule::new(WORLD_SEED),
+            surface_heights,
+            settings,
+            debug_view: DebugView::Filled,
+            last_tick: time::Instant::now(),
+            debug_console: DebugConsole::from_stdin(),
+            last_frame: time::Instant::now(),
+            frame_time: 0.0,
         }
     }
     pub fn reset_perspective(&mut self) {
@@ -118,56 +275,270 @@ impl Game {
             50_f32.to_radians(),
             self.renderer.swap_chain.swap_chain_extent.width as f32
                 / self.renderer.swap_chain.swap_chain_extent.height as f32,
-            0.1,
-            1000.0,
+            NEAR_PLANE,
+            self.settings.far_plane(),
+        );
+    }
+    /// Runs the game ticks that fell due since the last update, and the
+    /// debug commands typed since then.
+    pub fn update(&mut self) {
+        while self.last_tick.elapsed() >= TICK_DURATION {
+            self.last_tick += TICK_DURATION;
+            self.world_time.tick();
+            self.precipitate();
+        }
+        for command in self.debug_console.poll() {
+            match command {
+                DebugCommand::SetTime(time_of_day) => self.set_time(time_of_day),
+                DebugCommand::SetClouds(mode) => self.set_clouds(mode),
+                DebugCommand::SetWeather(weather) => self.set_weather(weather),
+                DebugCommand::SetPostEffect(effect, enabled) => {
+                    self.set_post_effect(effect, enabled)
+                }
+                DebugCommand::SetExposure(exposure) => self.set_exposure(exposure),
+                DebugCommand::SetMsaa(msaa) => self.set_msaa(msaa),
+                DebugCommand::SetSsao(enabled) => self.set_ssao(enabled),
+                DebugCommand::SetSsaoRadius(radius) => self.set_ssao_radius(radius),
+                DebugCommand::SetRenderDistance(blocks) => self.set_render_distance(blocks),
+                DebugCommand::SpawnParticles(kind, count) => {
+                    let eye = self.key_handler.position;
+                    let forward = glm::normalize(&(self.key_handler.target - eye));
+                    self.spawn_particles(kind, eye + forward * 4.0, count);
+                }
+            }
+        }
+    }
+    pub fn set_time(&mut self, time_of_day: TimeOfDay) {
+        self.world_time.set(time_of_day);
+        println!(
+            "Time set to {:?} (tick {})",
+            time_of_day, self.world_time.ticks
+        );
+    }
+    pub fn set_clouds(&mut self, mode: CloudMode) {
+        self.settings.clouds = mode;
+        if let Some(clouds) = self.renderer.render_systems.get_mut::<CloudRenderSystem>() {
+            clouds.set_mode(mode);
+        }
+        println!("Clouds set to {:?}", mode);
+    }
+    pub fn set_post_effect(&mut self, effect: PostEffect, enabled: bool) {
+        self.settings.set_post_effect(effect, enabled);
+        self.apply_post_process_settings();
+        println!("{:?} turned {}", effect, if enabled { "on" } else { "off" });
+    }
+    pub fn set_exposure(&mut self, exposure: f32) {
+        self.settings.exposure = exposure;
+        self.apply_post_process_settings();
+        println!("Exposure set to {}", exposure);
+    }
+    pub fn set_msaa(&mut self, msaa: Msaa) {
+        self.settings.msaa = msaa;
+        self.renderer
+            .set_samples(msaa.samples(), self.window.get_window_extent());
+        println!(
+            "MSAA set to {:?}, drawing with {:?}",
+            msaa, self.renderer.samples
         );
     }
+    pub fn set_ssao(&mut self, enabled: bool) {
+        self.settings.ssao = enabled;
+        self.apply_ssao_settings();
+        println!("SSAO turned {}", if enabled { "on" } else { "off" });
+    }
+    pub fn set_ssao_radius(&mut self, radius: f32) {
+        self.settings.ssao_radius = radius;
+        self.apply_ssao_settings();
+        println!("SSAO radius set to {} blocks", radius);
+    }
+    pub fn set_render_distance(&mut self, blocks: f32) {
+        self.settings.render_distance = blocks;
+        self.reset_perspective();
+        println!("Render distance set to {} blocks", blocks);
+    }
+    fn apply_ssao_settings(&mut self) {
+        if let Some(ssao_depth) = self.renderer.render_systems.get_mut::<SsaoDepthSystem>() {
+            ssao_depth.apply_settings(&self.settings);
+        }
+        for ssao in self.renderer.render_systems.iter_mut::<SsaoSystem>() {
+            ssao.apply_settings(&self.settings);
+        }
+    }
+    fn apply_post_process_settings(&mut self) {
+        for post_process in self.renderer.render_systems.iter_mut::<PostProcessSystem>() {
+            post_process.apply_settings(&self.settings);
+        }
+    }
+    pub fn set_weather(&mut self, weather: Weather) {
+        self.weather.force(self.world_time.ticks, weather);
+        println!("Weather set to {:?}", weather);
+    }
+    /// Spawns this tick's rain or snow around the camera.
+    fn precipitate(&mut self) {
+        let spawns = self.weather.state(self.world_time.ticks).precipitation(
+            self.world_time.ticks,
+            &self.key_handler.position,
+            &self.surface_heights,
+        );
+        for (kind, position) in spawns {
+            self.spawn_particles(kind, position, 1);
+        }
+    }
+    pub fn spawn_particles(&mut self, kind: ParticleKind, position: glm::Vec3, count: u32) {
+        if let Some(particles) = self.renderer.render_systems.get_mut::<ParticleSystem>() {
+            particles.spawn_particles(kind, position, count);
+        }
+    }
+    /// Sky-coloured fog out to the render distance, or thick water fog
+    /// when the camera is under water.
+    pub fn fog(&self, lighting: &Lighting) -> Fog {
+        let eye = glm::floor(&self.key_handler.position);
+        let eye_block = self
+            .world
+            .block(glm::vec3(eye.x as i32, eye.y as i32, eye.z as i32));
+        if eye_block == BlockKind::Water {
+            Fog {
+                mode: FogMode::Exponential {
+                    density: UNDERWATER_FOG_DENSITY,
+                },
+                color: glm::Vec3::from(UNDERWATER_FOG_COLOR) * lighting.sunlight,
+            }
+        } else {
+            let (start, end) = self.settings.fog_range();
+            Fog {
+                mode: FogMode::Linear { start, end },
+                color: lighting.sky_horizon,
+            }
+        }
+    }
     pub fn draw(&mut self) {
         self.camera.set_view_direction(
             &self.key_handler.position,
             &self.key_handler.target,
             &glm::vec3(0.0, 1.0, 0.0),
         );
-
-        let push = PushConstant {
+        let camera = CameraInfo {
             proj_view: self.camera.projection * self.camera.view,
+            projection: self.camera.projection,
+            position: self.key_handler.position,
+            forward: glm::normalize(&(self.key_handler.target - self.key_handler.position)),
+            near: NEAR_PLANE,
+            far: self.settings.far_plane(),
         };
-
-        let command_buffer = self.renderer.begin_frame();
-        if command_buffer != vk::CommandBuffer::null() {
-            self.renderer.begin_render_pass(command_buffer);
-
-            for game_object in &mut self.game_objects {
-                game_object.bind(&command_buffer);
-                game_object.draw();
-                game_object.update();
+        let visible_sections = visible_chunks(
+            &self.world,
+            &self.chunk_visibility,
+            &camera.position,
+            Some(&Frustum::from_matrix(&camera.proj_view)),
+        );
+        self.outline_bounding_boxes(&visible_sections);
+        if let Some(terrain) = self.renderer.render_systems.get_mut::<TerrainRenderSystem>() {
+            terrain.set_visible_sections(visible_sections);
+        }
+        if let Some(clouds) = self.renderer.render_systems.get_mut::<CloudRenderSystem>() {
+            clouds.set_drift(self.world_time.ticks as f32 * CLOUD_SPEED);
+        }
+        let lighting = self
+            .weather
+            .state(self.world_time.ticks)
+            .darken(self.world_time.lighting());
+        let fog = self.fog(&lighting);
+        self.update_hud();
+        if let Some(culling_stats) =
+            self.renderer
+                .draw_frame(camera, lighting, fog, &mut self.memory)
+        {
+            self.culling_stats = culling_stats;
+        }
+    }
+    /// Replaces last frame's HUD text: the frame rate, and with the debug
+    /// overlay on, where the camera is and what the world and renderer are
+    /// doing.
+    fn update_hud(&mut self) {
+        let frame_time = self.last_frame.elapsed().as_secs_f32();
+        self.last_frame = time::Instant::now();
+        self.frame_time = if self.frame_time == 0.0 {
+            frame_time
+        } else {
+            self.frame_time + (frame_time - self.frame_time) * FRAME_TIME_SMOOTHING
+        };
+        let mut lines = vec![format!(
+            "{:.0} fps",
+            1.0 / self.frame_time.max(f32::EPSILON)
+        )];
+        if self.settings.debug_overlay {
+            let position = self.key_handler.position;
+            let ticks = self.world_time.ticks;
+            lines.extend([
+                format!("XYZ: {:.1} {:.1} {:.1}", position.x, position.y, position.z),
+                format!(
+                    "Drawn: {} Culled: {}",
+                    self.culling_stats.drawn, self.culling_stats.culled
+                ),
+                format!(
+                    "Day {} tick {}",
+                    ticks / TICKS_PER_DAY,
+                    ticks % TICKS_PER_DAY
+                ),
+                format!("Weather: {:?}", self.weather.state(ticks).weather),
+                format!(
+                    "MSAA: {}x SSAO: {}",
+                    self.renderer.samples.as_raw(),
+                    if self.settings.ssao { "on" } else { "off" }
+                ),
+            ]);
+        }
+        if let Some(ui) = self.renderer.render_systems.get_mut::<UiRenderSystem>() {
+            ui.clear_text();
+            ui.draw_text(
+                &lines.join("\n"),
+                glm::vec2(HUD_MARGIN, HUD_MARGIN),
+                &TextStyle::default(),
+            );
+        }
+    }
+    pub fn toggle_debug_overlay(&mut self) {
+        self.settings.debug_overlay = !self.settings.debug_overlay;
+    }
+    /// Switches every block render system to the next debug view, skipping
+    /// wireframe when the device cannot rasterise lines as polygons.
+    pub fn cycle_debug_view(&mut self) {
+        self.debug_view = self.debug_view.next();
+        if self.debug_view == DebugView::Wireframe && !supports_wireframe(&self.core) {
+            self.debug_view = self.debug_view.next();
+        }
+        println!("Debug view: {:?}", self.debug_view);
+        self.renderer.render_systems.set_debug_view(self.debug_view);
+    }
+    pub fn toggle_bounding_boxes(&mut self) {
+        self.settings.bounding_boxes = !self.settings.bounding_boxes;
+    }
+    /// Replaces last frame's debug boxes with the bounds of the visible
+    /// chunks and of the game objects, when `Settings::bounding_boxes` is on.
+    fn outline_bounding_boxes(&mut self, visible_sections: &[bool]) {
+        let mut boxes = Vec::new();
+        if self.settings.bounding_boxes {
+            for (chunk, _) in self
+                .world
+                .chunks
+                .iter()
+                .zip(visible_sections)
+                .filter(|(chunk, visible)| **visible && !chunk.is_empty())
+            {
+                boxes.push((chunk.aabb(), glm::Vec4::from(CHUNK_BOX_COLOR)));
+            }
+            if let Some(entities) = self.renderer.render_systems.get_mut::<EntityRenderSystem>() {
+                let color = glm::Vec4::from(ENTITY_BOX_COLOR);
+                for game_object in &entities.game_objects {
+                    boxes.push((game_object.bounding_box(), color));
+                }
             }
-            self.render_system.bind(&command_buffer, push);
-            unsafe {
-                self.core.logical_device.cmd_bind_vertex_buffers(
-                    command_buffer,
-                    0,
-                    &[self.memory.buffers[0].handle],
-                    &[0],
-                );
-                self.core.logical_device.cmd_bind_index_buffer(
-                    command_buffer,
-                    self.memory.buffers[1].handle,
-                    0,
-                    vk::IndexType::UINT32,
-                );
-                self.core.logical_device.cmd_draw_indexed(
-                    command_buffer,
-                    self.index_count,
-                    1,
-                    0,
-                    0,
-                    0,
-                );
-                
+        }
+        if let Some(debug_lines) = self.renderer.render_systems.get_mut::<DebugLinesSystem>() {
+            debug_lines.clear();
+            for (aabb, color) in boxes {
+                debug_lines.draw_box(aabb, color);
             }
-            self.renderer.end_render_pass(command_buffer);
-            self.renderer.end_frame();
         }
     }
     pub fn run(&mut self, event_loop: &mut event_loop::EventLoop<()>) {
@@ -195,6 +566,15 @@ impl Game {
                                 }
                                 *control_flow = ControlFlow::Exit
                             }
+                            (Some(VirtualKeyCode::F3), ElementState::Pressed) => {
+                                self.toggle_debug_overlay();
+                            }
+                            (Some(VirtualKeyCode::F5), ElementState::Pressed) => {
+                                self.toggle_bounding_boxes();
+                            }
+                            (Some(VirtualKeyCode::F4), ElementState::Pressed) => {
+                                self.cycle_debug_view();
+                            }
                             _ => {
                                 handle_key_event(&input, &self.delta_time, &mut self.key_handler);
                             }
@@ -213,6 +593,7 @@ impl Game {
                     _ => {}
                 },
                 Event::MainEventsCleared => {
+                    self.update();
                     self.window.window.request_redraw();
                 }
                 Event::RedrawRequested(_window_id) => {
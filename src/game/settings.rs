@@ -0,0 +1,174 @@
+use std::str::FromStr;
+
+/// How far out, as a fraction of the render distance, fog starts.
+pub const FOG_START: f32 = 0.6;
+/// How far past the render distance the far plane sits, in blocks, so
+/// nothing is clipped before the fog has hidden it.
+pub const FAR_PLANE_MARGIN: f32 = 16.0;
+
+/// How the cloud layer is drawn.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum CloudMode {
+    Off,
+    /// A flat sheet of cloud.
+    Fast,
+    /// Blocky clouds with sides.
+    Fancy,
+}
+impl FromStr for CloudMode {
+    type Err = String;
+    fn from_str(name: &str) -> Result<Self, Self::Err> {
+        match name {
+            "off" => Ok(CloudMode::Off),
+            "fast" => Ok(CloudMode::Fast),
+            "fancy" => Ok(CloudMode::Fancy),
+            _ => Err(format!(
+                "Unknown cloud mode '{}', expected off, fast or fancy",
+                name
+            )),
+        }
+    }
+}
+
+/// Multisample anti-aliasing of the world. The renderer falls back to the
+/// most samples the device supports.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Msaa {
+    Off,
+    X2,
+    X4,
+    X8,
+}
+impl Msaa {
+    pub fn samples(&self) -> u32 {
+        match self {
+            Msaa::Off => 1,
+            Msaa::X2 => 2,
+            Msaa::X4 => 4,
+            Msaa::X8 => 8,
+        }
+    }
+}
+impl FromStr for Msaa {
+    type Err = String;
+    fn from_str(name: &str) -> Result<Self, Self::Err> {
+        match name {
+            "off" => Ok(Msaa::Off),
+            "2x" => Ok(Msaa::X2),
+            "4x" => Ok(Msaa::X4),
+            "8x" => Ok(Msaa::X8),
+            _ => Err(format!(
+                "Unknown MSAA level '{}', expected off, 2x, 4x or 8x",
+                name
+            )),
+        }
+    }
+}
+
+/// A full-screen step of the post-process chain.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum PostEffect {
+    /// Scales the scene by the exposure and maps it into the screen's range.
+    ToneMapping,
+    /// Encodes linear colour for the display.
+    Gamma,
+    /// Smooths jagged edges.
+    Fxaa,
+    /// Darkens the corners of the screen.
+    Vignette,
+    /// Tints and ripples the picture while the camera is under water.
+    UnderwaterTint,
+}
+impl PostEffect {
+    /// Every effect, in the order the chain runs them.
+    pub const ALL: [PostEffect; 5] = [
+        PostEffect::ToneMapping,
+        PostEffect::Gamma,
+        PostEffect::Fxaa,
+        PostEffect::Vignette,
+        PostEffect::UnderwaterTint,
+    ];
+}
+impl FromStr for PostEffect {
+    type Err = String;
+    fn from_str(name: &str) -> Result<Self, Self::Err> {
+        match name {
+            "tonemap" => Ok(PostEffect::ToneMapping),
+            "gamma" => Ok(PostEffect::Gamma),
+            "fxaa" => Ok(PostEffect::Fxaa),
+            "vignette" => Ok(PostEffect::Vignette),
+            "underwater" => Ok(PostEffect::UnderwaterTint),
+            _ => Err(format!(
+                "Unknown post effect '{}', expected tonemap, gamma, fxaa, vignette or underwater",
+                name
+            )),
+        }
+    }
+}
+
+/// Options the player can change while the game runs.
+#[derive(Debug, Clone, Copy)]
+pub struct Settings {
+    pub clouds: CloudMode,
+    pub msaa: Msaa,
+    /// Whether each of `PostEffect::ALL` runs.
+    post_effects: [bool; PostEffect::ALL.len()],
+    /// What the scene is multiplied by before tone mapping.
+    pub exposure: f32,
+    /// Whether screen-space ambient occlusion darkens the terrain.
+    pub ssao: bool,
+    /// How far around each surface SSAO looks for occluders, in blocks.
+    pub ssao_radius: f32,
+    /// Whether the debug overlay is drawn under the HUD.
+    pub debug_overlay: bool,
+    /// Whether visible chunks and game objects are outlined.
+    pub bounding_boxes: bool,
+    /// How far away terrain is drawn, in blocks. Fog thickens from
+    /// `FOG_START` of the way out and hides everything past it.
+    pub render_distance: f32,
+}
+impl Settings {
+    pub fn post_effect(&self, effect: PostEffect) -> bool {
+        self.post_effects[effect as usize]
+    }
+    pub fn set_post_effect(&mut self, effect: PostEffect, enabled: bool) {
+        self.post_effects[effect as usize] = enabled;
+    }
+    /// Where linear fog starts and where it hides everything.
+    pub fn fog_range(&self) -> (f32, f32) {
+        (self.render_distance * FOG_START, self.render_distance)
+    }
+    pub fn far_plane(&self) -> f32 {
+        self.render_distance + FAR_PLANE_MARGIN
+    }
+}
+impl Default for Settings {
+    fn default() -> Self {
+        Settings {
+            clouds: CloudMode::Fancy,
+            msaa: Msaa::X4,
+            post_effects: [true; PostEffect::ALL.len()],
+            exposure: 1.0,
+            ssao: false,
+            ssao_radius: 1.5,
+            debug_overlay: false,
+            bounding_boxes: false,
+            render_distance: 160.0,
+        }
+    }
+}
+
+#[cfg(test)]
+mod settings_tests {
+    use super::*;
+    #[test]
+    fn fog_and_far_plane_follow_render_distance() {
+        let settings = Settings {
+            render_distance: 80.0,
+            ..Settings::default()
+        };
+        let (start, end) = settings.fog_range();
+        assert!((start - 48.0).abs() < 1e-4 && end == 80.0);
+        assert!(settings.far_plane() > end);
+    }
+}
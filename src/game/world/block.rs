@@ -0,0 +1,71 @@
+use crate::game::block_textures::BlockTexture;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum BlockKind {
+    Air,
+    Stone,
+    Dirt,
+    Grass,
+    Water,
+    /// Not generated by the world yet.
+    #[allow(dead_code)]
+    Glass,
+    Ice,
+    Leaves,
+    TallGrass,
+    Flower,
+    /// Not generated by the world yet.
+    #[allow(dead_code)]
+    Lava,
+    /// Not generated by the world yet.
+    #[allow(dead_code)]
+    Fire,
+}
+impl BlockKind {
+    /// Opaque blocks hide the faces of their neighbours.
+    pub fn is_opaque(&self) -> bool {
+        !matches!(self, BlockKind::Air) && !self.is_translucent() && !self.is_cutout()
+    }
+    /// Translucent blocks are alpha blended in their own pass.
+    pub fn is_translucent(&self) -> bool {
+        matches!(self, BlockKind::Water | BlockKind::Glass | BlockKind::Ice)
+    }
+    /// Cutout blocks have fully transparent holes and are alpha tested.
+    pub fn is_cutout(&self) -> bool {
+        matches!(
+            self,
+            BlockKind::Leaves | BlockKind::TallGrass | BlockKind::Flower | BlockKind::Fire
+        )
+    }
+    /// Plants, and fire, are drawn as two crossed quads instead of a cube.
+    pub fn is_plant(&self) -> bool {
+        matches!(
+            self,
+            BlockKind::TallGrass | BlockKind::Flower | BlockKind::Fire
+        )
+    }
+    /// The texture for the face whose outward normal has this `y` component.
+    pub fn texture(&self, normal_y: f32) -> BlockTexture {
+        match self {
+            BlockKind::Air | BlockKind::Stone => BlockTexture::Stone,
+            BlockKind::Dirt => BlockTexture::Dirt,
+            BlockKind::Water => BlockTexture::Water,
+            BlockKind::Glass => BlockTexture::Glass,
+            BlockKind::Ice => BlockTexture::Ice,
+            BlockKind::Leaves => BlockTexture::Leaves,
+            BlockKind::TallGrass => BlockTexture::TallGrass,
+            BlockKind::Flower => BlockTexture::Flower,
+            BlockKind::Lava => BlockTexture::Lava,
+            BlockKind::Fire => BlockTexture::Fire,
+            BlockKind::Grass => {
+                if normal_y > 0.0 {
+                    BlockTexture::GrassTop
+                } else if normal_y < 0.0 {
+                    BlockTexture::Dirt
+                } else {
+                    BlockTexture::GrassSide
+                }
+            }
+        }
+    }
+}
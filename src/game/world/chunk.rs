@@ -0,0 +1,60 @@
+use super::block::BlockKind;
+use crate::game::game_objects::frustum::Aabb;
+use nalgebra_glm as glm;
+
+pub const CHUNK_SIZE: i32 = 16;
+const CHUNK_VOLUME: usize = (CHUNK_SIZE * CHUNK_SIZE * CHUNK_SIZE) as usize;
+
+/// A 16x16x16 section of the world. `position` is in chunk coordinates, so
+/// the chunk covers blocks `position * CHUNK_SIZE .. (position + 1) * CHUNK_SIZE`.
+pub struct Chunk {
+    pub position: glm::IVec3,
+    blocks: Vec<BlockKind>,
+    /// How many blocks each water block is from its source, 0 for a source.
+    flow: Vec<u8>,
+}
+impl Chunk {
+    pub fn new(position: glm::IVec3) -> Self {
+        Chunk {
+            position,
+            blocks: vec![BlockKind::Air; CHUNK_VOLUME],
+            flow: vec![0; CHUNK_VOLUME],
+        }
+    }
+    fn index(x: i32, y: i32, z: i32) -> usize {
+        (x + CHUNK_SIZE * (z + CHUNK_SIZE * y)) as usize
+    }
+    /// Local coordinates outside `0..CHUNK_SIZE` read as air.
+    pub fn get(&self, x: i32, y: i32, z: i32) -> BlockKind {
+        if [x, y, z].iter().any(|c| *c < 0 || *c >= CHUNK_SIZE) {
+            return BlockKind::Air;
+        }
+        self.blocks[Chunk::index(x, y, z)]
+    }
+    /// Also makes water placed here a source.
+    pub fn set(&mut self, x: i32, y: i32, z: i32, block: BlockKind) {
+        self.blocks[Chunk::index(x, y, z)] = block;
+        self.flow[Chunk::index(x, y, z)] = 0;
+    }
+    /// Local coordinates outside `0..CHUNK_SIZE` read as a source.
+    pub fn flow(&self, x: i32, y: i32, z: i32) -> u8 {
+        if [x, y, z].iter().any(|c| *c < 0 || *c >= CHUNK_SIZE) {
+            return 0;
+        }
+        self.flow[Chunk::index(x, y, z)]
+    }
+    pub fn set_flow(&mut self, x: i32, y: i32, z: i32, flow: u8) {
+        self.flow[Chunk::index(x, y, z)] = flow;
+    }
+    pub fn is_empty(&self) -> bool {
+        self.blocks.iter().all(|block| *block == BlockKind::Air)
+    }
+    /// World-space origin of the block at local `(0, 0, 0)`.
+    pub fn origin(&self) -> glm::IVec3 {
+        self.position * CHUNK_SIZE
+    }
+    pub fn aabb(&self) -> Aabb {
+        let min = glm::convert::<glm::IVec3, glm::Vec3>(self.origin());
+        Aabb::new(min, min + glm::vec3(1.0, 1.0, 1.0) * CHUNK_SIZE as f32)
+    }
+}
@@ -0,0 +1,202 @@
+use super::mesher::CUBE_FACES;
+use super::{lattice_value, smooth};
+use crate::game::game_objects::Vertex;
+use nalgebra_glm as glm;
+
+/// Cells along each side of the cloud map, which tiles the sky.
+pub const CLOUD_MAP_SIZE: i32 = 48;
+/// Width of one cloud cell in blocks.
+pub const CLOUD_CELL_SIZE: f32 = 12.0;
+/// Height of the bottom of the cloud layer.
+pub const CLOUD_HEIGHT: f32 = 96.0;
+pub const CLOUD_THICKNESS: f32 = 4.0;
+/// Blocks the clouds drift along +x every game tick.
+pub const CLOUD_SPEED: f32 = 0.03;
+/// Mixed into the world seed so clouds do not follow the terrain.
+const CLOUD_SEED: u32 = 0x85eb_ca6b;
+/// Noise value above which a cell is cloud.
+const CLOUD_COVER: f32 = 0.52;
+/// Lattice cells of cloud noise along the map, so the noise tiles with it.
+const CLOUD_NOISE_CELLS: i32 = 12;
+
+/// Which cells of the cloud layer hold cloud, wrapping at the edges.
+pub struct CloudMap {
+    cells: Vec<bool>,
+}
+impl CloudMap {
+    pub fn generate(seed: u32) -> Self {
+        let seed = seed ^ CLOUD_SEED;
+        let scale = CLOUD_MAP_SIZE as f32 / CLOUD_NOISE_CELLS as f32;
+        let mut cells = Vec::with_capacity((CLOUD_MAP_SIZE * CLOUD_MAP_SIZE) as usize);
+        for z in 0..CLOUD_MAP_SIZE {
+            for x in 0..CLOUD_MAP_SIZE {
+                let fx = x as f32 / scale;
+                let fz = z as f32 / scale;
+                let (x0, z0) = (fx.floor() as i32, fz.floor() as i32);
+                let (tx, tz) = (smooth(fx - fx.floor()), smooth(fz - fz.floor()));
+                let corner = |dx: i32, dz: i32| {
+                    lattice_value(
+                        seed,
+                        (x0 + dx).rem_euclid(CLOUD_NOISE_CELLS),
+                        (z0 + dz).rem_euclid(CLOUD_NOISE_CELLS),
+                    )
+                };
+                let top = corner(0, 0) + (corner(1, 0) - corner(0, 0)) * tx;
+                let bottom = corner(0, 1) + (corner(1, 1) - corner(0, 1)) * tx;
+                // a little per-cell noise breaks up the smooth outlines
+                let detail = lattice_value(seed.rotate_left(7), x, z) * 0.2 - 0.1;
+                cells.push(top + (bottom - top) * tz + detail > CLOUD_COVER);
+            }
+        }
+        CloudMap { cells }
+    }
+    pub fn is_cloud(&self, x: i32, z: i32) -> bool {
+        let x = x.rem_euclid(CLOUD_MAP_SIZE);
+        let z = z.rem_euclid(CLOUD_MAP_SIZE);
+        self.cells[(z * CLOUD_MAP_SIZE + x) as usize]
+    }
+    /// How far apart in blocks copies of the map repeat.
+    pub fn period() -> f32 {
+        CLOUD_MAP_SIZE as f32 * CLOUD_CELL_SIZE
+    }
+}
+
+/// Appends one face of the box from `min` to `min + size`.
+fn push_box_face(
+    vertices: &mut Vec<Vertex>,
+    indices: &mut Vec<u32>,
+    min: glm::Vec3,
+    size: glm::Vec3,
+    face: &([i32; 3], [i32; 3], [i32; 3]),
+    shade: f32,
+) {
+    let normal = glm::vec3(face.0[0] as f32, face.0[1] as f32, face.0[2] as f32);
+    let u = glm::vec3(face.1[0] as f32, face.1[1] as f32, face.1[2] as f32);
+    let v = glm::vec3(face.2[0] as f32, face.2[1] as f32, face.2[2] as f32);
+    let base = vertices.len() as u32;
+    for (su, sv) in [(-1.0, -1.0), (1.0, -1.0), (1.0, 1.0), (-1.0, 1.0)] {
+        let corner: glm::Vec3 = normal * 0.5 + u * (su * 0.5) + v * (sv * 0.5);
+        let position = min + (corner + glm::vec3(0.5, 0.5, 0.5)).component_mul(&size);
+        vertices.push(Vertex::new(
+            position,
+            glm::vec3(shade, shade, shade),
+            glm::vec2(0.0, 0.0),
+            0,
+            normal,
+        ));
+    }
+    indices.extend([base, base + 1, base + 2, base, base + 2, base + 3]);
+}
+
+/// Brightness of a cloud face, lit from above.
+fn face_shade(normal: &[i32; 3]) -> f32 {
+    match normal {
+        [0, 1, 0] => 1.0,
+        [0, -1, 0] => 0.72,
+        [_, _, 0] => 0.9,
+        _ => 0.82,
+    }
+}
+
+/// "Fast" clouds: one flat sheet at the bottom of the layer, facing both up
+/// and down, relative to the layer's origin.
+pub fn mesh_flat_clouds(map: &CloudMap) -> (Vec<Vertex>, Vec<u32>) {
+    let mut vertices = vec![];
+    let mut indices = vec![];
+    let size = glm::vec3(CLOUD_CELL_SIZE, 0.0, CLOUD_CELL_SIZE);
+    for z in 0..CLOUD_MAP_SIZE {
+        for x in 0..CLOUD_MAP_SIZE {
+            if !map.is_cloud(x, z) {
+                continue;
+            }
+            let min = glm::vec3(x as f32, 0.0, z as f32) * CLOUD_CELL_SIZE;
+            for face in CUBE_FACES.iter().filter(|face| face.0[1] != 0) {
+                push_box_face(
+                    &mut vertices,
+                    &mut indices,
+                    min,
+                    size,
+                    face,
+                    face_shade(&face.0),
+                );
+            }
+        }
+    }
+    (vertices, indices)
+}
+
+/// "Fancy" clouds: every cell extruded into a box, relative to the layer's
+/// origin. Sides between two cloud cells are left out, also across the
+/// wrapping edge so copies of the map join up.
+pub fn mesh_box_clouds(map: &CloudMap) -> (Vec<Vertex>, Vec<u32>) {
+    let mut vertices = vec![];
+    let mut indices = vec![];
+    let size = glm::vec3(CLOUD_CELL_SIZE, CLOUD_THICKNESS, CLOUD_CELL_SIZE);
+    for z in 0..CLOUD_MAP_SIZE {
+        for x in 0..CLOUD_MAP_SIZE {
+            if !map.is_cloud(x, z) {
+                continue;
+            }
+            let min = glm::vec3(x as f32, 0.0, z as f32) * CLOUD_CELL_SIZE;
+            for face in CUBE_FACES.iter() {
+                let [nx, ny, nz] = face.0;
+                if ny == 0 && map.is_cloud(x + nx, z + nz) {
+                    continue;
+                }
+                push_box_face(
+                    &mut vertices,
+                    &mut indices,
+                    min,
+                    size,
+                    face,
+                    face_shade(&face.0),
+                );
+            }
+        }
+    }
+    (vertices, indices)
+}
+
+#[cfg(test)]
+mod clouds_tests {
+    use super::*;
+    #[test]
+    fn the_map_wraps_and_is_partly_cloudy() {
+        let map = CloudMap::generate(1337);
+        assert_eq!(map.is_cloud(-1, 3), map.is_cloud(CLOUD_MAP_SIZE - 1, 3));
+        assert_eq!(map.is_cloud(5, CLOUD_MAP_SIZE), map.is_cloud(5, 0));
+        let cloudy = (0..CLOUD_MAP_SIZE)
+            .flat_map(|z| (0..CLOUD_MAP_SIZE).map(move |x| (x, z)))
+            .filter(|(x, z)| map.is_cloud(*x, *z))
+            .count();
+        let total = (CLOUD_MAP_SIZE * CLOUD_MAP_SIZE) as usize;
+        assert!(cloudy > total / 10 && cloudy < total * 9 / 10, "{}", cloudy);
+    }
+    #[test]
+    fn boxes_share_no_inner_sides() {
+        let map = CloudMap::generate(1337);
+        let cells = (0..CLOUD_MAP_SIZE)
+            .flat_map(|z| (0..CLOUD_MAP_SIZE).map(move |x| (x, z)))
+            .filter(|(x, z)| map.is_cloud(*x, *z))
+            .count();
+        let open_sides: usize = (0..CLOUD_MAP_SIZE)
+            .flat_map(|z| (0..CLOUD_MAP_SIZE).map(move |x| (x, z)))
+            .filter(|(x, z)| map.is_cloud(*x, *z))
+            .map(|(x, z)| {
+                [(1, 0), (-1, 0), (0, 1), (0, -1)]
+                    .iter()
+                    .filter(|(dx, dz)| !map.is_cloud(x + dx, z + dz))
+                    .count()
+            })
+            .sum();
+        let (vertices, indices) = mesh_box_clouds(&map);
+        assert_eq!(indices.len(), (cells * 2 + open_sides) * 6);
+        assert_eq!(vertices.len(), (cells * 2 + open_sides) * 4);
+        for vertex in vertices.iter() {
+            assert!(vertex.position.y == 0.0 || vertex.position.y == CLOUD_THICKNESS);
+        }
+        let (flat_vertices, flat_indices) = mesh_flat_clouds(&map);
+        assert_eq!(flat_indices.len(), cells * 2 * 6);
+        assert!(flat_vertices.iter().all(|vertex| vertex.position.y == 0.0));
+    }
+}
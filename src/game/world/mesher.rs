@@ -0,0 +1,407 @@
+use super::block::BlockKind;
+use super::World;
+use crate::game::game_objects::frustum::Aabb;
+use crate::game::game_objects::Vertex;
+use nalgebra_glm as glm;
+
+/// (normal, u, v) for every cube face, with u x v == normal so every face
+/// winds counter-clockwise when seen from outside.
+pub const CUBE_FACES: [([i32; 3], [i32; 3], [i32; 3]); 6] = [
+    ([1, 0, 0], [0, 1, 0], [0, 0, 1]),
+    ([-1, 0, 0], [0, 0, 1], [0, 1, 0]),
+    ([0, 1, 0], [0, 0, 1], [1, 0, 0]),
+    ([0, -1, 0], [1, 0, 0], [0, 0, 1]),
+    ([0, 0, 1], [1, 0, 0], [0, 1, 0]),
+    ([0, 0, -1], [0, 1, 0], [1, 0, 0]),
+];
+
+/// Where one chunk's mesh lives in the shared vertex and index buffers.
+#[derive(Debug, Clone, Copy)]
+pub struct ChunkMeshRange {
+    pub chunk_index: usize,
+    pub aabb: Aabb,
+    pub first_index: u32,
+    pub index_count: u32,
+}
+
+/// Meshes every chunk into one vertex and index list. Indices are already
+/// offset, so each range can be drawn with a vertex offset of zero.
+pub fn mesh_world(world: &World) -> (Vec<Vertex>, Vec<u32>, Vec<ChunkMeshRange>) {
+    let mut vertices = vec![];
+    let mut indices = vec![];
+    let mut ranges = vec![];
+    for chunk_index in 0..world.chunks.len() {
+        let (chunk_vertices, chunk_indices) = mesh_chunk(world, chunk_index);
+        if chunk_indices.is_empty() {
+            continue;
+        }
+        let base = vertices.len() as u32;
+        ranges.push(ChunkMeshRange {
+            chunk_index,
+            aabb: world.chunks[chunk_index].aabb(),
+            first_index: indices.len() as u32,
+            index_count: chunk_indices.len() as u32,
+        });
+        vertices.extend(chunk_vertices);
+        indices.extend(chunk_indices.iter().map(|index| index + base));
+    }
+    (vertices, indices, ranges)
+}
+
+/// Appends one quad for the face of the unit cube at `block` facing `normal`.
+pub fn push_face(
+    vertices: &mut Vec<Vertex>,
+    indices: &mut Vec<u32>,
+    block: glm::Vec3,
+    face: &([i32; 3], [i32; 3], [i32; 3]),
+    texture_layer: u32,
+    color: glm::Vec3,
+) {
+    let normal = glm::vec3(face.0[0] as f32, face.0[1] as f32, face.0[2] as f32);
+    let u = glm::vec3(face.1[0] as f32, face.1[1] as f32, face.1[2] as f32);
+    let v = glm::vec3(face.2[0] as f32, face.2[1] as f32, face.2[2] as f32);
+    let center = block + glm::vec3(0.5, 0.5, 0.5);
+    let base = vertices.len() as u32;
+    for (su, sv) in [(-1.0, -1.0), (1.0, -1.0), (1.0, 1.0), (-1.0, 1.0)] {
+        let offset: glm::Vec3 = normal * 0.5 + u * (su * 0.5) + v * (sv * 0.5);
+        let uv = if normal.y == 0.0 {
+            // side faces keep the top of the texture facing up
+            let horizontal = if u.y == 0.0 { u } else { v };
+            glm::vec2(glm::dot(&offset, &horizontal) + 0.5, 0.5 - offset.y)
+        } else {
+            glm::vec2(offset.x + 0.5, offset.z + 0.5)
+        };
+        vertices.push(Vertex::new(
+            center + offset,
+            color,
+            uv,
+            texture_layer,
+            normal,
+        ));
+    }
+    indices.extend([base, base + 1, base + 2, base, base + 2, base + 3]);
+}
+
+/// Builds the visible faces of one chunk in world space. Faces touching an
+/// opaque neighbour, including one in the next chunk, are skipped.
+pub fn mesh_chunk(world: &World, chunk_index: usize) -> (Vec<Vertex>, Vec<u32>) {
+    let chunk = &world.chunks[chunk_index];
+    let origin = chunk.origin();
+    let mut vertices = vec![];
+    let mut indices = vec![];
+    if chunk.is_empty() {
+        return (vertices, indices);
+    }
+    let size = super::chunk::CHUNK_SIZE;
+    for y in 0..size {
+        for z in 0..size {
+            for x in 0..size {
+                let block = chunk.get(x, y, z);
+                if !block.is_opaque() {
+                    continue;
+                }
+                let position = origin + glm::vec3(x, y, z);
+                for face in CUBE_FACES.iter() {
+                    let normal = glm::vec3(face.0[0], face.0[1], face.0[2]);
+                    if world.block(position + normal).is_opaque() {
+                        continue;
+                    }
+                    // cheap directional shading so faces stay readable untextured
+                    let shade = match face.0[1] {
+                        1 => 1.0,
+                        -1 => 0.55,
+                        _ => 0.8,
+                    };
+                    push_face(
+                        &mut vertices,
+                        &mut indices,
+                        glm::convert::<glm::IVec3, glm::Vec3>(position),
+                        face,
+                        block.texture(normal.y as f32).layer(),
+                        glm::vec3(shade, shade, shade),
+                    );
+                }
+            }
+        }
+    }
+    (vertices, indices)
+}
+
+/// Appends the two crossed quads of a plant filling the block at `block`.
+/// Cutout pipelines draw both sides, so each diagonal needs one quad.
+pub fn push_plant(
+    vertices: &mut Vec<Vertex>,
+    indices: &mut Vec<u32>,
+    block: glm::Vec3,
+    texture_layer: u32,
+    color: glm::Vec3,
+) {
+    for (start, end) in [([0.0, 0.0], [1.0, 1.0]), ([1.0, 0.0], [0.0, 1.0])] {
+        let start = glm::vec3(start[0], 0.0, start[1]);
+        let end = glm::vec3(end[0], 0.0, end[1]);
+        let normal = glm::normalize(&glm::vec3(end.z - start.z, 0.0, start.x - end.x));
+        let base = vertices.len() as u32;
+        for (t, height) in [(0.0, 0.0), (1.0, 0.0), (1.0, 1.0), (0.0, 1.0)] {
+            let corner = start + (end - start) * t + glm::vec3(0.0, height, 0.0);
+            vertices.push(Vertex::new(
+                block + corner,
+                color,
+                glm::vec2(t, 1.0 - height),
+                texture_layer,
+                normal,
+            ));
+        }
+        indices.extend([base, base + 1, base + 2, base, base + 2, base + 3]);
+    }
+}
+
+/// Builds the alpha-tested blocks of the whole world: leaf cubes, whose
+/// faces are skipped only against opaque blocks so the leaves behind show
+/// through the holes, and crossed-quad plants.
+pub fn mesh_cutout(world: &World) -> (Vec<Vertex>, Vec<u32>) {
+    let mut vertices = vec![];
+    let mut indices = vec![];
+    let size = super::chunk::CHUNK_SIZE;
+    for chunk in world.chunks.iter() {
+        let origin = chunk.origin();
+        for y in 0..size {
+            for z in 0..size {
+                for x in 0..size {
+                    let block = chunk.get(x, y, z);
+                    if !block.is_cutout() {
+                        continue;
+                    }
+                    let position = origin + glm::vec3(x, y, z);
+                    let block_position = glm::convert::<glm::IVec3, glm::Vec3>(position);
+                    if block.is_plant() {
+                        push_plant(
+                            &mut vertices,
+                            &mut indices,
+                            block_position,
+                            block.texture(0.0).layer(),
+                            glm::vec3(0.9, 0.9, 0.9),
+                        );
+                        continue;
+                    }
+                    for face in CUBE_FACES.iter() {
+                        let normal = glm::vec3(face.0[0], face.0[1], face.0[2]);
+                        if world.block(position + normal).is_opaque() {
+                            continue;
+                        }
+                        push_face(
+                            &mut vertices,
+                            &mut indices,
+                            block_position,
+                            face,
+                            block.texture(normal.y as f32).layer(),
+                            glm::vec3(0.8, 0.8, 0.8),
+                        );
+                    }
+                }
+            }
+        }
+    }
+    (vertices, indices)
+}
+
+/// The translucent faces of the whole world, four vertices per face, in
+/// no particular order. They are drawn with indices from
+/// `back_to_front_indices` instead of a fixed index list.
+pub struct TranslucentMesh {
+    pub vertices: Vec<Vertex>,
+    /// Centre of each face, face `i` owning vertices `4 * i .. 4 * i + 4`.
+    pub face_centers: Vec<glm::Vec3>,
+}
+
+/// Builds the faces of glass, ice and other translucent blocks except
+/// water, which `mesh_water` builds. A face is skipped when its neighbour
+/// is opaque or the same kind of block.
+pub fn mesh_translucent(world: &World) -> TranslucentMesh {
+    mesh_translucent_blocks(world, false)
+}
+
+/// Builds the faces of water, skipping those against opaque blocks or
+/// other water so a body of water only shows its surface. The surface is
+/// lowered to the water's level, so flowing water slopes away from its
+/// source.
+pub fn mesh_water(world: &World) -> TranslucentMesh {
+    mesh_translucent_blocks(world, true)
+}
+
+/// Height of the water surface at the corner `corner` of blocks, shared by
+/// the four blocks around it so neighbouring surfaces meet: the average
+/// over the water among them, or the full block where water lies on top of
+/// any of them.
+fn water_corner_height(world: &World, corner: glm::IVec3) -> f32 {
+    let mut total = 0.0;
+    let mut count = 0;
+    for (dx, dz) in [(-1, -1), (0, -1), (-1, 0), (0, 0)] {
+        let block = corner + glm::vec3(dx, 0, dz);
+        if world.block(block + glm::vec3(0, 1, 0)) == BlockKind::Water {
+            return 1.0;
+        }
+        if world.block(block) == BlockKind::Water {
+            total += world.water_height(block);
+            count += 1;
+        }
+    }
+    total / count.max(1) as f32
+}
+
+fn mesh_translucent_blocks(world: &World, water: bool) -> TranslucentMesh {
+    let mut vertices = vec![];
+    let mut indices = vec![];
+    let mut face_centers = vec![];
+    let size = super::chunk::CHUNK_SIZE;
+    for chunk in world.chunks.iter() {
+        let origin = chunk.origin();
+        for y in 0..size {
+            for z in 0..size {
+                for x in 0..size {
+                    let block = chunk.get(x, y, z);
+                    if !block.is_translucent() || (block == BlockKind::Water) != water {
+                        continue;
+                    }
+                    let position = origin + glm::vec3(x, y, z);
+                    for face in CUBE_FACES.iter() {
+                        let normal = glm::vec3(face.0[0], face.0[1], face.0[2]);
+                        let neighbour = world.block(position + normal);
+                        // a lowered surface is seen under an opaque block
+                        let hidden = neighbour.is_opaque() && !(water && normal.y == 1);
+                        if hidden || neighbour == block {
+                            continue;
+                        }
+                        let block_position = glm::convert::<glm::IVec3, glm::Vec3>(position);
+                        let normal = glm::convert::<glm::IVec3, glm::Vec3>(normal);
+                        let first_vertex = vertices.len();
+                        push_face(
+                            &mut vertices,
+                            &mut indices,
+                            block_position,
+                            face,
+                            block.texture(normal.y).layer(),
+                            glm::vec3(1.0, 1.0, 1.0),
+                        );
+                        let face_vertices = &mut vertices[first_vertex..];
+                        if water {
+                            for vertex in face_vertices.iter_mut() {
+                                if vertex.position.y > block_position.y + 0.5 {
+                                    let corner = vertex.position.map(|c| c.round() as i32);
+                                    vertex.position.y = block_position.y
+                                        + water_corner_height(world, corner - glm::vec3(0, 1, 0));
+                                }
+                            }
+                        }
+                        let center = face_vertices
+                            .iter()
+                            .fold(glm::vec3(0.0, 0.0, 0.0), |sum, vertex| {
+                                sum + vertex.position
+                            })
+                            / 4.0;
+                        face_centers.push(center);
+                    }
+                }
+            }
+        }
+    }
+    TranslucentMesh {
+        vertices,
+        face_centers,
+    }
+}
+
+/// Indices drawing the faces of a `TranslucentMesh` farthest from `eye`
+/// first, so each blends over the ones behind it.
+pub fn back_to_front_indices(face_centers: &[glm::Vec3], eye: &glm::Vec3) -> Vec<u32> {
+    let mut order: Vec<(f32, u32)> = face_centers
+        .iter()
+        .enumerate()
+        .map(|(face, center)| (glm::distance2(center, eye), face as u32))
+        .collect();
+    order.sort_by(|a, b| b.0.total_cmp(&a.0));
+    order
+        .iter()
+        .flat_map(|(_, face)| {
+            let base = face * 4;
+            [base, base + 1, base + 2, base, base + 2, base + 3]
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod mesher_tests {
+    use super::*;
+    use crate::game::world::block::BlockKind;
+
+    #[test]
+    fn water_only_shows_its_surface() {
+        let mut world = World::empty();
+        for x in 0..2 {
+            world.set_block(glm::vec3(x, 0, 0), BlockKind::Water);
+        }
+        world.set_block(glm::vec3(0, 0, 1), BlockKind::Stone);
+        let mesh = mesh_water(&world);
+        assert!(mesh_translucent(&world).face_centers.is_empty());
+        // two blocks of six faces, minus the two they share and the one
+        // against the stone
+        assert_eq!(mesh.face_centers.len(), 9);
+        assert_eq!(mesh.vertices.len(), 36);
+        // the stone face behind the water is still part of the opaque mesh
+        let (_, opaque_indices) = mesh_chunk(&world, 0);
+        assert_eq!(opaque_indices.len(), 6 * 6);
+    }
+    #[test]
+    fn flowing_water_slopes_down_from_its_source() {
+        let mut world = World::empty();
+        for x in 0..12 {
+            world.set_block(glm::vec3(x, 0, 4), BlockKind::Stone);
+            for z in [3, 5] {
+                world.set_block(glm::vec3(x, 1, z), BlockKind::Stone);
+            }
+        }
+        world.flow_water(&[glm::vec3(0, 1, 4)]);
+        let mesh = mesh_water(&world);
+        let top_height = |x: f32| {
+            mesh.vertices
+                .iter()
+                .filter(|vertex| vertex.normal.y == 1.0 && vertex.position.x == x)
+                .map(|vertex| vertex.position.y)
+                .fold(0.0, f32::max)
+        };
+        let heights: Vec<f32> = (0..9).map(|x| top_height(x as f32)).collect();
+        assert!(
+            heights.windows(2).all(|pair| pair[0] > pair[1]),
+            "{:?}",
+            heights
+        );
+        assert!(heights[0] < 2.0 && heights[8] > 1.0);
+    }
+    #[test]
+    fn plants_are_two_quads_and_leaves_keep_shared_faces() {
+        let mut world = World::empty();
+        world.set_block(glm::vec3(0, 0, 0), BlockKind::Flower);
+        world.set_block(glm::vec3(2, 0, 0), BlockKind::Leaves);
+        world.set_block(glm::vec3(3, 0, 0), BlockKind::Leaves);
+        world.set_block(glm::vec3(2, 1, 0), BlockKind::Stone);
+        let (vertices, indices) = mesh_cutout(&world);
+        // 2 plant quads, then 12 leaf faces less the one under the stone
+        assert_eq!(indices.len(), (2 + 11) * 6);
+        assert_eq!(vertices.len(), (2 + 11) * 4);
+        for vertex in &vertices[..8] {
+            assert_eq!(vertex.position.y.fract(), 0.0);
+            assert!(vertex.position.x <= 1.0 && vertex.position.z <= 1.0);
+        }
+    }
+    #[test]
+    fn faces_are_sorted_far_to_near() {
+        let centers = [
+            glm::vec3(1.0, 0.0, 0.0),
+            glm::vec3(5.0, 0.0, 0.0),
+            glm::vec3(3.0, 0.0, 0.0),
+        ];
+        let indices = back_to_front_indices(&centers, &glm::vec3(0.0, 0.0, 0.0));
+        let faces: Vec<u32> = indices.chunks(6).map(|quad| quad[0] / 4).collect();
+        assert_eq!(faces, vec![1, 2, 0]);
+        assert_eq!(&indices[..6], &[4, 5, 6, 4, 6, 7]);
+    }
+}
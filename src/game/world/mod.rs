@@ -0,0 +1,286 @@
+pub mod block;
+pub mod chunk;
+pub mod clouds;
+pub mod mesher;
+pub mod visibility;
+pub mod weather;
+use block::BlockKind;
+use chunk::{Chunk, CHUNK_SIZE};
+use nalgebra_glm as glm;
+use std::collections::VecDeque;
+
+/// Number of chunks along the x and z axes.
+pub const WORLD_SIZE_CHUNKS: i32 = 8;
+/// Number of chunk sections stacked along the y axis.
+pub const WORLD_HEIGHT_CHUNKS: i32 = 4;
+
+/// Air at or below this height over the terrain is filled with water.
+pub const SEA_LEVEL: i32 = 24;
+
+/// Water spreads this many blocks sideways from a source over level ground.
+pub const WATER_FLOW_DISTANCE: u8 = 7;
+
+/// Mixed into the world seed to scatter plants independently of terrain.
+const FOLIAGE_SEED: u32 = 0x9e37_79b9;
+/// Mixed into the world seed to place springs.
+const SPRING_SEED: u32 = 0x27d4_eb2f;
+/// Chance of a spring on a column high enough above the sea.
+const SPRING_CHANCE: f32 = 0.0015;
+/// How far above sea level springs rise, so their water has somewhere to
+/// run.
+const SPRING_MIN_HEIGHT: i32 = 4;
+
+/// A fixed-size block world starting at the origin.
+pub struct World {
+    pub chunks: Vec<Chunk>,
+}
+impl World {
+    /// A world made only of air.
+    pub fn empty() -> Self {
+        let mut chunks = vec![];
+        for y in 0..WORLD_HEIGHT_CHUNKS {
+            for z in 0..WORLD_SIZE_CHUNKS {
+                for x in 0..WORLD_SIZE_CHUNKS {
+                    chunks.push(Chunk::new(glm::vec3(x, y, z)));
+                }
+            }
+        }
+        World { chunks }
+    }
+    pub fn generate(seed: u32) -> Self {
+        let mut world = World::empty();
+        let size = WORLD_SIZE_CHUNKS * CHUNK_SIZE;
+        let mut springs = vec![];
+        for z in 0..size {
+            for x in 0..size {
+                let height = terrain_height(seed, x, z);
+                for y in 0..=height.max(SEA_LEVEL) {
+                    let block = if y > height && y == SEA_LEVEL && World::is_cold(x, z) {
+                        // the sea freezes over in the coldest corner
+                        BlockKind::Ice
+                    } else if y > height {
+                        BlockKind::Water
+                    } else if y == height && height < SEA_LEVEL {
+                        BlockKind::Dirt
+                    } else if y == height {
+                        BlockKind::Grass
+                    } else if y + 4 > height {
+                        BlockKind::Dirt
+                    } else {
+                        BlockKind::Stone
+                    };
+                    world.set_block(glm::vec3(x, y, z), block);
+                }
+                if height >= SEA_LEVEL + SPRING_MIN_HEIGHT
+                    && lattice_value(seed ^ SPRING_SEED, x, z) < SPRING_CHANCE
+                {
+                    springs.push(glm::vec3(x, height + 1, z));
+                } else if height >= SEA_LEVEL {
+                    let above = glm::vec3(x, height + 1, z);
+                    match lattice_value(seed ^ FOLIAGE_SEED, x, z) {
+                        roll if roll < 0.015 => {
+                            world.set_block(above, BlockKind::Leaves);
+                            world.set_block(above + glm::vec3(0, 1, 0), BlockKind::Leaves);
+                        }
+                        roll if roll < 0.035 => world.set_block(above, BlockKind::Flower),
+                        roll if roll < 0.15 => world.set_block(above, BlockKind::TallGrass),
+                        _ => {}
+                    }
+                }
+            }
+        }
+        world.flow_water(&springs);
+        world
+    }
+    pub fn chunk_index(&self, chunk_position: glm::IVec3) -> Option<usize> {
+        let (x, y, z) = (chunk_position.x, chunk_position.y, chunk_position.z);
+        if x < 0
+            || z < 0
+            || y < 0
+            || x >= WORLD_SIZE_CHUNKS
+            || z >= WORLD_SIZE_CHUNKS
+            || y >= WORLD_HEIGHT_CHUNKS
+        {
+            return None;
+        }
+        Some((x + WORLD_SIZE_CHUNKS * (z + WORLD_SIZE_CHUNKS * y)) as usize)
+    }
+    /// Chunk coordinates of the chunk holding this block.
+    pub fn chunk_position(block_position: glm::IVec3) -> glm::IVec3 {
+        block_position.map(|c| c.div_euclid(CHUNK_SIZE))
+    }
+    /// Blocks outside the world read as air.
+    pub fn block(&self, block_position: glm::IVec3) -> BlockKind {
+        match self.chunk_index(World::chunk_position(block_position)) {
+            Some(index) => {
+                let local = block_position.map(|c| c.rem_euclid(CHUNK_SIZE));
+                self.chunks[index].get(local.x, local.y, local.z)
+            }
+            None => BlockKind::Air,
+        }
+    }
+    pub fn set_block(&mut self, block_position: glm::IVec3, block: BlockKind) {
+        if let Some(index) = self.chunk_index(World::chunk_position(block_position)) {
+            let local = block_position.map(|c| c.rem_euclid(CHUNK_SIZE));
+            self.chunks[index].set(local.x, local.y, local.z, block);
+        }
+    }
+    /// How far the water at `block_position` is from its source, 0 for a
+    /// source or for anything that is not water.
+    pub fn flow(&self, block_position: glm::IVec3) -> u8 {
+        match self.chunk_index(World::chunk_position(block_position)) {
+            Some(index) => {
+                let local = block_position.map(|c| c.rem_euclid(CHUNK_SIZE));
+                self.chunks[index].flow(local.x, local.y, local.z)
+            }
+            None => 0,
+        }
+    }
+    fn set_water(&mut self, block_position: glm::IVec3, flow: u8) {
+        if let Some(index) = self.chunk_index(World::chunk_position(block_position)) {
+            let local = block_position.map(|c| c.rem_euclid(CHUNK_SIZE));
+            self.chunks[index].set(local.x, local.y, local.z, BlockKind::Water);
+            self.chunks[index].set_flow(local.x, local.y, local.z, flow);
+        }
+    }
+    /// How high the water surface stands in the block, from 8/9 for a
+    /// source down to 1/9 at the end of a flow.
+    pub fn water_height(&self, block_position: glm::IVec3) -> f32 {
+        let level = WATER_FLOW_DISTANCE + 1 - self.flow(block_position);
+        level as f32 / (WATER_FLOW_DISTANCE + 2) as f32
+    }
+    /// Places water sources at `sources` and lets it run: down into air
+    /// below, or else out sideways over the ground, one step further from
+    /// the source each block, up to `WATER_FLOW_DISTANCE`. Water landing
+    /// after a fall spreads as far again.
+    pub fn flow_water(&mut self, sources: &[glm::IVec3]) {
+        let mut queue = VecDeque::new();
+        for source in sources {
+            self.set_water(*source, 0);
+            queue.push_back(*source);
+        }
+        let inside = |world: &World, position: glm::IVec3| {
+            world.chunk_index(World::chunk_position(position)).is_some()
+        };
+        while let Some(position) = queue.pop_front() {
+            let below = position - glm::vec3(0, 1, 0);
+            match self.block(below) {
+                BlockKind::Air if inside(self, below) => {
+                    self.set_water(below, 0);
+                    queue.push_back(below);
+                    continue;
+                }
+                // joins the water underneath
+                BlockKind::Water => continue,
+                _ => {}
+            }
+            let flow = self.flow(position) + 1;
+            if flow > WATER_FLOW_DISTANCE {
+                continue;
+            }
+            for offset in [[1, 0], [-1, 0], [0, 1], [0, -1]] {
+                let neighbour = position + glm::vec3(offset[0], 0, offset[1]);
+                let spreads = match self.block(neighbour) {
+                    BlockKind::Air => inside(self, neighbour),
+                    BlockKind::Water => self.flow(neighbour) > flow,
+                    _ => false,
+                };
+                if spreads {
+                    self.set_water(neighbour, flow);
+                    queue.push_back(neighbour);
+                }
+            }
+        }
+    }
+    /// The coldest corner of the world, where the sea freezes over and
+    /// snow falls instead of rain.
+    pub fn is_cold(x: i32, z: i32) -> bool {
+        x + z < World::size() / 2
+    }
+    /// Blocks along each side of the world.
+    pub fn size() -> i32 {
+        WORLD_SIZE_CHUNKS * CHUNK_SIZE
+    }
+    /// The height of the ground in every block column, row by row along x:
+    /// the top of the highest block that is not air or a plant, or 0 where
+    /// there is none.
+    pub fn surface_heights(&self) -> Vec<f32> {
+        let size = World::size();
+        let top = WORLD_HEIGHT_CHUNKS * CHUNK_SIZE;
+        let mut heights = Vec::with_capacity((size * size) as usize);
+        for z in 0..size {
+            for x in 0..size {
+                let ground = (0..top).rev().find(|y| {
+                    let block = self.block(glm::vec3(x, *y, z));
+                    block != BlockKind::Air && !block.is_plant()
+                });
+                heights.push(ground.map_or(0.0, |y| (y + 1) as f32));
+            }
+        }
+        heights
+    }
+}
+
+/// Smoothly interpolated value noise, summed over two octaves.
+fn terrain_height(seed: u32, x: i32, z: i32) -> i32 {
+    let octave = |scale: f32, amplitude: f32| {
+        let fx = x as f32 / scale;
+        let fz = z as f32 / scale;
+        let (x0, z0) = (fx.floor() as i32, fz.floor() as i32);
+        let (tx, tz) = (smooth(fx - fx.floor()), smooth(fz - fz.floor()));
+        let corner = |dx: i32, dz: i32| lattice_value(seed, x0 + dx, z0 + dz);
+        let top = corner(0, 0) + (corner(1, 0) - corner(0, 0)) * tx;
+        let bottom = corner(0, 1) + (corner(1, 1) - corner(0, 1)) * tx;
+        (top + (bottom - top) * tz) * amplitude
+    };
+    20 + (octave(32.0, 16.0) + octave(8.0, 4.0)) as i32
+}
+fn smooth(t: f32) -> f32 {
+    t * t * (3.0 - 2.0 * t)
+}
+fn lattice_value(seed: u32, x: i32, z: i32) -> f32 {
+    let mut h = (x as u32).wrapping_mul(374761393)
+        ^ (z as u32).wrapping_mul(668265263)
+        ^ seed.wrapping_mul(2246822519);
+    h = (h ^ (h >> 13)).wrapping_mul(1274126177);
+    (h ^ (h >> 16)) as f32 / u32::MAX as f32
+}
+
+#[cfg(test)]
+mod world_tests {
+    use super::*;
+    #[test]
+    fn surface_heights_skip_air_and_plants() {
+        let mut world = World::empty();
+        world.set_block(glm::vec3(1, 0, 0), BlockKind::Stone);
+        world.set_block(glm::vec3(1, 5, 0), BlockKind::Water);
+        world.set_block(glm::vec3(2, 3, 0), BlockKind::Dirt);
+        world.set_block(glm::vec3(2, 4, 0), BlockKind::TallGrass);
+        let heights = world.surface_heights();
+        assert_eq!(heights.len(), (World::size() * World::size()) as usize);
+        assert_eq!(&heights[..4], &[0.0, 6.0, 4.0, 0.0]);
+    }
+    #[test]
+    fn water_runs_down_and_thins_out_over_level_ground() {
+        let mut world = World::empty();
+        for z in 0..20 {
+            for x in 0..20 {
+                world.set_block(glm::vec3(x, 0, z), BlockKind::Stone);
+            }
+        }
+        world.set_block(glm::vec3(2, 3, 10), BlockKind::Stone);
+        world.flow_water(&[glm::vec3(2, 4, 10)]);
+        // off the pillar's edge and down to the ground
+        assert_eq!(world.block(glm::vec3(3, 4, 10)), BlockKind::Water);
+        assert_eq!(world.flow(glm::vec3(3, 4, 10)), 1);
+        assert_eq!(world.block(glm::vec3(3, 1, 10)), BlockKind::Water);
+        assert_eq!(world.flow(glm::vec3(3, 1, 10)), 0);
+        // spreading out from where it landed
+        assert_eq!(world.flow(glm::vec3(6, 1, 10)), 3);
+        let last = glm::vec3(3 + WATER_FLOW_DISTANCE as i32, 1, 10);
+        assert_eq!(world.block(last), BlockKind::Water);
+        assert_eq!(world.block(last + glm::vec3(1, 0, 0)), BlockKind::Air);
+        assert!(world.water_height(glm::vec3(3, 1, 10)) > world.water_height(last));
+        assert!(world.water_height(last) > 0.0);
+    }
+}
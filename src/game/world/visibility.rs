@@ -0,0 +1,251 @@
+use super::chunk::{Chunk, CHUNK_SIZE};
+use super::World;
+use crate::game::game_objects::frustum::Frustum;
+use nalgebra_glm as glm;
+use std::collections::VecDeque;
+
+/// A side of a chunk section.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Face {
+    NegX,
+    PosX,
+    NegY,
+    PosY,
+    NegZ,
+    PosZ,
+}
+impl Face {
+    pub const ALL: [Face; 6] = [
+        Face::NegX,
+        Face::PosX,
+        Face::NegY,
+        Face::PosY,
+        Face::NegZ,
+        Face::PosZ,
+    ];
+    /// Direction to the neighbouring section across this face.
+    pub fn offset(&self) -> glm::IVec3 {
+        match self {
+            Face::NegX => glm::vec3(-1, 0, 0),
+            Face::PosX => glm::vec3(1, 0, 0),
+            Face::NegY => glm::vec3(0, -1, 0),
+            Face::PosY => glm::vec3(0, 1, 0),
+            Face::NegZ => glm::vec3(0, 0, -1),
+            Face::PosZ => glm::vec3(0, 0, 1),
+        }
+    }
+    pub fn opposite(&self) -> Face {
+        match self {
+            Face::NegX => Face::PosX,
+            Face::PosX => Face::NegX,
+            Face::NegY => Face::PosY,
+            Face::PosY => Face::NegY,
+            Face::NegZ => Face::PosZ,
+            Face::PosZ => Face::NegZ,
+        }
+    }
+    fn bit(&self) -> u8 {
+        1 << (*self as u8)
+    }
+}
+
+/// Which pairs of faces of a chunk section are joined by a path through
+/// non-opaque blocks, one bit per pair.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub struct ChunkVisibility {
+    connections: u64,
+}
+impl ChunkVisibility {
+    /// Every face sees every other face, as in an empty section.
+    pub fn all() -> Self {
+        let mut visibility = ChunkVisibility::default();
+        visibility.connect_faces(0b11_1111);
+        visibility
+    }
+    /// Flood fills each region of non-opaque blocks and connects all the
+    /// faces the region touches.
+    pub fn compute(chunk: &Chunk) -> Self {
+        if chunk.is_empty() {
+            return ChunkVisibility::all();
+        }
+        let mut visibility = ChunkVisibility::default();
+        let size = CHUNK_SIZE as usize;
+        let mut visited = vec![false; size * size * size];
+        let index = |p: glm::IVec3| (p.x + CHUNK_SIZE * (p.z + CHUNK_SIZE * p.y)) as usize;
+        let mut stack = vec![];
+        for y in 0..CHUNK_SIZE {
+            for z in 0..CHUNK_SIZE {
+                for x in 0..CHUNK_SIZE {
+                    let start = glm::vec3(x, y, z);
+                    if visited[index(start)] || chunk.get(x, y, z).is_opaque() {
+                        continue;
+                    }
+                    visited[index(start)] = true;
+                    stack.push(start);
+                    let mut faces = 0_u8;
+                    while let Some(block) = stack.pop() {
+                        for face in Face::ALL {
+                            let next = block + face.offset();
+                            if [next.x, next.y, next.z]
+                                .iter()
+                                .any(|c| *c < 0 || *c >= CHUNK_SIZE)
+                            {
+                                faces |= face.bit();
+                                continue;
+                            }
+                            if visited[index(next)] || chunk.get(next.x, next.y, next.z).is_opaque()
+                            {
+                                continue;
+                            }
+                            visited[index(next)] = true;
+                            stack.push(next);
+                        }
+                    }
+                    visibility.connect_faces(faces);
+                }
+            }
+        }
+        visibility
+    }
+    fn connect_faces(&mut self, faces: u8) {
+        for a in Face::ALL {
+            for b in Face::ALL {
+                if faces & a.bit() != 0 && faces & b.bit() != 0 {
+                    self.connections |= 1 << (a as u64 * 6 + b as u64);
+                }
+            }
+        }
+    }
+    pub fn connects(&self, a: Face, b: Face) -> bool {
+        self.connections & (1 << (a as u64 * 6 + b as u64)) != 0
+    }
+}
+
+/// Finds the sections that can be seen from `camera_position` by walking
+/// from the camera's section through faces its neighbours connect, never
+/// doubling back on a direction already taken. Returns one flag per
+/// `World::chunks` entry. A camera outside the world sees everything.
+pub fn visible_chunks(
+    world: &World,
+    visibilities: &[ChunkVisibility],
+    camera_position: &glm::Vec3,
+    frustum: Option<&Frustum>,
+) -> Vec<bool> {
+    let camera_block = camera_position.map(|c| c.floor() as i32);
+    let start = World::chunk_position(camera_block);
+    let start_index = match world.chunk_index(start) {
+        Some(index) => index,
+        None => return vec![true; world.chunks.len()],
+    };
+    let mut visible = vec![false; world.chunks.len()];
+    visible[start_index] = true;
+    // (section, face it was entered through, directions travelled so far)
+    let mut queue: VecDeque<(glm::IVec3, Option<Face>, u8)> = VecDeque::new();
+    queue.push_back((start, None, 0));
+    while let Some((position, entered_through, directions)) = queue.pop_front() {
+        let index = world.chunk_index(position).unwrap();
+        for face in Face::ALL {
+            if directions & face.opposite().bit() != 0 {
+                continue;
+            }
+            if let Some(entered_through) = entered_through {
+                if !visibilities[index].connects(entered_through, face) {
+                    continue;
+                }
+            }
+            let next = position + face.offset();
+            let next_index = match world.chunk_index(next) {
+                Some(next_index) => next_index,
+                None => continue,
+            };
+            if visible[next_index] {
+                continue;
+            }
+            if let Some(frustum) = frustum {
+                if !frustum.intersects_aabb(&world.chunks[next_index].aabb()) {
+                    continue;
+                }
+            }
+            visible[next_index] = true;
+            queue.push_back((next, Some(face.opposite()), directions | face.bit()));
+        }
+    }
+    visible
+}
+
+#[cfg(test)]
+mod visibility_tests {
+    use super::*;
+    use crate::game::world::block::BlockKind;
+    use crate::game::world::WORLD_SIZE_CHUNKS;
+
+    fn fill(chunk: &mut Chunk, block: BlockKind) {
+        for y in 0..CHUNK_SIZE {
+            for z in 0..CHUNK_SIZE {
+                for x in 0..CHUNK_SIZE {
+                    chunk.set(x, y, z, block);
+                }
+            }
+        }
+    }
+    #[test]
+    fn empty_and_solid_sections() {
+        let mut chunk = Chunk::new(glm::vec3(0, 0, 0));
+        assert_eq!(ChunkVisibility::compute(&chunk), ChunkVisibility::all());
+        fill(&mut chunk, BlockKind::Stone);
+        let solid = ChunkVisibility::compute(&chunk);
+        for a in Face::ALL {
+            for b in Face::ALL {
+                assert!(!solid.connects(a, b));
+            }
+        }
+    }
+    #[test]
+    fn a_wall_splits_the_section() {
+        let mut chunk = Chunk::new(glm::vec3(0, 0, 0));
+        for y in 0..CHUNK_SIZE {
+            for z in 0..CHUNK_SIZE {
+                chunk.set(8, y, z, BlockKind::Stone);
+            }
+        }
+        let visibility = ChunkVisibility::compute(&chunk);
+        assert!(!visibility.connects(Face::NegX, Face::PosX));
+        assert!(visibility.connects(Face::NegX, Face::PosY));
+        assert!(visibility.connects(Face::PosX, Face::NegZ));
+        assert!(visibility.connects(Face::NegY, Face::PosY));
+    }
+    #[test]
+    fn a_tunnel_connects_only_its_ends() {
+        let mut chunk = Chunk::new(glm::vec3(0, 0, 0));
+        fill(&mut chunk, BlockKind::Stone);
+        for x in 0..CHUNK_SIZE {
+            chunk.set(x, 5, 5, BlockKind::Air);
+        }
+        let visibility = ChunkVisibility::compute(&chunk);
+        assert!(visibility.connects(Face::NegX, Face::PosX));
+        assert!(visibility.connects(Face::PosX, Face::NegX));
+        assert!(!visibility.connects(Face::NegX, Face::PosY));
+        assert!(!visibility.connects(Face::NegY, Face::PosY));
+    }
+    #[test]
+    fn solid_ground_hides_the_sections_below() {
+        let mut world = World::empty();
+        // a solid layer of stone filling the second row of sections
+        let size = WORLD_SIZE_CHUNKS * CHUNK_SIZE;
+        for y in CHUNK_SIZE..2 * CHUNK_SIZE {
+            for z in 0..size {
+                for x in 0..size {
+                    world.set_block(glm::vec3(x, y, z), BlockKind::Stone);
+                }
+            }
+        }
+        let visibilities: Vec<ChunkVisibility> =
+            world.chunks.iter().map(ChunkVisibility::compute).collect();
+        let camera = glm::vec3(8.0, 40.0, 8.0);
+        let visible = visible_chunks(&world, &visibilities, &camera, None);
+        for (index, chunk) in world.chunks.iter().enumerate() {
+            // the stone layer itself is visible, what is under it is not
+            assert_eq!(visible[index], chunk.position.y >= 1, "{:?}", chunk.position);
+        }
+    }
+}
@@ -0,0 +1,289 @@
+use super::{lattice_value, World};
+use crate::game::world_time::TICKS_PER_SECOND;
+use crate::render_systems::particle_system::{ParticleKind, MAX_PARTICLES};
+use crate::render_systems::Lighting;
+use nalgebra_glm as glm;
+use std::str::FromStr;
+
+/// Game ticks one spell of weather lasts, five minutes of real time.
+pub const WEATHER_SPELL_TICKS: u64 = 6000;
+/// Ticks over which the sky and precipitation change between spells.
+const WEATHER_TRANSITION_TICKS: u64 = 200;
+/// Mixed into the world seed so the weather does not follow the terrain.
+const WEATHER_SEED: u32 = 0xc2b2_ae35;
+/// Precipitation falls within this many blocks of the camera along x and z.
+const PRECIPITATION_RADIUS: f32 = 24.0;
+/// How far above the camera, or the ground when that is higher,
+/// precipitation starts.
+const PRECIPITATION_HEIGHT: f32 = 16.0;
+/// Most snowflakes spawned in one tick, also where rain turns to snow over
+/// cold columns. Flakes live long, so this keeps them to four fifths of the
+/// particle ring and leaves the rest for other particles.
+const MAX_SNOWFLAKES_PER_TICK: usize = (MAX_PARTICLES as f32 * 0.8
+    / (ParticleKind::Snow.lifetime() * TICKS_PER_SECOND as f32))
+    as usize;
+/// The overcast sky fades towards this colour.
+const OVERCAST_SKY: [f32; 3] = [0.45, 0.48, 0.52];
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Weather {
+    Clear,
+    /// Rain, or snow in cold places.
+    Rain,
+    /// Heavier rain under a darker sky.
+    Thunder,
+    /// Snow everywhere.
+    Snow,
+}
+impl Weather {
+    /// How much of the sky's light the clouds take away, from 0 to 1.
+    fn dimming(&self) -> f32 {
+        match self {
+            Weather::Clear => 0.0,
+            Weather::Rain => 0.35,
+            Weather::Thunder => 0.55,
+            Weather::Snow => 0.25,
+        }
+    }
+    /// Particles spawned around the camera every tick.
+    fn precipitation(&self) -> f32 {
+        match self {
+            Weather::Clear => 0.0,
+            Weather::Rain => 40.0,
+            Weather::Thunder => 80.0,
+            Weather::Snow => MAX_SNOWFLAKES_PER_TICK as f32,
+        }
+    }
+}
+impl FromStr for Weather {
+    type Err = String;
+    fn from_str(name: &str) -> Result<Self, Self::Err> {
+        match name {
+            "clear" => Ok(Weather::Clear),
+            "rain" => Ok(Weather::Rain),
+            "thunder" => Ok(Weather::Thunder),
+            "snow" => Ok(Weather::Snow),
+            _ => Err(format!(
+                "Unknown weather '{}', expected clear, rain, thunder or snow",
+                name
+            )),
+        }
+    }
+}
+
+/// The weather at one tick, part way through changing from the previous
+/// spell's weather.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct WeatherState {
+    pub weather: Weather,
+    pub previous: Weather,
+    /// From 0 at the start of the spell to 1 once the change is over.
+    pub transition: f32,
+}
+impl WeatherState {
+    fn blend(&self, value: impl Fn(Weather) -> f32) -> f32 {
+        let (from, to) = (value(self.previous), value(self.weather));
+        from + (to - from) * self.transition
+    }
+    /// Dims the sun and ambient light and greys the sky.
+    pub fn darken(&self, lighting: Lighting) -> Lighting {
+        let dimming = self.blend(|weather| weather.dimming());
+        let overcast = glm::Vec3::from(OVERCAST_SKY) * lighting.daylight;
+        let grey = |color: &glm::Vec3| glm::lerp(color, &overcast, dimming) * (1.0 - dimming * 0.5);
+        // clouds hide the sun, so sunlit faces drop towards the ambient level
+        let sunlight = lighting.sunlight + (lighting.ambient - lighting.sunlight) * dimming;
+        Lighting {
+            ambient: lighting.ambient * (1.0 - dimming * 0.5),
+            sunlight: sunlight * (1.0 - dimming * 0.5),
+            sky_zenith: grey(&lighting.sky_zenith),
+            sky_horizon: grey(&lighting.sky_horizon),
+            ..lighting
+        }
+    }
+    /// Rain and snow to spawn this tick around `eye`, each placed over a
+    /// random column above its ground so it never starts under a roof.
+    /// Rain turns to snow over cold columns, up to `MAX_SNOWFLAKES_PER_TICK`.
+    pub fn precipitation(
+        &self,
+        ticks: u64,
+        eye: &glm::Vec3,
+        heights: &[f32],
+    ) -> Vec<(ParticleKind, glm::Vec3)> {
+        // while clearing up, the last spell's precipitation tails off
+        let falling = if self.weather.precipitation() > 0.0 {
+            self.weather
+        } else {
+            self.previous
+        };
+        let count = self.blend(|weather| weather.precipitation()).round() as i32;
+        let size = World::size();
+        let mut snowflakes = 0;
+        (0..count)
+            .map(|i| {
+                let seed = ticks as u32;
+                let random = |salt: i32| lattice_value(seed ^ WEATHER_SEED, i, salt) * 2.0 - 1.0;
+                let x = eye.x + random(0) * PRECIPITATION_RADIUS;
+                let z = eye.z + random(1) * PRECIPITATION_RADIUS;
+                let (column_x, column_z) = (x.floor() as i32, z.floor() as i32);
+                let ground = if (0..size).contains(&column_x) && (0..size).contains(&column_z) {
+                    heights[(column_z * size + column_x) as usize]
+                } else {
+                    0.0
+                };
+                let kind = if falling == Weather::Snow || World::is_cold(column_x, column_z) {
+                    ParticleKind::Snow
+                } else {
+                    ParticleKind::Rain
+                };
+                let y = eye.y.max(ground) + PRECIPITATION_HEIGHT;
+                (kind, glm::vec3(x, y, z))
+            })
+            .filter(|(kind, _)| {
+                if *kind == ParticleKind::Snow {
+                    snowflakes += 1;
+                    snowflakes <= MAX_SNOWFLAKES_PER_TICK
+                } else {
+                    true
+                }
+            })
+            .collect()
+    }
+}
+
+/// Which weather each spell brings, decided by the world seed. A debug
+/// command can override the current spell.
+pub struct WeatherSchedule {
+    seed: u32,
+    /// A spell and the weather forced on it.
+    forced: Option<(u64, Weather)>,
+}
+impl WeatherSchedule {
+    pub fn new(seed: u32) -> Self {
+        WeatherSchedule { seed, forced: None }
+    }
+    fn spell_weather(&self, spell: u64) -> Weather {
+        match self.forced {
+            Some((forced_spell, weather)) if forced_spell == spell => return weather,
+            _ => {}
+        }
+        match lattice_value(self.seed ^ WEATHER_SEED, spell as i32, (spell >> 32) as i32) {
+            roll if roll < 0.55 => Weather::Clear,
+            roll if roll < 0.8 => Weather::Rain,
+            roll if roll < 0.9 => Weather::Thunder,
+            _ => Weather::Snow,
+        }
+    }
+    pub fn state(&self, ticks: u64) -> WeatherState {
+        let spell = ticks / WEATHER_SPELL_TICKS;
+        let weather = self.spell_weather(spell);
+        let previous = match spell {
+            0 => weather,
+            _ => self.spell_weather(spell - 1),
+        };
+        let into_spell = ticks % WEATHER_SPELL_TICKS;
+        WeatherState {
+            weather,
+            previous,
+            transition: (into_spell as f32 / WEATHER_TRANSITION_TICKS as f32).min(1.0),
+        }
+    }
+    /// Makes the rest of the current spell `weather`.
+    pub fn force(&mut self, ticks: u64, weather: Weather) {
+        self.forced = Some((ticks / WEATHER_SPELL_TICKS, weather));
+    }
+}
+
+#[cfg(test)]
+mod weather_tests {
+    use super::*;
+    use crate::game::world_time::{TimeOfDay, WorldTime};
+    #[test]
+    fn the_schedule_is_seeded_and_varied() {
+        let first = WeatherSchedule::new(1337);
+        let second = WeatherSchedule::new(1337);
+        let spells: Vec<Weather> = (0..64)
+            .map(|spell| first.state(spell * WEATHER_SPELL_TICKS + 300).weather)
+            .collect();
+        for (spell, weather) in spells.iter().enumerate() {
+            assert_eq!(
+                second.state(spell as u64 * WEATHER_SPELL_TICKS).weather,
+                *weather
+            );
+        }
+        for weather in [
+            Weather::Clear,
+            Weather::Rain,
+            Weather::Thunder,
+            Weather::Snow,
+        ] {
+            assert!(spells.contains(&weather), "{:?}", weather);
+        }
+    }
+    #[test]
+    fn forcing_lasts_one_spell_and_fades_in() {
+        let mut schedule = WeatherSchedule::new(1337);
+        let ticks = 5 * WEATHER_SPELL_TICKS + 10;
+        schedule.force(ticks, Weather::Thunder);
+        let state = schedule.state(ticks);
+        assert_eq!(state.weather, Weather::Thunder);
+        assert!(state.transition < 0.1);
+        let later = schedule.state(6 * WEATHER_SPELL_TICKS - 1);
+        assert_eq!((later.weather, later.transition), (Weather::Thunder, 1.0));
+        assert_eq!(
+            schedule.state(6 * WEATHER_SPELL_TICKS).previous,
+            Weather::Thunder
+        );
+    }
+    #[test]
+    fn storms_darken_the_sky_and_snow_falls_where_it_is_cold() {
+        let mut noon = WorldTime::default();
+        noon.set(TimeOfDay::Noon);
+        let clear = noon.lighting();
+        let storm = WeatherState {
+            weather: Weather::Thunder,
+            previous: Weather::Thunder,
+            transition: 1.0,
+        };
+        let dark = storm.darken(clear);
+        assert!(dark.sunlight < clear.sunlight && dark.ambient < clear.ambient);
+        assert!(dark.sunlight >= dark.ambient);
+        assert!(glm::length(&dark.sky_zenith) < glm::length(&clear.sky_zenith));
+
+        let size = World::size();
+        let heights = vec![10.0; (size * size) as usize];
+        let rain = WeatherState {
+            weather: Weather::Rain,
+            ..storm
+        };
+        let cold_eye = glm::vec3(4.0, 5.0, 4.0);
+        let warm_eye = glm::vec3(size as f32 - 4.0, 5.0, size as f32 - 4.0);
+        let cold = rain.precipitation(7, &cold_eye, &heights);
+        let warm = rain.precipitation(7, &warm_eye, &heights);
+        assert_eq!(cold.len(), MAX_SNOWFLAKES_PER_TICK);
+        assert!(cold.iter().all(|(kind, _)| *kind == ParticleKind::Snow));
+        assert!(warm.iter().all(|(kind, _)| *kind == ParticleKind::Rain));
+        // spawned above the ground, not just above the camera below it
+        assert!(warm.iter().all(|(_, position)| position.y > 10.0));
+        let clear_sky = WeatherState {
+            weather: Weather::Clear,
+            previous: Weather::Clear,
+            transition: 1.0,
+        };
+        assert!(clear_sky.precipitation(7, &warm_eye, &heights).is_empty());
+    }
+    #[test]
+    fn precipitation_fits_in_the_particle_ring() {
+        let lifetime_ticks = |kind: ParticleKind| kind.lifetime() * TICKS_PER_SECOND as f32;
+        for weather in [
+            Weather::Clear,
+            Weather::Rain,
+            Weather::Thunder,
+            Weather::Snow,
+        ] {
+            let rate = weather.precipitation();
+            assert!(rate * lifetime_ticks(ParticleKind::Rain) <= MAX_PARTICLES as f32);
+            let snow_rate = rate.min(MAX_SNOWFLAKES_PER_TICK as f32);
+            assert!(snow_rate * lifetime_ticks(ParticleKind::Snow) <= MAX_PARTICLES as f32);
+        }
+    }
+}
@@ -0,0 +1,157 @@
+use crate::render_systems::Lighting;
+use nalgebra_glm as glm;
+use std::f32::consts::PI;
+use std::str::FromStr;
+use std::time;
+
+/// Game ticks per second of real time.
+pub const TICKS_PER_SECOND: u32 = 20;
+pub const TICK_DURATION: time::Duration =
+    time::Duration::from_millis(1000 / TICKS_PER_SECOND as u64);
+/// One full day and night, twenty minutes of real time.
+pub const TICKS_PER_DAY: u64 = 24000;
+/// Tilts the sun's path away from straight overhead so noon shadows are
+/// not hidden under the blocks that cast them.
+const SUN_TILT: f32 = 0.25;
+/// How far below the horizon, as the sine of the sun's height, twilight
+/// lasts.
+const TWILIGHT: f32 = 0.2;
+const DAY_AMBIENT: f32 = 0.6;
+const NIGHT_AMBIENT: f32 = 0.12;
+const DAY_ZENITH: [f32; 3] = [0.22, 0.45, 0.88];
+const DAY_HORIZON: [f32; 3] = [0.62, 0.78, 0.95];
+const NIGHT_ZENITH: [f32; 3] = [0.004, 0.008, 0.03];
+const NIGHT_HORIZON: [f32; 3] = [0.02, 0.035, 0.08];
+const SUNSET_HORIZON: [f32; 3] = [0.95, 0.48, 0.22];
+
+/// Named times a debug command can jump to.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum TimeOfDay {
+    Dawn,
+    Noon,
+    Dusk,
+    Midnight,
+}
+impl TimeOfDay {
+    /// Ticks into the day, which starts at sunrise.
+    pub fn ticks(&self) -> u64 {
+        match self {
+            TimeOfDay::Dawn => 0,
+            TimeOfDay::Noon => TICKS_PER_DAY / 4,
+            TimeOfDay::Dusk => TICKS_PER_DAY / 2,
+            TimeOfDay::Midnight => TICKS_PER_DAY * 3 / 4,
+        }
+    }
+}
+impl FromStr for TimeOfDay {
+    type Err = String;
+    fn from_str(name: &str) -> Result<Self, Self::Err> {
+        match name {
+            "dawn" => Ok(TimeOfDay::Dawn),
+            "noon" => Ok(TimeOfDay::Noon),
+            "dusk" => Ok(TimeOfDay::Dusk),
+            "midnight" => Ok(TimeOfDay::Midnight),
+            _ => Err(format!(
+                "Unknown time of day '{}', expected dawn, noon, dusk or midnight",
+                name
+            )),
+        }
+    }
+}
+
+/// The world clock, counted in game ticks since the first sunrise.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct WorldTime {
+    pub ticks: u64,
+}
+impl WorldTime {
+    pub fn tick(&mut self) {
+        self.ticks += 1;
+    }
+    /// Jumps forward to the next time `time_of_day` comes around.
+    pub fn set(&mut self, time_of_day: TimeOfDay) {
+        let day_start = self.ticks - self.ticks % TICKS_PER_DAY;
+        let mut ticks = day_start + time_of_day.ticks();
+        if ticks < self.ticks {
+            ticks += TICKS_PER_DAY;
+        }
+        self.ticks = ticks;
+    }
+    /// How far through the day the clock is, from 0 at sunrise up to 1.
+    pub fn day_fraction(&self) -> f32 {
+        (self.ticks % TICKS_PER_DAY) as f32 / TICKS_PER_DAY as f32
+    }
+    /// Points towards the sun, which rises in +x, stands highest at noon and
+    /// sets in -x. The moon is always opposite.
+    pub fn sun_direction(&self) -> glm::Vec3 {
+        let angle = self.day_fraction() * 2.0 * PI;
+        glm::normalize(&glm::vec3(angle.cos(), angle.sin(), SUN_TILT))
+    }
+    /// From 0 at night to 1 by day, easing through twilight.
+    pub fn daylight(&self) -> f32 {
+        let height = self.sun_direction().y;
+        let t = ((height + TWILIGHT) / (2.0 * TWILIGHT)).clamp(0.0, 1.0);
+        t * t * (3.0 - 2.0 * t)
+    }
+    pub fn lighting(&self) -> Lighting {
+        let sun_direction = self.sun_direction();
+        let daylight = self.daylight();
+        let color = |day: [f32; 3], night: [f32; 3]| {
+            glm::lerp(&glm::Vec3::from(night), &glm::Vec3::from(day), daylight)
+        };
+        // the horizon glows while the sun is close to it
+        let sunset = (1.0 - sun_direction.y.abs() / TWILIGHT).clamp(0.0, 1.0);
+        let sky_horizon = glm::lerp(
+            &color(DAY_HORIZON, NIGHT_HORIZON),
+            &glm::Vec3::from(SUNSET_HORIZON),
+            sunset * 0.6,
+        );
+        Lighting {
+            sun_direction,
+            ambient: NIGHT_AMBIENT + (DAY_AMBIENT - NIGHT_AMBIENT) * daylight,
+            sunlight: NIGHT_AMBIENT + (1.0 - NIGHT_AMBIENT) * daylight,
+            daylight,
+            sky_zenith: color(DAY_ZENITH, NIGHT_ZENITH),
+            sky_horizon,
+        }
+    }
+}
+
+#[cfg(test)]
+mod world_time_tests {
+    use super::*;
+    fn at(time_of_day: TimeOfDay) -> WorldTime {
+        let mut world_time = WorldTime::default();
+        world_time.set(time_of_day);
+        world_time
+    }
+    #[test]
+    fn the_sun_is_up_at_noon_and_down_at_midnight() {
+        assert!(at(TimeOfDay::Noon).sun_direction().y > 0.9);
+        assert!(at(TimeOfDay::Midnight).sun_direction().y < -0.9);
+        assert!(at(TimeOfDay::Dawn).sun_direction().y.abs() < 1e-5);
+        assert_eq!(at(TimeOfDay::Noon).daylight(), 1.0);
+        assert_eq!(at(TimeOfDay::Midnight).daylight(), 0.0);
+        let noon = at(TimeOfDay::Noon).lighting();
+        let midnight = at(TimeOfDay::Midnight).lighting();
+        assert!(noon.ambient > midnight.ambient);
+        assert!(noon.sunlight >= noon.ambient && midnight.sunlight >= midnight.ambient);
+        assert!(glm::length(&noon.sky_zenith) > glm::length(&midnight.sky_zenith));
+    }
+    #[test]
+    fn setting_the_time_only_moves_forward() {
+        let mut world_time = at(TimeOfDay::Midnight);
+        world_time.set(TimeOfDay::Noon);
+        assert_eq!(world_time.ticks, TICKS_PER_DAY + TimeOfDay::Noon.ticks());
+        world_time.tick();
+        assert!((world_time.day_fraction() - (0.25 + 1.0 / TICKS_PER_DAY as f32)).abs() < 1e-6);
+        world_time.set(TimeOfDay::Dawn);
+        assert_eq!(world_time.ticks, 2 * TICKS_PER_DAY);
+    }
+    #[test]
+    fn times_of_day_parse_by_name() {
+        assert_eq!("dawn".parse(), Ok(TimeOfDay::Dawn));
+        assert_eq!("midnight".parse(), Ok(TimeOfDay::Midnight));
+        assert!("teatime".parse::<TimeOfDay>().is_err());
+    }
+}
@@ -1,3 +1,111 @@
-struct Descriptor{
-    
-}
\ No newline at end of file
+use crate::core::Core;
+use ash::vk;
+use std::rc::Rc;
+pub struct Descriptor {
+    core: Rc<Core>,
+    pub set_layout: vk::DescriptorSetLayout,
+    pool: vk::DescriptorPool,
+    pub set: vk::DescriptorSet,
+}
+impl Descriptor {
+    pub fn new(core: Rc<Core>, bindings: &[vk::DescriptorSetLayoutBinding]) -> Self {
+        let layout_info = vk::DescriptorSetLayoutCreateInfo::builder()
+            .bindings(bindings)
+            .build();
+        let set_layout = unsafe {
+            core.logical_device
+                .create_descriptor_set_layout(&layout_info, None)
+                .expect("Failed to create descriptor set layout")
+        };
+        let pool_sizes: Vec<vk::DescriptorPoolSize> = bindings
+            .iter()
+            .map(|binding| vk::DescriptorPoolSize {
+                ty: binding.descriptor_type,
+                descriptor_count: binding.descriptor_count,
+            })
+            .collect();
+        let pool_info = vk::DescriptorPoolCreateInfo::builder()
+            .pool_sizes(&pool_sizes)
+            .max_sets(1)
+            .build();
+        let pool = unsafe {
+            core.logical_device
+                .create_descriptor_pool(&pool_info, None)
+                .expect("Failed to create descriptor pool")
+        };
+        let set_layouts = [set_layout];
+        let allocate_info = vk::DescriptorSetAllocateInfo::builder()
+            .descriptor_pool(pool)
+            .set_layouts(&set_layouts)
+            .build();
+        let set = unsafe {
+            core.logical_device
+                .allocate_descriptor_sets(&allocate_info)
+                .expect("Failed to allocate descriptor set")[0]
+        };
+        Descriptor {
+            core,
+            set_layout,
+            pool,
+            set,
+        }
+    }
+    pub fn write_image(
+        &self,
+        binding: u32,
+        descriptor_type: vk::DescriptorType,
+        image_view: vk::ImageView,
+        sampler: vk::Sampler,
+        image_layout: vk::ImageLayout,
+    ) {
+        let image_infos = [vk::DescriptorImageInfo {
+            sampler,
+            image_view,
+            image_layout,
+        }];
+        let write = vk::WriteDescriptorSet::builder()
+            .dst_set(self.set)
+            .dst_binding(binding)
+            .descriptor_type(descriptor_type)
+            .image_info(&image_infos)
+            .build();
+        unsafe {
+            self.core.logical_device.update_descriptor_sets(&[write], &[]);
+        }
+    }
+    pub fn write_buffer(
+        &self,
+        binding: u32,
+        descriptor_type: vk::DescriptorType,
+        buffer: vk::Buffer,
+        offset: vk::DeviceSize,
+        range: vk::DeviceSize,
+    ) {
+        let buffer_infos = [vk::DescriptorBufferInfo {
+            buffer,
+            offset,
+            range,
+        }];
+        let write = vk::WriteDescriptorSet::builder()
+            .dst_set(self.set)
+            .dst_binding(binding)
+            .descriptor_type(descriptor_type)
+            .buffer_info(&buffer_infos)
+            .build();
+        unsafe {
+            self.core.logical_device.update_descriptor_sets(&[write], &[]);
+        }
+    }
+}
+impl Drop for Descriptor {
+    fn drop(&mut self) {
+        unsafe {
+            self.core
+                .logical_device
+                .destroy_descriptor_pool(self.pool, None);
+            self.core
+                .logical_device
+                .destroy_descriptor_set_layout(self.set_layout, None);
+        }
+    }
+}
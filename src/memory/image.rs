@@ -1,84 +1,64 @@
-pub struct Image{
-    
+use crate::core::Core;
+use ash::vk;
+use std::rc::Rc;
+pub struct Image {
+    core: Rc<Core>,
+    pub handle: vk::Image,
+    pub view: vk::ImageView,
+    pub format: vk::Format,
+    pub mip_levels: u32,
+    pub array_layers: u32,
+    pub memory_requirements: vk::MemoryRequirements,
+    pub allocator_id: Option<usize>,
+    pub block_id: Option<usize>,
+}
+impl Image {
+    pub fn new(core: Rc<Core>, image_info: vk::ImageCreateInfo) -> Self {
+        let handle = unsafe {
+            core.logical_device
+                .create_image(&image_info, None)
+                .expect("Failed to create image")
+        };
+        Self {
+            handle,
+            view: vk::ImageView::null(),
+            format: image_info.format,
+            mip_levels: image_info.mip_levels,
+            array_layers: image_info.array_layers,
+            memory_requirements: unsafe {
+                core.logical_device.get_image_memory_requirements(handle)
+            },
+            allocator_id: None,
+            block_id: None,
+            core,
+        }
+    }
+    pub fn create_view(&mut self, view_type: vk::ImageViewType, aspect_mask: vk::ImageAspectFlags) {
+        let view_info = vk::ImageViewCreateInfo::builder()
+            .image(self.handle)
+            .view_type(view_type)
+            .format(self.format)
+            .subresource_range(vk::ImageSubresourceRange {
+                aspect_mask,
+                base_mip_level: 0,
+                level_count: self.mip_levels,
+                base_array_layer: 0,
+                layer_count: self.array_layers,
+            })
+            .build();
+        self.view = unsafe {
+            self.core
+                .logical_device
+                .create_image_view(&view_info, None)
+                .expect("Failed to create image view")
+        };
+    }
+}
+impl Drop for Image {
+    fn drop(&mut self) {
+        unsafe {
+            self.core.logical_device.destroy_image_view(self.view, None);
+            self.core.logical_device.destroy_image(self.handle, None);
+        }
+    }
 }
-
-// let depth_format = self.core.find_supported_format(
-//     vec![
-//         vk::Format::D32_SFLOAT,
-//         vk::Format::D32_SFLOAT_S8_UINT,
-//         vk::Format::D24_UNORM_S8_UINT,
-//     ],
-//     vk::ImageTiling::OPTIMAL,
-//     vk::FormatFeatureFlags::DEPTH_STENCIL_ATTACHMENT,
-// );
-// let extent = &self.swap_chain_extent;
-// for _i in 0..self.images.len() {
-//     let image_create_info = vk::ImageCreateInfo::builder()
-//         .image_type(vk::ImageType::TYPE_2D)
-//         .extent(vk::Extent3D {
-//             width: extent.width,
-//             height: extent.height,
-//             depth: 1,
-//         })
-//         .mip_levels(1)
-//         .array_layers(1)
-//         .format(depth_format)
-//         .tiling(vk::ImageTiling::OPTIMAL)
-//         .initial_layout(vk::ImageLayout::UNDEFINED)
-//         .usage(vk::ImageUsageFlags::DEPTH_STENCIL_ATTACHMENT)
-//         .sharing_mode(vk::SharingMode::EXCLUSIVE)
-//         .samples(vk::SampleCountFlags::TYPE_1)
-//         .build();
-//     let image = unsafe {
-//         self.core
-//             .logical_device
-//             .create_image(&image_create_info, None)
-//             .expect("Failed to create image")
-//     };
-//     let mem_req: vk::MemoryRequirements = unsafe {
-//         self.core
-//             .logical_device
-//             .get_image_memory_requirements(image)
-//     };
-//     let mem_index = self.core.find_memory_type(
-//         mem_req.memory_type_bits,
-//         vk::MemoryPropertyFlags::DEVICE_LOCAL,
-//     );
-//     let alloc_info = vk::MemoryAllocateInfo::builder()
-//         .allocation_size(mem_req.size)
-//         .memory_type_index(mem_index.unwrap())
-//         .build();
-//     let image_memory = unsafe {
-//         self.core
-//             .logical_device
-//             .allocate_memory(&alloc_info, None)
-//             .expect("Failed to allocate image memory")
-//     };
-//     unsafe {
-//         self.core
-//             .logical_device
-//             .bind_image_memory(image, image_memory, 0)
-//             .expect("Failed to bind")
-//     };
-//     self.depth_images.push(image);
-//     self.depth_image_memories.push(image_memory);
-//     let view_info = vk::ImageViewCreateInfo::builder()
-//         .image(image)
-//         .view_type(vk::ImageViewType::TYPE_2D)
-//         .format(depth_format)
-//         .subresource_range(vk::ImageSubresourceRange {
-//             aspect_mask: vk::ImageAspectFlags::DEPTH,
-//             base_mip_level: 0,
-//             level_count: 1,
-//             base_array_layer: 0,
-//             layer_count: 1,
-//         })
-//         .build();
-//     let depth_image_view = unsafe {
-//         self.core
-//             .logical_device
-//             .create_image_view(&view_info, None)
-//             .expect("Failed to create image view")
-//     };
-//     self.depth_image_views.push(depth_image_view);
-// }
\ No newline at end of file
@@ -0,0 +1,8 @@
+/// One vertex buffer and one index buffer holding the meshes of many
+/// drawables, so they can all be drawn from a single binding. Buffers are
+/// indices into `Memory::buffers`.
+#[derive(Debug, Clone, Copy)]
+pub struct MeshPool {
+    pub vertex_buffer: usize,
+    pub index_buffer: usize,
+}
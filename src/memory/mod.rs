@@ -1,12 +1,17 @@
 pub mod allocators;
 mod buffers;
+pub mod descriptor;
 mod image;
+pub mod mesh_pool;
+pub mod texture;
 use self::allocators::Block;
+use crate::utils::slots::Slots;
 use crate::{core::Core, utils::list::Link};
 use allocators::Allocator;
 use ash::vk;
 use buffers::Buffer;
 use image::Image;
+use mesh_pool::MeshPool;
 use std::ffi::c_void;
 use std::panic;
 use std::{rc::Rc, sync::Arc};
@@ -62,7 +67,9 @@ fn get_memory_heaps(core: &Core) -> Vec<MemoryHeap> {
 pub struct Memory {
     core: Rc<Core>,
     pub allocators: Vec<Allocator>,
-    pub buffers: Vec<Buffer>,
+    /// Indexed by the ids `create_buffer` returns, which stay valid after
+    /// other buffers are freed.
+    pub buffers: Slots<Buffer>,
     pub images: Vec<Image>,
     memory_heaps: Vec<MemoryHeap>,
     granularity: vk::DeviceSize,
@@ -72,7 +79,7 @@ impl Memory {
         Self {
             memory_heaps: get_memory_heaps(&core),
             allocators: Vec::new(),
-            buffers: Vec::new(),
+            buffers: Slots::new(),
             images: Vec::new(),
             granularity: unsafe {
                 core.instance
@@ -148,8 +155,59 @@ impl Memory {
                 .unwrap();
         }
       
-        self.buffers.push(buffer);
-        self.buffers.len() - 1
+        self.buffers.insert(buffer)
+    }
+
+    /// Packs meshes that were already concatenated into a host-visible
+    /// vertex buffer and index buffer. `indices` must already be offset into
+    /// `vertices`.
+    pub fn create_mesh_pool<V>(&mut self, vertices: &[V], indices: &[u32]) -> MeshPool {
+        let vertices_size = std::mem::size_of_val(vertices) as vk::DeviceSize;
+        let indices_size = std::mem::size_of_val(indices) as vk::DeviceSize;
+        let vertex_buffer = self.create_buffer(
+            vertices_size,
+            AllocationType::Buffer,
+            vk::BufferUsageFlags::VERTEX_BUFFER,
+            vk::MemoryPropertyFlags::HOST_VISIBLE,
+        );
+        let index_buffer = self.create_buffer(
+            indices_size,
+            AllocationType::Buffer,
+            vk::BufferUsageFlags::INDEX_BUFFER,
+            vk::MemoryPropertyFlags::HOST_VISIBLE,
+        );
+        self.copy_memory(None, vertex_buffer, 0, vertices_size, vertices.as_ptr() as *const u8);
+        self.copy_memory(None, index_buffer, 0, indices_size, indices.as_ptr() as *const u8);
+        MeshPool {
+            vertex_buffer,
+            index_buffer,
+        }
+    }
+
+    pub fn create_image(
+        &mut self,
+        image_info: vk::ImageCreateInfo,
+        view_type: vk::ImageViewType,
+        aspect_mask: vk::ImageAspectFlags,
+    ) -> usize {
+        let mut image = Image::new(self.core.clone(), image_info);
+        let (_data, allocator_index, block_id, offset) = self.allocate_memory(
+            image.memory_requirements,
+            vk::MemoryPropertyFlags::DEVICE_LOCAL,
+            AllocationType::ImageOptimal,
+            self.granularity,
+        );
+        image.allocator_id = Some(allocator_index);
+        image.block_id = Some(block_id);
+        unsafe {
+            self.core
+                .logical_device
+                .bind_image_memory(image.handle, self.allocators[allocator_index].handle, offset)
+                .unwrap();
+        }
+        image.create_view(view_type, aspect_mask);
+        self.images.push(image);
+        self.images.len() - 1
     }
 
     fn allocate_memory(
@@ -183,10 +241,10 @@ impl Memory {
         self.allocators
             .iter_mut()
             .enumerate()
-            .find(|(index, allocator)| {
+            .find(|(_index, allocator)| {
                 allocator.memory_flags.contains(memory_type)
                     && allocator.free_memory >= size
-                    && memory_type_index & (1 << index) != 0
+                    && memory_type_index & (1 << allocator.memory_type_index) != 0
             })
             .map(|(index, allocator)| index)
     }
@@ -269,7 +327,6 @@ impl Memory {
     ) -> Option<usize> {
         self.buffers
             .iter_mut()
-            .enumerate()
             .find(|(index, buffer)| {
                 buffer.size >= size
                     && buffer.memory_type.contains(memory_flags)
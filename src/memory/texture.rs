@@ -0,0 +1,364 @@
+use super::{AllocationType, Memory};
+use crate::command::Command;
+use crate::core::Core;
+use ash::vk;
+use std::rc::Rc;
+
+/// How a `TextureArray` is filtered.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum TextureFilter {
+    /// A full mip chain per layer, sampled trilinearly and anisotropically
+    /// with repeating coordinates.
+    Mipmapped,
+    /// The base level only, sampled so texels stay square blocks at any
+    /// scale, with clamped coordinates.
+    Nearest,
+}
+
+/// Textures uploaded as a 2D array image with one layer per texture.
+/// Unlike an atlas, each layer gets its own mip chain, so distant faces
+/// never pick up texels from their neighbours.
+pub struct TextureArray {
+    core: Rc<Core>,
+    pub image_index: usize,
+    pub sampler: vk::Sampler,
+}
+impl TextureArray {
+    /// `layers` holds tightly packed RGBA8 pixels, `width * height * 4` bytes each.
+    pub fn new(
+        core: Rc<Core>,
+        memory: &mut Memory,
+        command: &Command,
+        width: u32,
+        height: u32,
+        layers: &[Vec<u8>],
+        filter: TextureFilter,
+    ) -> Self {
+        let format = vk::Format::R8G8B8A8_SRGB;
+        let layer_count = layers.len() as u32;
+        let layer_size = (width * height * 4) as vk::DeviceSize;
+        let mip_levels = match filter {
+            TextureFilter::Mipmapped => mip_level_count(width, height),
+            TextureFilter::Nearest => 1,
+        };
+
+        let pixels: Vec<u8> = layers
+            .iter()
+            .flat_map(|layer| {
+                assert!(
+                    layer.len() as vk::DeviceSize == layer_size,
+                    "Texture layer has the wrong size"
+                );
+                layer.iter().copied()
+            })
+            .collect();
+        let staging_buffer_index = memory.create_buffer(
+            pixels.len() as vk::DeviceSize,
+            AllocationType::Buffer,
+            vk::BufferUsageFlags::TRANSFER_SRC,
+            vk::MemoryPropertyFlags::HOST_VISIBLE,
+        );
+        memory.copy_memory(
+            None,
+            staging_buffer_index,
+            0,
+            pixels.len() as vk::DeviceSize,
+            pixels.as_ptr(),
+        );
+
+        let image_index = memory.create_image(
+            vk::ImageCreateInfo::builder()
+                .image_type(vk::ImageType::TYPE_2D)
+                .extent(vk::Extent3D {
+                    width,
+                    height,
+                    depth: 1,
+                })
+                .mip_levels(mip_levels)
+                .array_layers(layer_count)
+                .format(format)
+                .tiling(vk::ImageTiling::OPTIMAL)
+                .initial_layout(vk::ImageLayout::UNDEFINED)
+                .usage(
+                    vk::ImageUsageFlags::TRANSFER_SRC
+                        | vk::ImageUsageFlags::TRANSFER_DST
+                        | vk::ImageUsageFlags::SAMPLED,
+                )
+                .sharing_mode(vk::SharingMode::EXCLUSIVE)
+                .samples(vk::SampleCountFlags::TYPE_1)
+                .build(),
+            vk::ImageViewType::TYPE_2D_ARRAY,
+            vk::ImageAspectFlags::COLOR,
+        );
+        let image = memory.images[image_index].handle;
+
+        let command_buffer = command.begin_single_time_commands(&core);
+        transition_layout(
+            &core,
+            command_buffer,
+            image,
+            0,
+            mip_levels,
+            layer_count,
+            vk::ImageLayout::UNDEFINED,
+            vk::ImageLayout::TRANSFER_DST_OPTIMAL,
+            vk::AccessFlags::empty(),
+            vk::AccessFlags::TRANSFER_WRITE,
+            vk::PipelineStageFlags::TOP_OF_PIPE,
+            vk::PipelineStageFlags::TRANSFER,
+        );
+        let region = vk::BufferImageCopy::builder()
+            .buffer_offset(0)
+            .buffer_row_length(0)
+            .buffer_image_height(0)
+            .image_subresource(vk::ImageSubresourceLayers {
+                aspect_mask: vk::ImageAspectFlags::COLOR,
+                mip_level: 0,
+                base_array_layer: 0,
+                layer_count,
+            })
+            .image_offset(vk::Offset3D { x: 0, y: 0, z: 0 })
+            .image_extent(vk::Extent3D {
+                width,
+                height,
+                depth: 1,
+            })
+            .build();
+        unsafe {
+            core.logical_device.cmd_copy_buffer_to_image(
+                command_buffer,
+                memory.buffers[staging_buffer_index].handle,
+                image,
+                vk::ImageLayout::TRANSFER_DST_OPTIMAL,
+                &[region],
+            );
+        }
+        generate_mipmaps(
+            &core,
+            command_buffer,
+            image,
+            format,
+            width,
+            height,
+            mip_levels,
+            layer_count,
+        );
+        command.end_single_time_commands(&core, command_buffer);
+        memory.free_buffer(staging_buffer_index);
+
+        let sampler_info = match filter {
+            TextureFilter::Mipmapped => {
+                let max_anisotropy = unsafe {
+                    core.instance
+                        .get_physical_device_properties(core.physical_device)
+                        .limits
+                        .max_sampler_anisotropy
+                };
+                vk::SamplerCreateInfo::builder()
+                    .mag_filter(vk::Filter::NEAREST)
+                    .min_filter(vk::Filter::LINEAR)
+                    .mipmap_mode(vk::SamplerMipmapMode::LINEAR)
+                    .address_mode_u(vk::SamplerAddressMode::REPEAT)
+                    .address_mode_v(vk::SamplerAddressMode::REPEAT)
+                    .address_mode_w(vk::SamplerAddressMode::REPEAT)
+                    .anisotropy_enable(true)
+                    .max_anisotropy(max_anisotropy)
+            }
+            TextureFilter::Nearest => vk::SamplerCreateInfo::builder()
+                .mag_filter(vk::Filter::NEAREST)
+                .min_filter(vk::Filter::NEAREST)
+                .mipmap_mode(vk::SamplerMipmapMode::NEAREST)
+                .address_mode_u(vk::SamplerAddressMode::CLAMP_TO_EDGE)
+                .address_mode_v(vk::SamplerAddressMode::CLAMP_TO_EDGE)
+                .address_mode_w(vk::SamplerAddressMode::CLAMP_TO_EDGE),
+        }
+        .compare_enable(false)
+        .min_lod(0.0)
+        .max_lod((mip_levels - 1) as f32)
+        .border_color(vk::BorderColor::INT_OPAQUE_BLACK)
+        .unnormalized_coordinates(false)
+        .build();
+        let sampler = unsafe {
+            core.logical_device
+                .create_sampler(&sampler_info, None)
+                .expect("Failed to create texture sampler")
+        };
+        TextureArray {
+            core,
+            image_index,
+            sampler,
+        }
+    }
+}
+impl Drop for TextureArray {
+    fn drop(&mut self) {
+        unsafe {
+            self.core.logical_device.destroy_sampler(self.sampler, None);
+        }
+    }
+}
+
+pub fn mip_level_count(width: u32, height: u32) -> u32 {
+    32 - width.max(height).max(1).leading_zeros()
+}
+
+#[allow(clippy::too_many_arguments)]
+fn transition_layout(
+    core: &Core,
+    command_buffer: vk::CommandBuffer,
+    image: vk::Image,
+    base_mip_level: u32,
+    level_count: u32,
+    layer_count: u32,
+    old_layout: vk::ImageLayout,
+    new_layout: vk::ImageLayout,
+    src_access_mask: vk::AccessFlags,
+    dst_access_mask: vk::AccessFlags,
+    src_stage_mask: vk::PipelineStageFlags,
+    dst_stage_mask: vk::PipelineStageFlags,
+) {
+    let barrier = vk::ImageMemoryBarrier::builder()
+        .image(image)
+        .old_layout(old_layout)
+        .new_layout(new_layout)
+        .src_queue_family_index(vk::QUEUE_FAMILY_IGNORED)
+        .dst_queue_family_index(vk::QUEUE_FAMILY_IGNORED)
+        .src_access_mask(src_access_mask)
+        .dst_access_mask(dst_access_mask)
+        .subresource_range(vk::ImageSubresourceRange {
+            aspect_mask: vk::ImageAspectFlags::COLOR,
+            base_mip_level,
+            level_count,
+            base_array_layer: 0,
+            layer_count,
+        })
+        .build();
+    unsafe {
+        core.logical_device.cmd_pipeline_barrier(
+            command_buffer,
+            src_stage_mask,
+            dst_stage_mask,
+            vk::DependencyFlags::empty(),
+            &[],
+            &[],
+            &[barrier],
+        );
+    }
+}
+
+/// Fills mip levels `1..mip_levels` by repeatedly blitting the previous level
+/// at half size, then leaves the whole image in `SHADER_READ_ONLY_OPTIMAL`.
+#[allow(clippy::too_many_arguments)]
+fn generate_mipmaps(
+    core: &Core,
+    command_buffer: vk::CommandBuffer,
+    image: vk::Image,
+    format: vk::Format,
+    width: u32,
+    height: u32,
+    mip_levels: u32,
+    layer_count: u32,
+) {
+    let format_properties = unsafe {
+        core.instance
+            .get_physical_device_format_properties(core.physical_device, format)
+    };
+    assert!(
+        format_properties
+            .optimal_tiling_features
+            .contains(vk::FormatFeatureFlags::SAMPLED_IMAGE_FILTER_LINEAR),
+        "Texture format does not support linear blitting"
+    );
+    let mut mip_width = width as i32;
+    let mut mip_height = height as i32;
+    for level in 1..mip_levels {
+        transition_layout(
+            core,
+            command_buffer,
+            image,
+            level - 1,
+            1,
+            layer_count,
+            vk::ImageLayout::TRANSFER_DST_OPTIMAL,
+            vk::ImageLayout::TRANSFER_SRC_OPTIMAL,
+            vk::AccessFlags::TRANSFER_WRITE,
+            vk::AccessFlags::TRANSFER_READ,
+            vk::PipelineStageFlags::TRANSFER,
+            vk::PipelineStageFlags::TRANSFER,
+        );
+        let next_width = (mip_width / 2).max(1);
+        let next_height = (mip_height / 2).max(1);
+        let blit = vk::ImageBlit::builder()
+            .src_offsets([
+                vk::Offset3D { x: 0, y: 0, z: 0 },
+                vk::Offset3D {
+                    x: mip_width,
+                    y: mip_height,
+                    z: 1,
+                },
+            ])
+            .src_subresource(vk::ImageSubresourceLayers {
+                aspect_mask: vk::ImageAspectFlags::COLOR,
+                mip_level: level - 1,
+                base_array_layer: 0,
+                layer_count,
+            })
+            .dst_offsets([
+                vk::Offset3D { x: 0, y: 0, z: 0 },
+                vk::Offset3D {
+                    x: next_width,
+                    y: next_height,
+                    z: 1,
+                },
+            ])
+            .dst_subresource(vk::ImageSubresourceLayers {
+                aspect_mask: vk::ImageAspectFlags::COLOR,
+                mip_level: level,
+                base_array_layer: 0,
+                layer_count,
+            })
+            .build();
+        unsafe {
+            core.logical_device.cmd_blit_image(
+                command_buffer,
+                image,
+                vk::ImageLayout::TRANSFER_SRC_OPTIMAL,
+                image,
+                vk::ImageLayout::TRANSFER_DST_OPTIMAL,
+                &[blit],
+                vk::Filter::LINEAR,
+            );
+        }
+        transition_layout(
+            core,
+            command_buffer,
+            image,
+            level - 1,
+            1,
+            layer_count,
+            vk::ImageLayout::TRANSFER_SRC_OPTIMAL,
+            vk::ImageLayout::SHADER_READ_ONLY_OPTIMAL,
+            vk::AccessFlags::TRANSFER_READ,
+            vk::AccessFlags::SHADER_READ,
+            vk::PipelineStageFlags::TRANSFER,
+            vk::PipelineStageFlags::FRAGMENT_SHADER,
+        );
+        mip_width = next_width;
+        mip_height = next_height;
+    }
+    transition_layout(
+        core,
+        command_buffer,
+        image,
+        mip_levels - 1,
+        1,
+        layer_count,
+        vk::ImageLayout::TRANSFER_DST_OPTIMAL,
+        vk::ImageLayout::SHADER_READ_ONLY_OPTIMAL,
+        vk::AccessFlags::TRANSFER_WRITE,
+        vk::AccessFlags::SHADER_READ,
+        vk::PipelineStageFlags::TRANSFER,
+        vk::PipelineStageFlags::FRAGMENT_SHADER,
+    );
+}
+
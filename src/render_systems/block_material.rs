@@ -0,0 +1,94 @@
+use crate::command::Command;
+use crate::core::Core;
+use crate::game::block_textures::{all_texture_pixels, texture_animation_table, TEXTURE_SIZE};
+use crate::memory::descriptor::Descriptor;
+use crate::memory::texture::{TextureArray, TextureFilter};
+use crate::memory::{AllocationType, Memory};
+use ash::vk;
+use std::mem;
+use std::rc::Rc;
+
+/// The block texture array, with the frame strips of its animated
+/// textures, and the descriptor set that binds both, shared by every render
+/// system that draws blocks.
+pub struct BlockMaterial {
+    /// Owns the image and sampler `descriptor` points at.
+    _block_textures: TextureArray,
+    pub descriptor: Descriptor,
+}
+impl BlockMaterial {
+    pub fn new(core: Rc<Core>, memory: &mut Memory, command: &Command) -> Self {
+        let descriptor = Descriptor::new(
+            core.clone(),
+            &[
+                vk::DescriptorSetLayoutBinding::builder()
+                    .binding(0)
+                    .descriptor_type(vk::DescriptorType::SAMPLED_IMAGE)
+                    .descriptor_count(1)
+                    .stage_flags(vk::ShaderStageFlags::FRAGMENT)
+                    .build(),
+                vk::DescriptorSetLayoutBinding::builder()
+                    .binding(1)
+                    .descriptor_type(vk::DescriptorType::SAMPLER)
+                    .descriptor_count(1)
+                    .stage_flags(vk::ShaderStageFlags::FRAGMENT)
+                    .build(),
+                vk::DescriptorSetLayoutBinding::builder()
+                    .binding(2)
+                    .descriptor_type(vk::DescriptorType::UNIFORM_BUFFER)
+                    .descriptor_count(1)
+                    .stage_flags(vk::ShaderStageFlags::FRAGMENT)
+                    .build(),
+            ],
+        );
+        let block_textures = TextureArray::new(
+            core,
+            memory,
+            command,
+            TEXTURE_SIZE,
+            TEXTURE_SIZE,
+            &all_texture_pixels(),
+            TextureFilter::Mipmapped,
+        );
+        descriptor.write_image(
+            0,
+            vk::DescriptorType::SAMPLED_IMAGE,
+            memory.images[block_textures.image_index].view,
+            vk::Sampler::null(),
+            vk::ImageLayout::SHADER_READ_ONLY_OPTIMAL,
+        );
+        descriptor.write_image(
+            1,
+            vk::DescriptorType::SAMPLER,
+            vk::ImageView::null(),
+            block_textures.sampler,
+            vk::ImageLayout::UNDEFINED,
+        );
+        let animations = texture_animation_table();
+        let animations_size = mem::size_of_val(animations.as_slice()) as vk::DeviceSize;
+        let animation_buffer = memory.create_buffer(
+            animations_size,
+            AllocationType::Buffer,
+            vk::BufferUsageFlags::UNIFORM_BUFFER,
+            vk::MemoryPropertyFlags::HOST_VISIBLE,
+        );
+        memory.copy_memory(
+            None,
+            animation_buffer,
+            0,
+            animations_size,
+            animations.as_ptr() as *const u8,
+        );
+        descriptor.write_buffer(
+            2,
+            vk::DescriptorType::UNIFORM_BUFFER,
+            memory.buffers[animation_buffer].handle,
+            0,
+            animations_size,
+        );
+        BlockMaterial {
+            _block_textures: block_textures,
+            descriptor,
+        }
+    }
+}
@@ -0,0 +1,246 @@
+use super::pipeline::shader::{CLOUD_FRAG, CLOUD_VERT};
+use super::pipeline::{BlendMode, Pipeline, PipelineConfig, PipelineVariant};
+use super::shadow_map::ShadowMap;
+use super::{FrameInfo, RenderStage, RenderSystem};
+use crate::core::Core;
+use crate::game::game_objects::Vertex;
+use crate::game::settings::CloudMode;
+use crate::game::world::clouds::{
+    mesh_box_clouds, mesh_flat_clouds, CloudMap, CLOUD_HEIGHT, CLOUD_THICKNESS,
+};
+use crate::memory::mesh_pool::MeshPool;
+use crate::memory::Memory;
+use crate::renderer::render_graph::{RenderGraph, MAIN_PASS};
+use ash::vk;
+use nalgebra_glm as glm;
+use std::any::Any;
+use std::mem;
+use std::rc::Rc;
+
+/// Laid out as `Push` in `cloud.vert`.
+#[derive(Debug, Clone, Copy)]
+#[repr(C)]
+struct CloudPush {
+    proj_view: glm::Mat4,
+    /// Where the first of the 3x3 copies starts in `xyz`, the map's period
+    /// in `w`.
+    origin: glm::Vec4,
+}
+
+/// A depth-only pipeline that lays down the nearest cloud surface, and a
+/// blended one that shades exactly that surface, so overlapping faces of
+/// the translucent boxes are never seen through each other.
+struct CloudPipelines {
+    depth: Pipeline,
+    color: Pipeline,
+}
+impl CloudPipelines {
+    fn new(core: Rc<Core>, config: &PipelineConfig, cull_mode: vk::CullModeFlags) -> Self {
+        let binding_descriptions = [Vertex::get_binding_description()];
+        let attribute_descriptions = &Vertex::get_attribute_descriptions()[..2];
+        let mut depth = Pipeline::new(core.clone());
+        depth.create_graphic_pipeline(
+            &CLOUD_VERT,
+            &CLOUD_FRAG,
+            config
+                .clone()
+                .cull_mode(cull_mode)
+                .blend_mode(BlendMode::Masked),
+            &binding_descriptions,
+            attribute_descriptions,
+        );
+        let mut color = Pipeline::new(core);
+        color.create_graphic_pipeline(
+            &CLOUD_VERT,
+            &CLOUD_FRAG,
+            config
+                .variant(PipelineVariant::Translucent)
+                .cull_mode(cull_mode)
+                .depth_compare_op(vk::CompareOp::LESS_OR_EQUAL),
+            &binding_descriptions,
+            attribute_descriptions,
+        );
+        CloudPipelines { depth, color }
+    }
+    fn reload_shaders(&mut self) {
+        self.depth.reload_if_changed();
+        self.color.reload_if_changed();
+    }
+    fn render_graph_changed(&mut self, render_graph: &RenderGraph) {
+        self.depth.rebuild_for_pass(render_graph, MAIN_PASS);
+        self.color.rebuild_for_pass(render_graph, MAIN_PASS);
+    }
+}
+
+/// A cloud mesh and how many indices to draw.
+#[derive(Clone, Copy)]
+struct CloudMesh {
+    mesh_pool: MeshPool,
+    index_count: u32,
+}
+impl CloudMesh {
+    fn new(memory: &mut Memory, (vertices, indices): (Vec<Vertex>, Vec<u32>)) -> Self {
+        CloudMesh {
+            mesh_pool: memory.create_mesh_pool(&vertices, &indices),
+            index_count: indices.len() as u32,
+        }
+    }
+}
+
+/// Draws the drifting cloud layer in the translucent stage, as a flat
+/// sheet or as blocky boxes depending on `CloudMode`. Inside the layer the
+/// boxes are drawn without culling, so their insides surround the camera.
+pub struct CloudRenderSystem {
+    core: Rc<Core>,
+    pipeline_layout: vk::PipelineLayout,
+    outside: CloudPipelines,
+    inside: CloudPipelines,
+    shadow_map: Rc<ShadowMap>,
+    flat_mesh: CloudMesh,
+    box_mesh: CloudMesh,
+    mode: CloudMode,
+    /// How far the clouds have drifted along +x, in blocks.
+    drift: f32,
+}
+impl CloudRenderSystem {
+    pub fn new(
+        core: Rc<Core>,
+        memory: &mut Memory,
+        shadow_map: Rc<ShadowMap>,
+        render_graph: &RenderGraph,
+        map: &CloudMap,
+        mode: CloudMode,
+    ) -> Self {
+        let set_layouts = [shadow_map.descriptor.set_layout];
+        let push_constant_ranges = [vk::PushConstantRange::builder()
+            .stage_flags(vk::ShaderStageFlags::VERTEX)
+            .offset(0)
+            .size(mem::size_of::<CloudPush>() as u32)
+            .build()];
+        let pipeline_layout_info = vk::PipelineLayoutCreateInfo::builder()
+            .set_layouts(&set_layouts)
+            .push_constant_ranges(&push_constant_ranges)
+            .build();
+        let pipeline_layout = unsafe {
+            core.logical_device
+                .create_pipeline_layout(&pipeline_layout_info, None)
+                .expect("Failed to create pipeline layout")
+        };
+        let config = PipelineConfig::for_pass(render_graph, MAIN_PASS, pipeline_layout);
+        let outside = CloudPipelines::new(core.clone(), &config, vk::CullModeFlags::BACK);
+        let inside = CloudPipelines::new(core.clone(), &config, vk::CullModeFlags::NONE);
+        CloudRenderSystem {
+            core,
+            pipeline_layout,
+            outside,
+            inside,
+            shadow_map,
+            flat_mesh: CloudMesh::new(memory, mesh_flat_clouds(map)),
+            box_mesh: CloudMesh::new(memory, mesh_box_clouds(map)),
+            mode,
+            drift: 0.0,
+        }
+    }
+    pub fn set_mode(&mut self, mode: CloudMode) {
+        self.mode = mode;
+    }
+    pub fn set_drift(&mut self, drift: f32) {
+        self.drift = drift;
+    }
+    /// Places the 3x3 copies of the map so the camera is over the middle one.
+    fn origin(&self, camera: &glm::Vec3) -> glm::Vec4 {
+        let period = CloudMap::period();
+        let first_copy =
+            |camera: f32, drift: f32| ((camera - drift) / period).floor() * period - period + drift;
+        glm::vec4(
+            first_copy(camera.x, self.drift.rem_euclid(period)),
+            CLOUD_HEIGHT,
+            first_copy(camera.z, 0.0),
+            period,
+        )
+    }
+}
+impl RenderSystem for CloudRenderSystem {
+    fn stage(&self) -> RenderStage {
+        RenderStage::Translucent
+    }
+    fn render(&mut self, frame: &mut FrameInfo) {
+        let camera = frame.camera.position;
+        let in_layer = camera.y > CLOUD_HEIGHT && camera.y < CLOUD_HEIGHT + CLOUD_THICKNESS;
+        let (mesh, pipelines) = match self.mode {
+            CloudMode::Off => return,
+            CloudMode::Fast => (self.flat_mesh, &self.outside),
+            CloudMode::Fancy if in_layer => (self.box_mesh, &self.inside),
+            CloudMode::Fancy => (self.box_mesh, &self.outside),
+        };
+        let push = CloudPush {
+            proj_view: frame.camera.proj_view,
+            origin: self.origin(&camera),
+        };
+        let push = unsafe {
+            std::slice::from_raw_parts(
+                &push as *const CloudPush as *const u8,
+                mem::size_of::<CloudPush>(),
+            )
+        };
+        let device = &self.core.logical_device;
+        let command_buffer = frame.command_buffer;
+        unsafe {
+            device.cmd_bind_descriptor_sets(
+                command_buffer,
+                vk::PipelineBindPoint::GRAPHICS,
+                self.pipeline_layout,
+                0,
+                &[self.shadow_map.descriptor.set],
+                &[],
+            );
+            device.cmd_push_constants(
+                command_buffer,
+                self.pipeline_layout,
+                vk::ShaderStageFlags::VERTEX,
+                0,
+                push,
+            );
+            device.cmd_bind_vertex_buffers(
+                command_buffer,
+                0,
+                &[frame.memory.buffers[mesh.mesh_pool.vertex_buffer].handle],
+                &[0],
+            );
+            device.cmd_bind_index_buffer(
+                command_buffer,
+                frame.memory.buffers[mesh.mesh_pool.index_buffer].handle,
+                0,
+                vk::IndexType::UINT32,
+            );
+            for pipeline in [&pipelines.depth, &pipelines.color] {
+                device.cmd_bind_pipeline(
+                    command_buffer,
+                    vk::PipelineBindPoint::GRAPHICS,
+                    pipeline.graphic_pipeline,
+                );
+                device.cmd_draw_indexed(command_buffer, mesh.index_count, 9, 0, 0, 0);
+            }
+        }
+    }
+    fn reload_shaders(&mut self) {
+        self.outside.reload_shaders();
+        self.inside.reload_shaders();
+    }
+    fn render_graph_changed(&mut self, render_graph: &RenderGraph) {
+        self.outside.render_graph_changed(render_graph);
+        self.inside.render_graph_changed(render_graph);
+    }
+    fn as_any_mut(&mut self) -> &mut dyn Any {
+        self
+    }
+}
+impl Drop for CloudRenderSystem {
+    fn drop(&mut self) {
+        unsafe {
+            self.core
+                .logical_device
+                .destroy_pipeline_layout(self.pipeline_layout, None);
+        }
+    }
+}
@@ -0,0 +1,318 @@
+use super::pipeline::shader::CHUNK_CULLING_COMP;
+use super::pipeline::ComputePipeline;
+use crate::core::Core;
+use crate::memory::descriptor::Descriptor;
+use crate::memory::{AllocationType, Memory};
+use crate::MAX_FRAMES_IN_FLIGHT;
+use ash::vk;
+use nalgebra_glm as glm;
+use std::mem;
+use std::rc::Rc;
+
+/// A chunk mesh as the culling shader sees it, laid out as `ChunkBounds`
+/// in `shader.comp` (std430).
+#[derive(Debug, Clone, Copy)]
+#[repr(C)]
+pub struct ChunkBounds {
+    /// `w` is non-zero when the chunk was occluded last time it was tested.
+    pub min: [f32; 4],
+    pub max: [f32; 4],
+    pub first_index: u32,
+    pub index_count: u32,
+    pub vertex_offset: i32,
+    /// Written to `first_instance`, so the vertex shader sees it through
+    /// `gl_InstanceIndex`.
+    pub draw_id: u32,
+}
+impl ChunkBounds {
+    pub fn new(min: glm::Vec3, max: glm::Vec3, first_index: u32, index_count: u32, draw_id: u32) -> Self {
+        ChunkBounds {
+            min: [min.x, min.y, min.z, 0.0],
+            max: [max.x, max.y, max.z, 0.0],
+            first_index,
+            index_count,
+            vertex_offset: 0,
+            draw_id,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy)]
+#[repr(C)]
+struct CullingPushConstant {
+    planes: [glm::Vec4; 6],
+    chunk_count: u32,
+}
+
+/// The buffers one frame in flight culls with, so a frame never rewrites
+/// what another frame's dispatch or draw may still be using.
+struct CullingFrame {
+    descriptor: Descriptor,
+    chunk_buffer: usize,
+    command_buffer: usize,
+    count_buffer: usize,
+}
+
+/// Frustum-culls chunk meshes on the GPU. `dispatch` writes one
+/// `vk::DrawIndexedIndirectCommand` per visible chunk and the number of
+/// commands, which `draw` then consumes with `cmd_draw_indexed_indirect_count`.
+/// Every method takes the frame in flight whose buffers it uses.
+/// Only available when `Core::draw_indirect_count` is loaded.
+pub struct CullingSystem {
+    core: Rc<Core>,
+    pipeline_layout: vk::PipelineLayout,
+    pipeline: ComputePipeline,
+    frames: Vec<CullingFrame>,
+    chunk_count: u32,
+}
+impl CullingSystem {
+    pub fn new(core: Rc<Core>, memory: &mut Memory, chunks: &[ChunkBounds]) -> Self {
+        let storage_binding = |binding: u32| {
+            vk::DescriptorSetLayoutBinding::builder()
+                .binding(binding)
+                .descriptor_type(vk::DescriptorType::STORAGE_BUFFER)
+                .descriptor_count(1)
+                .stage_flags(vk::ShaderStageFlags::COMPUTE)
+                .build()
+        };
+        let bindings = [storage_binding(0), storage_binding(1), storage_binding(2)];
+        // Buffers must not be empty, even when there is nothing to cull.
+        let chunk_count = chunks.len() as u32;
+        let slots = chunk_count.max(1) as vk::DeviceSize;
+        let chunks_size = slots * mem::size_of::<ChunkBounds>() as vk::DeviceSize;
+        let commands_size =
+            slots * mem::size_of::<vk::DrawIndexedIndirectCommand>() as vk::DeviceSize;
+        let count_size = mem::size_of::<u32>() as vk::DeviceSize;
+        let frames: Vec<CullingFrame> = (0..MAX_FRAMES_IN_FLIGHT)
+            .map(|_| {
+                let descriptor = Descriptor::new(core.clone(), &bindings);
+                let chunk_buffer = memory.create_buffer(
+                    chunks_size,
+                    AllocationType::Buffer,
+                    vk::BufferUsageFlags::STORAGE_BUFFER,
+                    vk::MemoryPropertyFlags::HOST_VISIBLE,
+                );
+                let command_buffer = memory.create_buffer(
+                    commands_size,
+                    AllocationType::Buffer,
+                    vk::BufferUsageFlags::STORAGE_BUFFER | vk::BufferUsageFlags::INDIRECT_BUFFER,
+                    vk::MemoryPropertyFlags::HOST_VISIBLE,
+                );
+                // Host visible so the draw count can be read back for the stats.
+                let count_buffer = memory.create_buffer(
+                    count_size,
+                    AllocationType::Buffer,
+                    vk::BufferUsageFlags::STORAGE_BUFFER | vk::BufferUsageFlags::INDIRECT_BUFFER,
+                    vk::MemoryPropertyFlags::HOST_VISIBLE,
+                );
+                memory.copy_memory(
+                    None,
+                    chunk_buffer,
+                    0,
+                    mem::size_of_val(chunks) as vk::DeviceSize,
+                    chunks.as_ptr() as *const u8,
+                );
+                memory.copy_memory(
+                    None,
+                    count_buffer,
+                    0,
+                    count_size,
+                    [0_u32].as_ptr() as *const u8,
+                );
+                for (binding, buffer, size) in [
+                    (0, chunk_buffer, chunks_size),
+                    (1, command_buffer, commands_size),
+                    (2, count_buffer, count_size),
+                ] {
+                    descriptor.write_buffer(
+                        binding,
+                        vk::DescriptorType::STORAGE_BUFFER,
+                        memory.buffers[buffer].handle,
+                        0,
+                        size,
+                    );
+                }
+                CullingFrame {
+                    descriptor,
+                    chunk_buffer,
+                    command_buffer,
+                    count_buffer,
+                }
+            })
+            .collect();
+        let set_layouts = [frames[0].descriptor.set_layout];
+        let pipeline_layout_info = vk::PipelineLayoutCreateInfo::builder()
+            .set_layouts(&set_layouts)
+            .push_constant_ranges(&[vk::PushConstantRange::builder()
+                .stage_flags(vk::ShaderStageFlags::COMPUTE)
+                .offset(0)
+                .size(mem::size_of::<CullingPushConstant>() as u32)
+                .build()])
+            .build();
+        let pipeline_layout = unsafe {
+            core.logical_device
+                .create_pipeline_layout(&pipeline_layout_info, None)
+                .expect("Failed to create pipeline layout")
+        };
+        let pipeline = ComputePipeline::new(core.clone(), &CHUNK_CULLING_COMP, pipeline_layout);
+        CullingSystem {
+            core,
+            pipeline_layout,
+            pipeline,
+            frames,
+            chunk_count,
+        }
+    }
+    /// Makes `frame_index`'s culling pass skip `chunk` until it is marked
+    /// visible again.
+    pub fn set_occluded(
+        &self,
+        memory: &mut Memory,
+        frame_index: u32,
+        chunk: usize,
+        occluded: bool,
+    ) {
+        let flag: f32 = if occluded { 1.0 } else { 0.0 };
+        let offset = chunk * mem::size_of::<ChunkBounds>() + 3 * mem::size_of::<f32>();
+        memory.copy_memory(
+            None,
+            self.frames[frame_index as usize].chunk_buffer,
+            offset as vk::DeviceSize,
+            mem::size_of::<f32>() as vk::DeviceSize,
+            &flag as *const f32 as *const u8,
+        );
+    }
+    /// Records the culling pass. Must be recorded outside a render pass,
+    /// before `draw`.
+    pub fn dispatch(
+        &self,
+        command_buffer: vk::CommandBuffer,
+        memory: &Memory,
+        frame_index: u32,
+        planes: [glm::Vec4; 6],
+    ) {
+        let frame = &self.frames[frame_index as usize];
+        let push_constant = CullingPushConstant {
+            planes,
+            chunk_count: self.chunk_count,
+        };
+        let push_constant_bytes = unsafe {
+            std::slice::from_raw_parts(
+                &push_constant as *const CullingPushConstant as *const u8,
+                mem::size_of::<CullingPushConstant>(),
+            )
+        };
+        let buffer_barrier = |buffer: usize, src_access_mask, dst_access_mask| {
+            vk::BufferMemoryBarrier::builder()
+                .src_access_mask(src_access_mask)
+                .dst_access_mask(dst_access_mask)
+                .src_queue_family_index(vk::QUEUE_FAMILY_IGNORED)
+                .dst_queue_family_index(vk::QUEUE_FAMILY_IGNORED)
+                .buffer(memory.buffers[buffer].handle)
+                .offset(0)
+                .size(vk::WHOLE_SIZE)
+                .build()
+        };
+        let device = &self.core.logical_device;
+        unsafe {
+            // the commands are rewritten only once earlier draws read them
+            device.cmd_pipeline_barrier(
+                command_buffer,
+                vk::PipelineStageFlags::DRAW_INDIRECT,
+                vk::PipelineStageFlags::COMPUTE_SHADER,
+                vk::DependencyFlags::empty(),
+                &[],
+                &[frame.command_buffer, frame.count_buffer].map(|buffer| {
+                    buffer_barrier(
+                        buffer,
+                        vk::AccessFlags::INDIRECT_COMMAND_READ,
+                        vk::AccessFlags::SHADER_WRITE,
+                    )
+                }),
+                &[],
+            );
+            device.cmd_bind_pipeline(
+                command_buffer,
+                vk::PipelineBindPoint::COMPUTE,
+                self.pipeline.handle,
+            );
+            device.cmd_bind_descriptor_sets(
+                command_buffer,
+                vk::PipelineBindPoint::COMPUTE,
+                self.pipeline_layout,
+                0,
+                &[frame.descriptor.set],
+                &[],
+            );
+            device.cmd_push_constants(
+                command_buffer,
+                self.pipeline_layout,
+                vk::ShaderStageFlags::COMPUTE,
+                0,
+                push_constant_bytes,
+            );
+            // one workgroup walks all chunks so the output can be compacted
+            // without atomics
+            device.cmd_dispatch(command_buffer, 1, 1, 1);
+            device.cmd_pipeline_barrier(
+                command_buffer,
+                vk::PipelineStageFlags::COMPUTE_SHADER,
+                vk::PipelineStageFlags::DRAW_INDIRECT | vk::PipelineStageFlags::HOST,
+                vk::DependencyFlags::empty(),
+                &[],
+                &[frame.command_buffer, frame.count_buffer].map(|buffer| {
+                    buffer_barrier(
+                        buffer,
+                        vk::AccessFlags::SHADER_WRITE,
+                        vk::AccessFlags::INDIRECT_COMMAND_READ | vk::AccessFlags::HOST_READ,
+                    )
+                }),
+                &[],
+            );
+        }
+    }
+    /// Draws the commands written by `frame_index`'s last `dispatch`. The
+    /// chunk meshes' vertex and index buffers must already be bound.
+    pub fn draw(&self, command_buffer: vk::CommandBuffer, memory: &Memory, frame_index: u32) {
+        let frame = &self.frames[frame_index as usize];
+        let draw_indirect_count = self
+            .core
+            .draw_indirect_count
+            .as_ref()
+            .expect("GPU culling needs VK_KHR_draw_indirect_count");
+        unsafe {
+            draw_indirect_count.cmd_draw_indexed_indirect_count(
+                command_buffer,
+                memory.buffers[frame.command_buffer].handle,
+                0,
+                memory.buffers[frame.count_buffer].handle,
+                0,
+                self.chunk_count,
+                mem::size_of::<vk::DrawIndexedIndirectCommand>() as u32,
+            );
+        }
+    }
+    /// How many chunks `frame_index`'s last dispatch drew. Only valid once
+    /// that frame's fence has signalled.
+    pub fn drawn_count(&self, memory: &Memory, frame_index: u32) -> u32 {
+        match memory.buffers[self.frames[frame_index as usize].count_buffer].data {
+            Some(data) => unsafe { *(data as *const u32) },
+            None => self.chunk_count,
+        }
+    }
+    pub fn chunk_count(&self) -> u32 {
+        self.chunk_count
+    }
+    pub fn reload_shaders(&mut self) {
+        self.pipeline.reload_if_changed();
+    }
+}
+impl Drop for CullingSystem {
+    fn drop(&mut self) {
+        unsafe {
+            self.core
+                .logical_device
+                .destroy_pipeline_layout(self.pipeline_layout, None);
+        }
+    }
+}
@@ -0,0 +1,119 @@
+use super::block_material::BlockMaterial;
+use super::main_render_system::PushConstant;
+use super::pipeline::PipelineVariant;
+use super::shadow_map::ShadowMap;
+use super::shadow_render_system::ShadowCaster;
+use super::{DebugView, FrameInfo, MainRenderSystem, RenderStage, RenderSystem};
+use crate::core::Core;
+use crate::game::game_objects::Vertex;
+use crate::memory::mesh_pool::MeshPool;
+use crate::memory::Memory;
+use crate::renderer::render_graph::RenderGraph;
+use ash::vk;
+use std::any::Any;
+use std::rc::Rc;
+
+/// Draws leaves, grass and flowers with an alpha test, so their holes need
+/// no sorting and they write depth like opaque blocks.
+pub struct CutoutRenderSystem {
+    core: Rc<Core>,
+    main_render_system: MainRenderSystem,
+    block_material: Rc<BlockMaterial>,
+    shadow_map: Rc<ShadowMap>,
+    /// `None` when the world has no cutout blocks.
+    mesh_pool: Option<MeshPool>,
+    index_count: u32,
+}
+impl CutoutRenderSystem {
+    pub fn new(
+        core: Rc<Core>,
+        memory: &mut Memory,
+        block_material: Rc<BlockMaterial>,
+        shadow_map: Rc<ShadowMap>,
+        render_graph: &RenderGraph,
+        vertices: &[Vertex],
+        indices: &[u32],
+    ) -> Self {
+        let main_render_system = MainRenderSystem::new(
+            core.clone(),
+            render_graph,
+            &Vertex::get_attribute_descriptions(),
+            &vec![Vertex::get_binding_description()],
+            &[
+                block_material.descriptor.set_layout,
+                shadow_map.descriptor.set_layout,
+            ],
+            PipelineVariant::Cutout,
+        );
+        let mesh_pool = if indices.is_empty() {
+            None
+        } else {
+            Some(memory.create_mesh_pool(vertices, indices))
+        };
+        CutoutRenderSystem {
+            core,
+            main_render_system,
+            block_material,
+            shadow_map,
+            mesh_pool,
+            index_count: indices.len() as u32,
+        }
+    }
+    /// Leaves and plants cast shadows through their alpha-tested holes.
+    pub fn shadow_caster(&self) -> Option<ShadowCaster> {
+        self.mesh_pool.map(|mesh_pool| ShadowCaster {
+            mesh_pool,
+            index_count: self.index_count,
+        })
+    }
+}
+impl RenderSystem for CutoutRenderSystem {
+    fn stage(&self) -> RenderStage {
+        RenderStage::Terrain
+    }
+    fn render(&mut self, frame: &mut FrameInfo) {
+        let mesh_pool = match self.mesh_pool {
+            Some(mesh_pool) => mesh_pool,
+            None => return,
+        };
+        let command_buffer = frame.command_buffer;
+        let push = PushConstant::new(frame.camera.proj_view, frame.camera.near, frame.camera.far);
+        self.main_render_system.bind(&command_buffer, push);
+        self.main_render_system.bind_descriptor_sets(
+            &command_buffer,
+            &[
+                self.block_material.descriptor.set,
+                self.shadow_map.descriptor.set,
+            ],
+        );
+        unsafe {
+            self.core.logical_device.cmd_bind_vertex_buffers(
+                command_buffer,
+                0,
+                &[frame.memory.buffers[mesh_pool.vertex_buffer].handle],
+                &[0],
+            );
+            self.core.logical_device.cmd_bind_index_buffer(
+                command_buffer,
+                frame.memory.buffers[mesh_pool.index_buffer].handle,
+                0,
+                vk::IndexType::UINT32,
+            );
+            self.core
+                .logical_device
+                .cmd_draw_indexed(command_buffer, self.index_count, 1, 0, 0, 0);
+        }
+    }
+    fn reload_shaders(&mut self) {
+        self.main_render_system.reload_shaders();
+    }
+    fn render_graph_changed(&mut self, render_graph: &RenderGraph) {
+        self.main_render_system.render_graph_changed(render_graph);
+    }
+    fn set_debug_view(&mut self, debug_view: DebugView) {
+        self.main_render_system.set_debug_view(debug_view);
+    }
+    fn as_any_mut(&mut self) -> &mut dyn Any {
+        self
+    }
+}
@@ -0,0 +1,126 @@
+use super::pipeline::shader::{DEBUG_LINES_FRAG, DEBUG_LINES_VERT};
+use super::pipeline::{Pipeline, PipelineConfig, PipelineVariant};
+use super::{FrameInfo, RenderStage, RenderSystem};
+use crate::core::Core;
+use crate::game::game_objects::frustum::Aabb;
+use crate::renderer::render_graph::{RenderGraph, MAIN_PASS};
+use ash::vk;
+use nalgebra_glm as glm;
+use std::any::Any;
+use std::mem;
+use std::rc::Rc;
+
+/// Laid out as `Push` in `debug_lines.vert`.
+#[derive(Debug, Clone, Copy)]
+#[repr(C)]
+struct BoxPush {
+    proj_view: glm::Mat4,
+    min_corner: glm::Vec4,
+    max_corner: glm::Vec4,
+    color: glm::Vec4,
+}
+
+fn as_bytes<T>(value: &T) -> &[u8] {
+    unsafe { std::slice::from_raw_parts(value as *const T as *const u8, mem::size_of::<T>()) }
+}
+
+/// Outlines boxes, such as chunk and entity bounds, on top of the finished
+/// scene. Boxes are queued with `draw_box` and stay until `clear`.
+pub struct DebugLinesSystem {
+    core: Rc<Core>,
+    pipeline_layout: vk::PipelineLayout,
+    pipeline: Pipeline,
+    boxes: Vec<(Aabb, glm::Vec4)>,
+}
+impl DebugLinesSystem {
+    pub fn new(core: Rc<Core>, render_graph: &RenderGraph) -> Self {
+        let push_constant_ranges = [vk::PushConstantRange::builder()
+            .stage_flags(vk::ShaderStageFlags::VERTEX)
+            .offset(0)
+            .size(mem::size_of::<BoxPush>() as u32)
+            .build()];
+        let pipeline_layout_info = vk::PipelineLayoutCreateInfo::builder()
+            .push_constant_ranges(&push_constant_ranges)
+            .build();
+        let pipeline_layout = unsafe {
+            core.logical_device
+                .create_pipeline_layout(&pipeline_layout_info, None)
+                .expect("Failed to create pipeline layout")
+        };
+        let mut pipeline = Pipeline::new(core.clone());
+        pipeline.create_graphic_pipeline(
+            &DEBUG_LINES_VERT,
+            &DEBUG_LINES_FRAG,
+            PipelineConfig::for_pass(render_graph, MAIN_PASS, pipeline_layout)
+                .variant(PipelineVariant::Lines)
+                .depth_write(false),
+            &[],
+            &[],
+        );
+        DebugLinesSystem {
+            core,
+            pipeline_layout,
+            pipeline,
+            boxes: Vec::new(),
+        }
+    }
+    pub fn draw_box(&mut self, aabb: Aabb, color: glm::Vec4) {
+        self.boxes.push((aabb, color));
+    }
+    pub fn clear(&mut self) {
+        self.boxes.clear();
+    }
+}
+impl RenderSystem for DebugLinesSystem {
+    fn stage(&self) -> RenderStage {
+        RenderStage::DebugLines
+    }
+    fn render(&mut self, frame: &mut FrameInfo) {
+        if self.boxes.is_empty() {
+            return;
+        }
+        let device = &self.core.logical_device;
+        unsafe {
+            device.cmd_bind_pipeline(
+                frame.command_buffer,
+                vk::PipelineBindPoint::GRAPHICS,
+                self.pipeline.graphic_pipeline,
+            );
+            for (aabb, color) in &self.boxes {
+                let push = BoxPush {
+                    proj_view: frame.camera.proj_view,
+                    min_corner: aabb.min.push(1.0),
+                    max_corner: aabb.max.push(1.0),
+                    color: *color,
+                };
+                device.cmd_push_constants(
+                    frame.command_buffer,
+                    self.pipeline_layout,
+                    vk::ShaderStageFlags::VERTEX,
+                    0,
+                    as_bytes(&push),
+                );
+                // two vertices for each of the 12 edges
+                device.cmd_draw(frame.command_buffer, 24, 1, 0, 0);
+            }
+        }
+    }
+    fn reload_shaders(&mut self) {
+        self.pipeline.reload_if_changed();
+    }
+    fn render_graph_changed(&mut self, render_graph: &RenderGraph) {
+        self.pipeline.rebuild_for_pass(render_graph, MAIN_PASS);
+    }
+    fn as_any_mut(&mut self) -> &mut dyn Any {
+        self
+    }
+}
+impl Drop for DebugLinesSystem {
+    fn drop(&mut self) {
+        unsafe {
+            self.core
+                .logical_device
+                .destroy_pipeline_layout(self.pipeline_layout, None);
+        }
+    }
+}
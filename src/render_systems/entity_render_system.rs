@@ -0,0 +1,136 @@
+use super::pipeline::shader::{ENTITY_FRAG, ENTITY_VERT};
+use super::pipeline::{Pipeline, PipelineConfig, PipelineVariant};
+use super::{FrameInfo, RenderStage, RenderSystem};
+use crate::core::Core;
+use crate::game::game_objects::GameObject;
+use crate::renderer::render_graph::{RenderGraph, MAIN_PASS};
+use ash::vk;
+use nalgebra_glm as glm;
+use std::any::Any;
+use std::mem;
+use std::rc::Rc;
+
+/// Laid out as `Push` in `entity.vert`.
+#[derive(Debug, Clone, Copy)]
+#[repr(C)]
+struct EntityPush {
+    proj_view: glm::Mat4,
+    min_corner: glm::Vec4,
+    max_corner: glm::Vec4,
+    /// `GameObject::color` in `rgb`, `Lighting::ambient` in `a`.
+    color: glm::Vec4,
+    /// Towards the sun in `xyz`, `Lighting::sunlight` in `w`.
+    sun_direction: glm::Vec4,
+}
+
+fn as_bytes<T>(value: &T) -> &[u8] {
+    unsafe { std::slice::from_raw_parts(value as *const T as *const u8, mem::size_of::<T>()) }
+}
+
+/// Draws the game objects that are not part of the block world, each as a
+/// sunlit solid box filling its bounding box.
+pub struct EntityRenderSystem {
+    core: Rc<Core>,
+    pipeline_layout: vk::PipelineLayout,
+    pipeline: Pipeline,
+    pub game_objects: Vec<Box<dyn GameObject>>,
+}
+impl EntityRenderSystem {
+    pub fn new(
+        core: Rc<Core>,
+        render_graph: &RenderGraph,
+        game_objects: Vec<Box<dyn GameObject>>,
+    ) -> Self {
+        let push_constant_ranges = [vk::PushConstantRange::builder()
+            .stage_flags(vk::ShaderStageFlags::VERTEX)
+            .offset(0)
+            .size(mem::size_of::<EntityPush>() as u32)
+            .build()];
+        let pipeline_layout_info = vk::PipelineLayoutCreateInfo::builder()
+            .push_constant_ranges(&push_constant_ranges)
+            .build();
+        let pipeline_layout = unsafe {
+            core.logical_device
+                .create_pipeline_layout(&pipeline_layout_info, None)
+                .expect("Failed to create pipeline layout")
+        };
+        let mut pipeline = Pipeline::new(core.clone());
+        pipeline.create_graphic_pipeline(
+            &ENTITY_VERT,
+            &ENTITY_FRAG,
+            // the generated faces do not share a winding, so both sides are drawn
+            PipelineConfig::for_pass(render_graph, MAIN_PASS, pipeline_layout)
+                .variant(PipelineVariant::Opaque)
+                .cull_mode(vk::CullModeFlags::NONE),
+            &[],
+            &[],
+        );
+        EntityRenderSystem {
+            core,
+            pipeline_layout,
+            pipeline,
+            game_objects,
+        }
+    }
+}
+impl RenderSystem for EntityRenderSystem {
+    fn stage(&self) -> RenderStage {
+        RenderStage::Entities
+    }
+    fn render(&mut self, frame: &mut FrameInfo) {
+        let device = &self.core.logical_device;
+        unsafe {
+            device.cmd_bind_pipeline(
+                frame.command_buffer,
+                vk::PipelineBindPoint::GRAPHICS,
+                self.pipeline.graphic_pipeline,
+            );
+        }
+        let lighting = &frame.lighting;
+        for game_object in &mut self.game_objects {
+            game_object.update();
+            let aabb = game_object.bounding_box();
+            if !frame.frustum.intersects_aabb(&aabb) {
+                frame.culling_stats.culled += 1;
+                continue;
+            }
+            frame.culling_stats.drawn += 1;
+            let push = EntityPush {
+                proj_view: frame.camera.proj_view,
+                min_corner: aabb.min.push(1.0),
+                max_corner: aabb.max.push(1.0),
+                color: game_object.color().push(lighting.ambient),
+                sun_direction: lighting.sun_direction.push(lighting.sunlight),
+            };
+            unsafe {
+                device.cmd_push_constants(
+                    frame.command_buffer,
+                    self.pipeline_layout,
+                    vk::ShaderStageFlags::VERTEX,
+                    0,
+                    as_bytes(&push),
+                );
+                // two triangles for each of the 6 faces
+                device.cmd_draw(frame.command_buffer, 36, 1, 0, 0);
+            }
+        }
+    }
+    fn reload_shaders(&mut self) {
+        self.pipeline.reload_if_changed();
+    }
+    fn render_graph_changed(&mut self, render_graph: &RenderGraph) {
+        self.pipeline.rebuild_for_pass(render_graph, MAIN_PASS);
+    }
+    fn as_any_mut(&mut self) -> &mut dyn Any {
+        self
+    }
+}
+impl Drop for EntityRenderSystem {
+    fn drop(&mut self) {
+        unsafe {
+            self.core
+                .logical_device
+                .destroy_pipeline_layout(self.pipeline_layout, None);
+        }
+    }
+}
@@ -1,51 +1,113 @@
-use super::pipeline::{Pipeline, PipelineConfig};
-use super::RenderSystem;
+use super::pipeline::shader::{CUTOUT_FRAG, DEBUG_VIEW_FRAG, MAIN_FRAG, MAIN_VERT, WATER_FRAG};
+use super::pipeline::{Pipeline, PipelineConfig, PipelineVariant};
 use crate::core::Core;
+use crate::renderer::render_graph::{RenderGraph, MAIN_PASS};
 use ash::vk;
 use nalgebra_glm as glm;
 use std::mem;
 use std::rc::Rc;
-#[derive(Debug)]
+#[derive(Debug, Clone, Copy)]
+#[repr(C)]
 pub struct PushConstant {
     pub proj_view: glm::Mat4,
+    /// Identifies the drawable, used to colour each chunk differently.
+    pub draw_id: u32,
+    pub debug_view: u32,
+    pub near: f32,
+    pub far: f32,
 }
 impl PushConstant {
-    pub fn new(proj_view: glm::Mat4) -> Self {
-        PushConstant { proj_view }
+    pub fn new(proj_view: glm::Mat4, near: f32, far: f32) -> Self {
+        PushConstant {
+            proj_view,
+            draw_id: 0,
+            debug_view: DebugView::Filled as u32,
+            near,
+            far,
+        }
     }
     pub fn as_u8(&self) -> Vec<u8> {
         unsafe {
-            let mut proj_view = self.proj_view.as_slice().align_to::<u8>().1.to_owned();
-            proj_view
+            std::slice::from_raw_parts(
+                self as *const PushConstant as *const u8,
+                mem::size_of::<PushConstant>(),
+            )
+            .to_owned()
         }
     }
 }
 
+/// How the main render system shades the world. The last three share one
+/// debug fragment shader and are told apart by `PushConstant::debug_view`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum DebugView {
+    Filled,
+    Wireframe,
+    Normals,
+    Depth,
+    ChunkColour,
+}
+impl DebugView {
+    pub fn next(&self) -> Self {
+        match self {
+            DebugView::Filled => DebugView::Wireframe,
+            DebugView::Wireframe => DebugView::Normals,
+            DebugView::Normals => DebugView::Depth,
+            DebugView::Depth => DebugView::ChunkColour,
+            DebugView::ChunkColour => DebugView::Filled,
+        }
+    }
+}
+
+/// Whether the device can rasterise lines as polygons, which
+/// `DebugView::Wireframe` needs.
+pub fn supports_wireframe(core: &Core) -> bool {
+    let features = unsafe {
+        core.instance
+            .get_physical_device_features(core.physical_device)
+    };
+    features.fill_mode_non_solid == vk::TRUE
+}
+
 pub struct MainRenderSystem {
     core: Rc<Core>,
     pipeline_layout: vk::PipelineLayout,
     pipeline: Pipeline,
+    wireframe_pipeline: Option<Pipeline>,
+    debug_pipeline: Pipeline,
+    debug_view: DebugView,
 }
 impl MainRenderSystem {
-    fn new(
+    pub fn new(
         core: Rc<Core>,
-        render_pass: &vk::RenderPass,
+        render_graph: &RenderGraph,
         attribute_descriptions: &Vec<vk::VertexInputAttributeDescription>,
         binding_descriptions: &Vec<vk::VertexInputBindingDescription>,
+        descriptor_set_layouts: &[vk::DescriptorSetLayout],
+        variant: PipelineVariant,
     ) -> Self {
         let mut render_system = MainRenderSystem {
             pipeline: Pipeline::new(core.clone()),
+            wireframe_pipeline: None,
+            debug_pipeline: Pipeline::new(core.clone()),
+            debug_view: DebugView::Filled,
             pipeline_layout: vk::PipelineLayout::default(),
             core,
         };
-        render_system.create_pipeline_layout();
-        render_system.create_pipeline(render_pass, attribute_descriptions, binding_descriptions);
+        render_system.create_pipeline_layout(descriptor_set_layouts);
+        render_system.create_pipeline(
+            render_graph,
+            attribute_descriptions,
+            binding_descriptions,
+            variant,
+        );
         render_system
     }
-    fn create_pipeline_layout(&mut self) {
+    fn create_pipeline_layout(&mut self, descriptor_set_layouts: &[vk::DescriptorSetLayout]) {
         let pipeline_layout_info = vk::PipelineLayoutCreateInfo::builder()
+            .set_layouts(descriptor_set_layouts)
             .push_constant_ranges(&[vk::PushConstantRange::builder()
-                .stage_flags(vk::ShaderStageFlags::VERTEX)
+                .stage_flags(vk::ShaderStageFlags::VERTEX | vk::ShaderStageFlags::FRAGMENT)
                 .offset(0)
                 .size(mem::size_of::<PushConstant>() as u32)
                 .build()])
@@ -61,61 +123,118 @@ impl MainRenderSystem {
     }
     fn create_pipeline(
         &mut self,
-        render_pass: &vk::RenderPass,
+        render_graph: &RenderGraph,
         attribute_descriptions: &Vec<vk::VertexInputAttributeDescription>,
         binding_descriptions: &Vec<vk::VertexInputBindingDescription>,
+        variant: PipelineVariant,
     ) {
         assert!(
             self.pipeline_layout != vk::PipelineLayout::default(),
             "Cannot create pipeline before pipeline layout"
         );
-        let mut pipeline_config = PipelineConfig::default();
-        pipeline_config.init();
-        pipeline_config.render_pass = render_pass.clone();
-        pipeline_config.pipeline_layout = self.pipeline_layout;
+        let pipeline_config =
+            PipelineConfig::for_pass(render_graph, MAIN_PASS, self.pipeline_layout)
+                .variant(variant);
+        // cutout geometry needs the alpha test in every shaded view
+        let fragment_shader = match variant {
+            PipelineVariant::Cutout => &CUTOUT_FRAG,
+            PipelineVariant::Water => &WATER_FRAG,
+            _ => &MAIN_FRAG,
+        };
         self.pipeline.create_graphic_pipeline(
-            "shaders/shader.vert.spv",
-            "shaders/shader.frag.spv",
-            pipeline_config,
+            &MAIN_VERT,
+            fragment_shader,
+            pipeline_config.clone(),
+            binding_descriptions.as_slice(),
+            attribute_descriptions.as_slice(),
+        );
+        self.debug_pipeline.create_graphic_pipeline(
+            &MAIN_VERT,
+            &DEBUG_VIEW_FRAG,
+            pipeline_config.clone(),
             binding_descriptions.as_slice(),
             attribute_descriptions.as_slice(),
         );
+        if supports_wireframe(&self.core) {
+            let mut wireframe_pipeline = Pipeline::new(self.core.clone());
+            wireframe_pipeline.create_graphic_pipeline(
+                &MAIN_VERT,
+                fragment_shader,
+                pipeline_config.variant(PipelineVariant::Wireframe),
+                binding_descriptions.as_slice(),
+                attribute_descriptions.as_slice(),
+            );
+            self.wireframe_pipeline = Some(wireframe_pipeline);
+        }
+    }
+    /// Wireframe is drawn filled when `supports_wireframe` is false.
+    pub fn set_debug_view(&mut self, debug_view: DebugView) {
+        self.debug_view = debug_view;
     }
     pub fn bind(&mut self, command_buffer: &vk::CommandBuffer, push_constant: PushConstant) {
         let pipeline_bind_point = vk::PipelineBindPoint::GRAPHICS;
+        let pipeline = match (self.debug_view, &self.wireframe_pipeline) {
+            (DebugView::Filled, _) => &self.pipeline,
+            (DebugView::Wireframe, Some(wireframe_pipeline)) => wireframe_pipeline,
+            (DebugView::Wireframe, None) => &self.pipeline,
+            _ => &self.debug_pipeline,
+        };
         unsafe {
             self.core.logical_device.cmd_bind_pipeline(
                 *command_buffer,
                 pipeline_bind_point,
-                self.pipeline.graphic_pipeline,
+                pipeline.graphic_pipeline,
             );
-            //bind vertex buffer to cmd
+        }
+        self.push_constants(command_buffer, push_constant);
+    }
+    /// Updates the push constants for the next draw, e.g. its `draw_id`.
+    pub fn push_constants(&self, command_buffer: &vk::CommandBuffer, push_constant: PushConstant) {
+        let push_constant = PushConstant {
+            debug_view: self.debug_view as u32,
+            ..push_constant
+        };
+        unsafe {
             self.core.logical_device.cmd_push_constants(
                 *command_buffer,
                 self.pipeline_layout,
-                vk::ShaderStageFlags::VERTEX,
+                vk::ShaderStageFlags::VERTEX | vk::ShaderStageFlags::FRAGMENT,
                 0,
                 push_constant.as_u8().as_slice(),
             )
         }
     }
-}
-impl RenderSystem for MainRenderSystem {
-    fn new(
-        core: Rc<Core>,
-        render_pass: &vk::RenderPass,
-        attribute_descriptions: &Vec<vk::VertexInputAttributeDescription>,
-        binding_descriptions: &Vec<vk::VertexInputBindingDescription>,
-    ) -> Self {
-        Self::new(
-            core,
-            render_pass,
-            attribute_descriptions,
-            binding_descriptions,
-        )
+    pub fn reload_shaders(&mut self) {
+        self.pipeline.reload_if_changed();
+        self.debug_pipeline.reload_if_changed();
+        if let Some(ref mut wireframe_pipeline) = self.wireframe_pipeline {
+            wireframe_pipeline.reload_if_changed();
+        }
+    }
+    /// Rebuilds every pipeline for the main pass of a rebuilt render graph.
+    pub fn render_graph_changed(&mut self, render_graph: &RenderGraph) {
+        self.pipeline.rebuild_for_pass(render_graph, MAIN_PASS);
+        self.debug_pipeline
+            .rebuild_for_pass(render_graph, MAIN_PASS);
+        if let Some(ref mut wireframe_pipeline) = self.wireframe_pipeline {
+            wireframe_pipeline.rebuild_for_pass(render_graph, MAIN_PASS);
+        }
     }
-    fn bind(&mut self, command_buffer: &vk::CommandBuffer, push_constant: PushConstant) {
-        self.bind(command_buffer, push_constant);
+    pub fn bind_descriptor_sets(
+        &self,
+        command_buffer: &vk::CommandBuffer,
+        descriptor_sets: &[vk::DescriptorSet],
+    ) {
+        unsafe {
+            self.core.logical_device.cmd_bind_descriptor_sets(
+                *command_buffer,
+                vk::PipelineBindPoint::GRAPHICS,
+                self.pipeline_layout,
+                0,
+                descriptor_sets,
+                &[],
+            );
+        }
     }
 }
 impl Drop for MainRenderSystem {
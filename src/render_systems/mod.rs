@@ -1,21 +1,181 @@
+pub mod block_material;
+pub mod cloud_render_system;
+pub mod culling_system;
+pub mod cutout_render_system;
+pub mod debug_lines_system;
+pub mod entity_render_system;
 pub mod main_render_system;
+pub mod occlusion_system;
+pub mod particle_system;
 mod pipeline;
+pub mod post_process_system;
+pub mod shadow_map;
+pub mod shadow_render_system;
+pub mod sky_render_system;
+pub mod ssao_system;
+pub mod terrain_render_system;
+pub mod translucent_render_system;
+pub mod ui_render_system;
+use crate::game::game_objects::frustum::{CullingStats, Frustum};
+use crate::memory::Memory;
+use crate::renderer::render_graph::{RenderGraph, MAIN_PASS};
 use ash::vk;
-pub use main_render_system::MainRenderSystem;
-use std::ops::Drop;
-use crate::core::Core;
-use std::rc::Rc;
-use main_render_system::PushConstant;
-pub trait RenderSystem: Drop {
-    fn new(
-        core: Rc<Core>,
-        render_pass: &vk::RenderPass,
-        attribute_descriptions: &Vec<vk::VertexInputAttributeDescription>,
-        binding_descriptions: &Vec<vk::VertexInputBindingDescription>,
-    ) -> Self
-    where
-        Self: Sized;
-    fn bind(&mut self, command_buffer: &vk::CommandBuffer,push_constant:PushConstant);
+pub use main_render_system::{DebugView, MainRenderSystem};
+use nalgebra_glm as glm;
+use std::any::Any;
+
+/// When a render system records its draws. Systems run in this order, and
+/// in registration order within a stage.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+pub enum RenderStage {
+    Terrain,
+    Entities,
+    Sky,
+    Translucent,
+    DebugLines,
+    PostProcess,
+    Ui,
+}
+
+/// The camera as every render system sees it this frame.
+#[derive(Debug, Clone, Copy)]
+pub struct CameraInfo {
+    pub proj_view: glm::Mat4,
+    /// The perspective projection alone, for passes working in view space.
+    pub projection: glm::Mat4,
+    pub position: glm::Vec3,
+    /// Unit view direction.
+    pub forward: glm::Vec3,
+    pub near: f32,
+    pub far: f32,
+}
+
+/// How the scene is lit this frame.
+#[derive(Debug, Clone, Copy)]
+pub struct Lighting {
+    /// Points towards the sun, also below the horizon at night.
+    pub sun_direction: glm::Vec3,
+    /// Light level of faces the sun does not reach, from 0 to 1.
+    pub ambient: f32,
+    /// Light level of faces in full sun, never below `ambient`.
+    pub sunlight: f32,
+    /// From 0 at night to 1 by day.
+    pub daylight: f32,
+    pub sky_zenith: glm::Vec3,
+    pub sky_horizon: glm::Vec3,
+}
+
+/// How fog thickens with distance from the camera.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum FogMode {
+    /// No fog before `start`, opaque from `end` on.
+    Linear { start: f32, end: f32 },
+    /// Thickens from the camera on, e.g. under water.
+    Exponential { density: f32 },
+}
+
+/// The fog the world fades into this frame.
+#[derive(Debug, Clone, Copy)]
+pub struct Fog {
+    pub mode: FogMode,
+    /// Also the colour the frame is cleared to.
+    pub color: glm::Vec3,
+}
+
+/// Everything a render system may use while recording one frame.
+pub struct FrameInfo<'a> {
+    pub command_buffer: vk::CommandBuffer,
+    /// Which of the `MAX_FRAMES_IN_FLIGHT` frames is being recorded.
+    pub frame_index: u32,
+    pub camera: CameraInfo,
+    pub frustum: Frustum,
+    pub lighting: Lighting,
+    pub fog: Fog,
+    /// Seconds since the renderer started, for animations.
+    pub time: f32,
+    pub memory: &'a mut Memory,
+    /// Systems that cull add what they drew and skipped.
+    pub culling_stats: CullingStats,
+}
+
+/// One kind of drawable with its own pipelines, pipeline layout and push
+/// constant or descriptor types. Registered with `RenderSystems`.
+pub trait RenderSystem: Any {
+    fn stage(&self) -> RenderStage;
+    /// The render graph pass the system draws in.
+    fn pass(&self) -> &'static str {
+        MAIN_PASS
+    }
+    /// Records work that must happen outside the render pass, such as
+    /// compute dispatches, query resets and barriers.
+    fn prepare(&mut self, _frame: &mut FrameInfo) {}
+    /// Records the draws, inside the render pass.
+    fn render(&mut self, frame: &mut FrameInfo);
+    /// Rebuilds pipelines whose shaders changed on disk. Called between frames.
+    fn reload_shaders(&mut self) {}
+    /// Called between frames after the render graph changed: either its
+    /// swap-chain-sized images were recreated, or it was rebuilt with new
+    /// render passes and images, e.g. for another sample count. Systems
+    /// point descriptors at the new views and rebuild their pipelines.
+    fn render_graph_changed(&mut self, _render_graph: &RenderGraph) {}
+    /// Switches how the system shades blocks, if it draws any.
+    fn set_debug_view(&mut self, _debug_view: DebugView) {}
+    fn as_any_mut(&mut self) -> &mut dyn Any;
+}
+
+/// The render systems of a `Renderer`, kept sorted by stage.
+#[derive(Default)]
+pub struct RenderSystems {
+    systems: Vec<Box<dyn RenderSystem>>,
+}
+impl RenderSystems {
+    pub fn register(&mut self, system: Box<dyn RenderSystem>) {
+        let stage = system.stage();
+        let position = self
+            .systems
+            .iter()
+            .position(|registered| registered.stage() > stage)
+            .unwrap_or(self.systems.len());
+        self.systems.insert(position, system);
+    }
+    /// The first registered system of type `T`.
+    pub fn get_mut<T: RenderSystem>(&mut self) -> Option<&mut T> {
+        self.systems
+            .iter_mut()
+            .find_map(|system| system.as_any_mut().downcast_mut::<T>())
+    }
+    /// Every registered system of type `T`, in the order they run.
+    pub fn iter_mut<T: RenderSystem>(&mut self) -> impl Iterator<Item = &mut T> {
+        self.systems
+            .iter_mut()
+            .filter_map(|system| system.as_any_mut().downcast_mut::<T>())
+    }
+    pub fn prepare(&mut self, frame: &mut FrameInfo) {
+        for system in self.systems.iter_mut() {
+            system.prepare(frame);
+        }
+    }
+    /// Runs the systems that draw in `pass`.
+    pub fn render(&mut self, frame: &mut FrameInfo, pass: &str) {
+        for system in self.systems.iter_mut().filter(|system| system.pass() == pass) {
+            system.render(frame);
+        }
+    }
+    pub fn reload_shaders(&mut self) {
+        for system in self.systems.iter_mut() {
+            system.reload_shaders();
+        }
+    }
+    pub fn render_graph_changed(&mut self, render_graph: &RenderGraph) {
+        for system in self.systems.iter_mut() {
+            system.render_graph_changed(render_graph);
+        }
+    }
+    pub fn set_debug_view(&mut self, debug_view: DebugView) {
+        for system in self.systems.iter_mut() {
+            system.set_debug_view(debug_view);
+        }
+    }
 }
 
 #[macro_export]
@@ -28,3 +188,39 @@ macro_rules! offset_of {
         }
     }};
 }
+
+#[cfg(test)]
+mod render_systems_tests {
+    use super::*;
+    struct Stub(RenderStage, u32);
+    impl RenderSystem for Stub {
+        fn stage(&self) -> RenderStage {
+            self.0
+        }
+        fn render(&mut self, _frame: &mut FrameInfo) {}
+        fn as_any_mut(&mut self) -> &mut dyn Any {
+            self
+        }
+    }
+    #[test]
+    fn systems_run_in_stage_then_registration_order() {
+        let mut systems = RenderSystems::default();
+        systems.register(Box::new(Stub(RenderStage::Ui, 0)));
+        systems.register(Box::new(Stub(RenderStage::Terrain, 1)));
+        systems.register(Box::new(Stub(RenderStage::Translucent, 2)));
+        systems.register(Box::new(Stub(RenderStage::Terrain, 3)));
+        let stages: Vec<RenderStage> = systems.systems.iter().map(|system| system.stage()).collect();
+        assert_eq!(
+            stages,
+            vec![
+                RenderStage::Terrain,
+                RenderStage::Terrain,
+                RenderStage::Translucent,
+                RenderStage::Ui
+            ]
+        );
+        assert_eq!(systems.get_mut::<Stub>().unwrap().1, 1);
+        let ids: Vec<u32> = systems.iter_mut::<Stub>().map(|stub| stub.1).collect();
+        assert_eq!(ids, vec![1, 3, 2, 0]);
+    }
+}
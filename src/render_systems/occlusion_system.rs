@@ -0,0 +1,200 @@
+use super::pipeline::shader::{OCCLUSION_BOX_FRAG, OCCLUSION_BOX_VERT};
+use super::pipeline::{BlendMode, Pipeline, PipelineConfig};
+use crate::core::Core;
+use crate::game::game_objects::frustum::Aabb;
+use crate::renderer::render_graph::{RenderGraph, MAIN_PASS};
+use crate::MAX_FRAMES_IN_FLIGHT;
+use ash::vk;
+use nalgebra_glm as glm;
+use std::mem;
+use std::rc::Rc;
+
+/// Grows each proxy box so it is not hidden by the chunk faces lying on it.
+const BOX_PADDING: f32 = 0.05;
+
+#[derive(Debug, Clone, Copy)]
+#[repr(C)]
+struct OcclusionPushConstant {
+    proj_view: glm::Mat4,
+    min: glm::Vec4,
+    max: glm::Vec4,
+}
+
+/// Hardware occlusion queries against chunk bounding boxes. Each frame the
+/// boxes of the chunks in the frustum are drawn after the terrain, depth
+/// tested but invisible, and counted in that frame's query pool. When the
+/// frame in flight comes round again its results decide which chunks are
+/// skipped; a query without a result counts as visible.
+pub struct OcclusionSystem {
+    core: Rc<Core>,
+    pipeline_layout: vk::PipelineLayout,
+    pipeline: Pipeline,
+    query_pools: Vec<vk::QueryPool>,
+    /// Which query pools have queries recorded since they were last reset.
+    issued: Vec<bool>,
+    visible: Vec<bool>,
+}
+impl OcclusionSystem {
+    pub fn new(core: Rc<Core>, render_graph: &RenderGraph, query_count: u32) -> Self {
+        let pipeline_layout_info = vk::PipelineLayoutCreateInfo::builder()
+            .push_constant_ranges(&[vk::PushConstantRange::builder()
+                .stage_flags(vk::ShaderStageFlags::VERTEX)
+                .offset(0)
+                .size(mem::size_of::<OcclusionPushConstant>() as u32)
+                .build()])
+            .build();
+        let pipeline_layout = unsafe {
+            core.logical_device
+                .create_pipeline_layout(&pipeline_layout_info, None)
+                .expect("Failed to create pipeline layout")
+        };
+        let mut pipeline = Pipeline::new(core.clone());
+        pipeline.create_graphic_pipeline(
+            &OCCLUSION_BOX_VERT,
+            &OCCLUSION_BOX_FRAG,
+            PipelineConfig::for_pass(render_graph, MAIN_PASS, pipeline_layout)
+                .topology(vk::PrimitiveTopology::TRIANGLE_STRIP)
+                .cull_mode(vk::CullModeFlags::NONE)
+                .depth_write(false)
+                .depth_compare_op(vk::CompareOp::LESS_OR_EQUAL)
+                .blend_mode(BlendMode::Masked),
+            &[],
+            &[],
+        );
+        let query_pool_info = vk::QueryPoolCreateInfo::builder()
+            .query_type(vk::QueryType::OCCLUSION)
+            .query_count(query_count.max(1))
+            .build();
+        let query_pools = (0..MAX_FRAMES_IN_FLIGHT)
+            .map(|_| unsafe {
+                core.logical_device
+                    .create_query_pool(&query_pool_info, None)
+                    .expect("Failed to create query pool")
+            })
+            .collect();
+        OcclusionSystem {
+            core,
+            pipeline_layout,
+            pipeline,
+            query_pools,
+            issued: vec![false; MAX_FRAMES_IN_FLIGHT as usize],
+            visible: vec![true; query_count as usize],
+        }
+    }
+    /// Reads back the queries this frame in flight recorded last time and
+    /// resets its pool. Its fence must have been waited on, and this must be
+    /// recorded outside a render pass.
+    pub fn begin_frame(&mut self, command_buffer: vk::CommandBuffer, frame_index: u32) {
+        let query_pool = self.query_pools[frame_index as usize];
+        let query_count = self.visible.len() as u32;
+        if self.issued[frame_index as usize] && query_count > 0 {
+            // (samples passed, availability) per query
+            let mut results = vec![[0_u32; 2]; query_count as usize];
+            let status = unsafe {
+                self.core.logical_device.get_query_pool_results(
+                    query_pool,
+                    0,
+                    query_count,
+                    &mut results,
+                    vk::QueryResultFlags::WITH_AVAILABILITY,
+                )
+            };
+            match status {
+                Ok(()) | Err(vk::Result::NOT_READY) => {
+                    for (visible, [samples, available]) in self.visible.iter_mut().zip(results) {
+                        *visible = available == 0 || samples > 0;
+                    }
+                }
+                Err(error) => {
+                    eprintln!("Failed to read occlusion queries: {:?}", error);
+                    self.visible.iter_mut().for_each(|visible| *visible = true);
+                }
+            }
+        }
+        unsafe {
+            self.core.logical_device.cmd_reset_query_pool(
+                command_buffer,
+                query_pool,
+                0,
+                query_count.max(1),
+            );
+        }
+        self.issued[frame_index as usize] = false;
+    }
+    pub fn reload_shaders(&mut self) {
+        self.pipeline.reload_if_changed();
+    }
+    pub fn render_graph_changed(&mut self, render_graph: &RenderGraph) {
+        self.pipeline.rebuild_for_pass(render_graph, MAIN_PASS);
+    }
+    /// Whether `query` passed the last time it was read back.
+    pub fn is_visible(&self, query: usize) -> bool {
+        self.visible[query]
+    }
+    pub fn bind(&self, command_buffer: vk::CommandBuffer) {
+        unsafe {
+            self.core.logical_device.cmd_bind_pipeline(
+                command_buffer,
+                vk::PipelineBindPoint::GRAPHICS,
+                self.pipeline.graphic_pipeline,
+            );
+        }
+    }
+    /// Draws `aabb` inside occlusion query `query`. Must follow `bind` and
+    /// the geometry that could hide it.
+    pub fn query_box(
+        &mut self,
+        command_buffer: vk::CommandBuffer,
+        frame_index: u32,
+        query: usize,
+        aabb: &Aabb,
+        proj_view: &glm::Mat4,
+    ) {
+        let padding = glm::vec3(BOX_PADDING, BOX_PADDING, BOX_PADDING);
+        let push_constant = OcclusionPushConstant {
+            proj_view: *proj_view,
+            min: glm::vec3_to_vec4(&(aabb.min - padding)),
+            max: glm::vec3_to_vec4(&(aabb.max + padding)),
+        };
+        let push_constant_bytes = unsafe {
+            std::slice::from_raw_parts(
+                &push_constant as *const OcclusionPushConstant as *const u8,
+                mem::size_of::<OcclusionPushConstant>(),
+            )
+        };
+        let query_pool = self.query_pools[frame_index as usize];
+        let device = &self.core.logical_device;
+        unsafe {
+            device.cmd_push_constants(
+                command_buffer,
+                self.pipeline_layout,
+                vk::ShaderStageFlags::VERTEX,
+                0,
+                push_constant_bytes,
+            );
+            device.cmd_begin_query(
+                command_buffer,
+                query_pool,
+                query as u32,
+                vk::QueryControlFlags::empty(),
+            );
+            device.cmd_draw(command_buffer, 14, 1, 0, 0);
+            device.cmd_end_query(command_buffer, query_pool, query as u32);
+        }
+        self.issued[frame_index as usize] = true;
+    }
+}
+impl Drop for OcclusionSystem {
+    fn drop(&mut self) {
+        unsafe {
+            for query_pool in self.query_pools.iter() {
+                self.core
+                    .logical_device
+                    .destroy_query_pool(*query_pool, None);
+            }
+            self.core
+                .logical_device
+                .destroy_pipeline_layout(self.pipeline_layout, None);
+        }
+    }
+}
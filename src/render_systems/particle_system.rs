@@ -0,0 +1,463 @@
+use super::pipeline::shader::{PARTICLES_COMP, PARTICLE_FRAG, PARTICLE_VERT};
+use super::pipeline::{ComputePipeline, Pipeline, PipelineConfig, PipelineVariant};
+use super::{FrameInfo, RenderStage, RenderSystem};
+use crate::core::Core;
+use crate::memory::descriptor::Descriptor;
+use crate::memory::{AllocationType, Memory};
+use crate::renderer::render_graph::{RenderGraph, MAIN_PASS};
+use crate::MAX_FRAMES_IN_FLIGHT;
+use ash::vk;
+use nalgebra_glm as glm;
+use std::any::Any;
+use std::mem;
+use std::rc::Rc;
+use std::str::FromStr;
+use std::time;
+
+/// Slots in the particle ring. Spawning more than this overwrites the
+/// oldest particles.
+pub const MAX_PARTICLES: u32 = 8192;
+/// Most particles spawned in one frame. Further requests wait for the next.
+const SPAWN_CAPACITY: usize = 1024;
+/// `local_size_x` in `particles.comp`.
+const WORKGROUP_SIZE: u32 = 64;
+/// Longest step the simulation takes, so a stalled frame does not fling
+/// particles through the ground.
+const MAX_DELTA_TIME: f32 = 0.1;
+
+/// What a particle looks like and how it moves, matching the `KIND_`
+/// constants of the particle shaders.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[repr(u32)]
+pub enum ParticleKind {
+    /// Chips flying off a broken block, falling and bouncing.
+    Debris = 0,
+    /// Fast streaks, gone when they hit the ground.
+    Rain = 1,
+    /// Grey puffs rising and growing.
+    Smoke = 2,
+    /// Small bright flickers rising off a torch, unlit.
+    Flame = 3,
+    /// Flakes drifting down and settling for a moment.
+    Snow = 4,
+}
+impl ParticleKind {
+    /// Longest a particle of this kind lives, in seconds, as `particles.comp`
+    /// spawns it.
+    pub const fn lifetime(&self) -> f32 {
+        match self {
+            ParticleKind::Debris => 2.5,
+            ParticleKind::Rain => 4.0,
+            ParticleKind::Smoke => 4.0,
+            ParticleKind::Flame => 0.8,
+            ParticleKind::Snow => 14.0,
+        }
+    }
+}
+impl FromStr for ParticleKind {
+    type Err = String;
+    fn from_str(name: &str) -> Result<Self, Self::Err> {
+        match name {
+            "debris" => Ok(ParticleKind::Debris),
+            "rain" => Ok(ParticleKind::Rain),
+            "smoke" => Ok(ParticleKind::Smoke),
+            "flame" => Ok(ParticleKind::Flame),
+            "snow" => Ok(ParticleKind::Snow),
+            _ => Err(format!(
+                "Unknown particle kind '{}', expected debris, rain, smoke, flame or snow",
+                name
+            )),
+        }
+    }
+}
+
+/// Laid out as `Particle` in `particles.comp` (std430).
+#[derive(Debug, Clone, Copy)]
+#[repr(C)]
+struct Particle {
+    position_age: [f32; 4],
+    /// `w` is the lifetime, 0 for a free slot.
+    velocity_life: [f32; 4],
+    kind: [u32; 4],
+}
+
+/// Laid out as `Spawn` in `particles.comp` (std430).
+#[derive(Debug, Clone, Copy)]
+#[repr(C)]
+struct SpawnRequest {
+    position: [f32; 4],
+    /// The kind in `x`, a random seed in `y`.
+    kind_seed: [u32; 4],
+}
+
+/// Laid out as `Push` in `particles.comp`.
+#[derive(Debug, Clone, Copy)]
+#[repr(C)]
+struct SimulatePush {
+    particle_count: u32,
+    first_spawn: u32,
+    spawn_count: u32,
+    world_size: u32,
+    delta_time: f32,
+}
+
+/// Laid out as `Push` in `particle.vert`.
+#[derive(Debug, Clone, Copy)]
+#[repr(C)]
+struct DrawPush {
+    proj_view: glm::Mat4,
+    camera_right: glm::Vec4,
+    /// `Lighting::sunlight` in `w`.
+    camera_up: glm::Vec4,
+}
+
+fn as_bytes<T>(value: &T) -> &[u8] {
+    unsafe { std::slice::from_raw_parts(value as *const T as *const u8, mem::size_of::<T>()) }
+}
+
+/// Spawns, simulates and draws particles on the GPU. Spawn requests queued
+/// with `spawn_particles` are handed ring slots in `prepare`, where a
+/// compute pass starts them and moves every live particle on under gravity,
+/// colliding with the world's heightfield. The ring is then drawn as
+/// camera-facing billboards in the translucent stage.
+pub struct ParticleSystem {
+    core: Rc<Core>,
+    /// One per frame in flight, each binding that frame's spawn buffer.
+    descriptors: Vec<Descriptor>,
+    simulate_layout: vk::PipelineLayout,
+    simulate_pipeline: ComputePipeline,
+    draw_layout: vk::PipelineLayout,
+    draw_pipeline: Pipeline,
+    particle_buffer: usize,
+    /// One per frame in flight, so uploading spawns never overwrites
+    /// requests another frame's dispatch may still be reading.
+    spawn_buffers: Vec<usize>,
+    world_size: u32,
+    /// Requests not yet handed to the GPU.
+    pending: Vec<SpawnRequest>,
+    /// The ring slot the next spawn goes to.
+    cursor: u32,
+    /// Particles spawned so far, hashed into each request's seed.
+    spawned: u32,
+    last_update: time::Instant,
+}
+impl ParticleSystem {
+    /// `heights` holds the ground height of every block column of a world
+    /// `world_size` blocks wide, row by row along x.
+    pub fn new(
+        core: Rc<Core>,
+        memory: &mut Memory,
+        render_graph: &RenderGraph,
+        heights: &[f32],
+        world_size: u32,
+    ) -> Self {
+        let storage_binding = |binding: u32, stage_flags: vk::ShaderStageFlags| {
+            vk::DescriptorSetLayoutBinding::builder()
+                .binding(binding)
+                .descriptor_type(vk::DescriptorType::STORAGE_BUFFER)
+                .descriptor_count(1)
+                .stage_flags(stage_flags)
+                .build()
+        };
+        let bindings = [
+            storage_binding(
+                0,
+                vk::ShaderStageFlags::COMPUTE | vk::ShaderStageFlags::VERTEX,
+            ),
+            storage_binding(1, vk::ShaderStageFlags::COMPUTE),
+            storage_binding(2, vk::ShaderStageFlags::COMPUTE),
+        ];
+        let descriptors: Vec<Descriptor> = (0..MAX_FRAMES_IN_FLIGHT)
+            .map(|_| Descriptor::new(core.clone(), &bindings))
+            .collect();
+        let pipeline_layout = |stage_flags: vk::ShaderStageFlags, size: usize| {
+            let set_layouts = [descriptors[0].set_layout];
+            let push_constant_ranges = [vk::PushConstantRange::builder()
+                .stage_flags(stage_flags)
+                .offset(0)
+                .size(size as u32)
+                .build()];
+            let pipeline_layout_info = vk::PipelineLayoutCreateInfo::builder()
+                .set_layouts(&set_layouts)
+                .push_constant_ranges(&push_constant_ranges)
+                .build();
+            unsafe {
+                core.logical_device
+                    .create_pipeline_layout(&pipeline_layout_info, None)
+                    .expect("Failed to create pipeline layout")
+            }
+        };
+        let simulate_layout = pipeline_layout(
+            vk::ShaderStageFlags::COMPUTE,
+            mem::size_of::<SimulatePush>(),
+        );
+        let draw_layout = pipeline_layout(vk::ShaderStageFlags::VERTEX, mem::size_of::<DrawPush>());
+        let simulate_pipeline =
+            ComputePipeline::new(core.clone(), &PARTICLES_COMP, simulate_layout);
+        let mut draw_pipeline = Pipeline::new(core.clone());
+        draw_pipeline.create_graphic_pipeline(
+            &PARTICLE_VERT,
+            &PARTICLE_FRAG,
+            PipelineConfig::for_pass(render_graph, MAIN_PASS, draw_layout)
+                .variant(PipelineVariant::Translucent)
+                .cull_mode(vk::CullModeFlags::NONE)
+                .topology(vk::PrimitiveTopology::TRIANGLE_STRIP),
+            &[],
+            &[],
+        );
+
+        let particles_size =
+            (MAX_PARTICLES as usize * mem::size_of::<Particle>()) as vk::DeviceSize;
+        let spawns_size = (SPAWN_CAPACITY * mem::size_of::<SpawnRequest>()) as vk::DeviceSize;
+        let heights_size = mem::size_of_val(heights).max(mem::size_of::<f32>()) as vk::DeviceSize;
+        let create_buffer = |memory: &mut Memory, size: vk::DeviceSize| {
+            memory.create_buffer(
+                size,
+                AllocationType::Buffer,
+                vk::BufferUsageFlags::STORAGE_BUFFER,
+                vk::MemoryPropertyFlags::HOST_VISIBLE,
+            )
+        };
+        let particle_buffer = create_buffer(memory, particles_size);
+        let spawn_buffers: Vec<usize> = (0..MAX_FRAMES_IN_FLIGHT)
+            .map(|_| create_buffer(memory, spawns_size))
+            .collect();
+        let height_buffer = create_buffer(memory, heights_size);
+        // every slot starts out free
+        let free = vec![
+            Particle {
+                position_age: [0.0; 4],
+                velocity_life: [0.0; 4],
+                kind: [0; 4],
+            };
+            MAX_PARTICLES as usize
+        ];
+        memory.copy_memory(
+            None,
+            particle_buffer,
+            0,
+            particles_size,
+            free.as_ptr() as *const u8,
+        );
+        memory.copy_memory(
+            None,
+            height_buffer,
+            0,
+            mem::size_of_val(heights) as vk::DeviceSize,
+            heights.as_ptr() as *const u8,
+        );
+        for (descriptor, &spawn_buffer) in descriptors.iter().zip(&spawn_buffers) {
+            for (binding, buffer, size) in [
+                (0, particle_buffer, particles_size),
+                (1, spawn_buffer, spawns_size),
+                (2, height_buffer, heights_size),
+            ] {
+                descriptor.write_buffer(
+                    binding,
+                    vk::DescriptorType::STORAGE_BUFFER,
+                    memory.buffers[buffer].handle,
+                    0,
+                    size,
+                );
+            }
+        }
+        ParticleSystem {
+            core,
+            descriptors,
+            simulate_layout,
+            simulate_pipeline,
+            draw_layout,
+            draw_pipeline,
+            particle_buffer,
+            spawn_buffers,
+            world_size,
+            pending: vec![],
+            cursor: 0,
+            spawned: 0,
+            last_update: time::Instant::now(),
+        }
+    }
+    /// Queues `count` particles of `kind` to start at `position` next frame.
+    pub fn spawn_particles(&mut self, kind: ParticleKind, position: glm::Vec3, count: u32) {
+        for _ in 0..count {
+            self.spawned = self.spawned.wrapping_add(1);
+            self.pending.push(SpawnRequest {
+                position: [position.x, position.y, position.z, 0.0],
+                kind_seed: [kind as u32, self.spawned.wrapping_mul(2654435761), 0, 0],
+            });
+        }
+    }
+    /// Writes up to `SPAWN_CAPACITY` pending requests into `frame_index`'s
+    /// spawn buffer and reserves their ring slots. Returns the first slot
+    /// and the count.
+    fn upload_spawns(&mut self, memory: &mut Memory, frame_index: u32) -> (u32, u32) {
+        let count = self.pending.len().min(SPAWN_CAPACITY);
+        let spawns: Vec<SpawnRequest> = self.pending.drain(..count).collect();
+        if !spawns.is_empty() {
+            memory.copy_memory(
+                None,
+                self.spawn_buffers[frame_index as usize],
+                0,
+                mem::size_of_val(spawns.as_slice()) as vk::DeviceSize,
+                spawns.as_ptr() as *const u8,
+            );
+        }
+        let first = self.cursor;
+        self.cursor = (self.cursor + count as u32) % MAX_PARTICLES;
+        (first, count as u32)
+    }
+}
+impl RenderSystem for ParticleSystem {
+    fn stage(&self) -> RenderStage {
+        RenderStage::Translucent
+    }
+    fn prepare(&mut self, frame: &mut FrameInfo) {
+        let (first_spawn, spawn_count) = self.upload_spawns(frame.memory, frame.frame_index);
+        let delta_time = self.last_update.elapsed().as_secs_f32().min(MAX_DELTA_TIME);
+        self.last_update = time::Instant::now();
+        let push = SimulatePush {
+            particle_count: MAX_PARTICLES,
+            first_spawn,
+            spawn_count,
+            world_size: self.world_size,
+            delta_time,
+        };
+        let buffer_barrier = |src_access_mask, dst_access_mask| {
+            vk::BufferMemoryBarrier::builder()
+                .src_access_mask(src_access_mask)
+                .dst_access_mask(dst_access_mask)
+                .src_queue_family_index(vk::QUEUE_FAMILY_IGNORED)
+                .dst_queue_family_index(vk::QUEUE_FAMILY_IGNORED)
+                .buffer(frame.memory.buffers[self.particle_buffer].handle)
+                .offset(0)
+                .size(vk::WHOLE_SIZE)
+                .build()
+        };
+        let descriptor_set = self.descriptors[frame.frame_index as usize].set;
+        let device = &self.core.logical_device;
+        let command_buffer = frame.command_buffer;
+        unsafe {
+            // the previous frame's billboards are drawn before the ring moves on
+            device.cmd_pipeline_barrier(
+                command_buffer,
+                vk::PipelineStageFlags::VERTEX_SHADER,
+                vk::PipelineStageFlags::COMPUTE_SHADER,
+                vk::DependencyFlags::empty(),
+                &[],
+                &[buffer_barrier(
+                    vk::AccessFlags::SHADER_READ,
+                    vk::AccessFlags::SHADER_READ | vk::AccessFlags::SHADER_WRITE,
+                )],
+                &[],
+            );
+            device.cmd_bind_pipeline(
+                command_buffer,
+                vk::PipelineBindPoint::COMPUTE,
+                self.simulate_pipeline.handle,
+            );
+            device.cmd_bind_descriptor_sets(
+                command_buffer,
+                vk::PipelineBindPoint::COMPUTE,
+                self.simulate_layout,
+                0,
+                &[descriptor_set],
+                &[],
+            );
+            device.cmd_push_constants(
+                command_buffer,
+                self.simulate_layout,
+                vk::ShaderStageFlags::COMPUTE,
+                0,
+                as_bytes(&push),
+            );
+            device.cmd_dispatch(command_buffer, MAX_PARTICLES.div_ceil(WORKGROUP_SIZE), 1, 1);
+            device.cmd_pipeline_barrier(
+                command_buffer,
+                vk::PipelineStageFlags::COMPUTE_SHADER,
+                vk::PipelineStageFlags::VERTEX_SHADER,
+                vk::DependencyFlags::empty(),
+                &[],
+                &[buffer_barrier(
+                    vk::AccessFlags::SHADER_WRITE,
+                    vk::AccessFlags::SHADER_READ,
+                )],
+                &[],
+            );
+        }
+    }
+    fn render(&mut self, frame: &mut FrameInfo) {
+        // billboards face the camera and stay upright
+        let forward = frame.camera.forward;
+        let right = glm::normalize(&glm::cross(&forward, &glm::vec3(0.0, 1.0, 0.0)));
+        let up = glm::cross(&right, &forward);
+        let push = DrawPush {
+            proj_view: frame.camera.proj_view,
+            camera_right: right.push(0.0),
+            camera_up: up.push(frame.lighting.sunlight),
+        };
+        let device = &self.core.logical_device;
+        let command_buffer = frame.command_buffer;
+        unsafe {
+            device.cmd_bind_pipeline(
+                command_buffer,
+                vk::PipelineBindPoint::GRAPHICS,
+                self.draw_pipeline.graphic_pipeline,
+            );
+            device.cmd_bind_descriptor_sets(
+                command_buffer,
+                vk::PipelineBindPoint::GRAPHICS,
+                self.draw_layout,
+                0,
+                &[self.descriptors[frame.frame_index as usize].set],
+                &[],
+            );
+            device.cmd_push_constants(
+                command_buffer,
+                self.draw_layout,
+                vk::ShaderStageFlags::VERTEX,
+                0,
+                as_bytes(&push),
+            );
+            device.cmd_draw(command_buffer, 4, MAX_PARTICLES, 0, 0);
+        }
+    }
+    fn reload_shaders(&mut self) {
+        self.simulate_pipeline.reload_if_changed();
+        self.draw_pipeline.reload_if_changed();
+    }
+    fn render_graph_changed(&mut self, render_graph: &RenderGraph) {
+        self.draw_pipeline.rebuild_for_pass(render_graph, MAIN_PASS);
+    }
+    fn as_any_mut(&mut self) -> &mut dyn Any {
+        self
+    }
+}
+impl Drop for ParticleSystem {
+    fn drop(&mut self) {
+        unsafe {
+            self.core
+                .logical_device
+                .destroy_pipeline_layout(self.simulate_layout, None);
+            self.core
+                .logical_device
+                .destroy_pipeline_layout(self.draw_layout, None);
+        }
+    }
+}
+
+#[cfg(test)]
+mod particle_system_tests {
+    use super::*;
+    #[test]
+    fn gpu_structs_match_std430() {
+        assert_eq!(mem::size_of::<Particle>(), 48);
+        assert_eq!(mem::size_of::<SpawnRequest>(), 32);
+        assert_eq!(mem::size_of::<DrawPush>(), 96);
+    }
+    #[test]
+    fn kinds_parse_by_name() {
+        assert_eq!("smoke".parse(), Ok(ParticleKind::Smoke));
+        assert_eq!("flame".parse(), Ok(ParticleKind::Flame));
+        assert!("confetti".parse::<ParticleKind>().is_err());
+    }
+}
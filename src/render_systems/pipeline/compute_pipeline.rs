@@ -0,0 +1,116 @@
+use super::shader::{ShaderSource, ShaderWatcher};
+use crate::core::Core;
+use ash::vk;
+use std::rc::Rc;
+pub struct ComputePipeline {
+    core: Rc<Core>,
+    pub handle: vk::Pipeline,
+    shader_module: vk::ShaderModule,
+    shader: ShaderSource,
+    pipeline_layout: vk::PipelineLayout,
+    shader_watcher: Option<ShaderWatcher>,
+}
+impl ComputePipeline {
+    pub fn new(core: Rc<Core>, shader: &ShaderSource, pipeline_layout: vk::PipelineLayout) -> Self {
+        let code = shader
+            .compile()
+            .unwrap_or_else(|error| panic!("Failed to compile shader:\n{}", error));
+        let (shader_module, handle) = Self::build(&core, &code, pipeline_layout)
+            .unwrap_or_else(|error| panic!("Failed to create compute pipeline: {}", error));
+        ComputePipeline {
+            core,
+            handle,
+            shader_module,
+            shader: *shader,
+            pipeline_layout,
+            shader_watcher: ShaderWatcher::new(&[*shader]),
+        }
+    }
+    /// Recompiles the shader from disk when it changed and swaps in the new
+    /// pipeline. On failure the error is printed and the old pipeline stays.
+    pub fn reload_if_changed(&mut self) {
+        let changed = match self.shader_watcher {
+            Some(ref mut shader_watcher) => shader_watcher.poll(),
+            None => false,
+        };
+        if !changed {
+            return;
+        }
+        let code = match self.shader.compile_from_disk() {
+            Ok(code) => code,
+            Err(error) => {
+                eprintln!(
+                    "Failed to reload shaders, keeping the old pipeline:\n{}",
+                    error
+                );
+                return;
+            }
+        };
+        unsafe {
+            self.core.logical_device.device_wait_idle().unwrap();
+        }
+        match Self::build(&self.core, &code, self.pipeline_layout) {
+            Ok((shader_module, handle)) => {
+                self.destroy();
+                self.shader_module = shader_module;
+                self.handle = handle;
+                println!("Reloaded shader {}", self.shader.path);
+            }
+            Err(error) => eprintln!(
+                "Failed to rebuild pipeline for {}, keeping the old one: {}",
+                self.shader.path, error
+            ),
+        }
+    }
+    fn build(
+        core: &Core,
+        code: &[u32],
+        pipeline_layout: vk::PipelineLayout,
+    ) -> Result<(vk::ShaderModule, vk::Pipeline), vk::Result> {
+        let create_info = vk::ShaderModuleCreateInfo::builder().code(code).build();
+        let shader_module = unsafe {
+            core.logical_device
+                .create_shader_module(&create_info, None)?
+        };
+        let stage = vk::PipelineShaderStageCreateInfo::builder()
+            .stage(vk::ShaderStageFlags::COMPUTE)
+            .module(shader_module)
+            .name(c"main")
+            .build();
+        let pipeline_info = vk::ComputePipelineCreateInfo::builder()
+            .stage(stage)
+            .layout(pipeline_layout)
+            .base_pipeline_index(-1)
+            .build();
+        let pipelines = unsafe {
+            core.logical_device.create_compute_pipelines(
+                core.pipeline_cache.handle,
+                &[pipeline_info],
+                None,
+            )
+        };
+        match pipelines {
+            Ok(pipelines) => Ok((shader_module, pipelines[0])),
+            Err((_, error)) => {
+                unsafe {
+                    core.logical_device
+                        .destroy_shader_module(shader_module, None);
+                }
+                Err(error)
+            }
+        }
+    }
+    fn destroy(&mut self) {
+        unsafe {
+            self.core
+                .logical_device
+                .destroy_shader_module(self.shader_module, None);
+            self.core.logical_device.destroy_pipeline(self.handle, None);
+        }
+    }
+}
+impl Drop for ComputePipeline {
+    fn drop(&mut self) {
+        self.destroy();
+    }
+}
@@ -1,15 +1,29 @@
+mod compute_pipeline;
 mod pipeline_config;
-pub use pipeline_config::PipelineConfig;
+pub mod shader;
+pub use compute_pipeline::ComputePipeline;
+pub use pipeline_config::{BlendMode, PipelineConfig, PipelineVariant};
 use crate::core::Core;
+use crate::renderer::render_graph::RenderGraph;
 use ash::vk;
+use shader::{ShaderSource, ShaderWatcher};
 use std::ffi::CStr;
-use std::io::prelude::*;
+use std::mem;
 use std::rc::Rc;
 pub struct Pipeline {
     core: Rc<Core>,
     pub graphic_pipeline: vk::Pipeline,
     vert_shader_module: vk::ShaderModule,
     frag_shader_module: vk::ShaderModule,
+    shaders: Vec<ShaderSource>,
+    /// The SPIR-V the pipeline was last built from, kept so it can be
+    /// rebuilt for another render pass without compiling again.
+    vert_code: Vec<u32>,
+    frag_code: Vec<u32>,
+    shader_watcher: Option<ShaderWatcher>,
+    config_info: PipelineConfig,
+    binding_descriptions: Vec<vk::VertexInputBindingDescription>,
+    attribute_descriptions: Vec<vk::VertexInputAttributeDescription>,
 }
 impl Pipeline {
     pub fn new(core: Rc<Core>) -> Self {
@@ -18,12 +32,19 @@ impl Pipeline {
             graphic_pipeline: vk::Pipeline::null(),
             vert_shader_module: vk::ShaderModule::null(),
             frag_shader_module: vk::ShaderModule::null(),
+            shaders: vec![],
+            vert_code: vec![],
+            frag_code: vec![],
+            shader_watcher: None,
+            config_info: PipelineConfig::new(vk::RenderPass::null(), vk::PipelineLayout::null()),
+            binding_descriptions: vec![],
+            attribute_descriptions: vec![],
         }
     }
     pub fn create_graphic_pipeline(
         &mut self,
-        vert_file_path: &'static str,
-        frag_file_path: &'static str,
+        vert_shader: &ShaderSource,
+        frag_shader: &ShaderSource,
         config_info: PipelineConfig,
         binding_descriptions: &[vk::VertexInputBindingDescription],
         attribute_descriptions: &[vk::VertexInputAttributeDescription],
@@ -36,76 +57,190 @@ impl Pipeline {
             config_info.render_pass != vk::RenderPass::null(),
             "Cannot create pipeline without render pass"
         );
-        let vert_code = read_shader_code(vert_file_path);
-        let frag_code = read_shader_code(frag_file_path);
-        self.vert_shader_module = self.create_shader_mode(vert_code.as_slice());
-        self.frag_shader_module = self.create_shader_mode(frag_code.as_slice());
+        let vert_code = vert_shader
+            .compile()
+            .unwrap_or_else(|error| panic!("Failed to compile shader:\n{}", error));
+        let frag_code = frag_shader
+            .compile()
+            .unwrap_or_else(|error| panic!("Failed to compile shader:\n{}", error));
+        self.shaders = vec![*vert_shader, *frag_shader];
+        self.shader_watcher = ShaderWatcher::new(&self.shaders);
+        self.config_info = config_info;
+        self.binding_descriptions = binding_descriptions.to_vec();
+        self.attribute_descriptions = attribute_descriptions.to_vec();
+        self.build(vert_code, frag_code)
+            .unwrap_or_else(|error| panic!("Failed to create graphics pipeline: {}", error));
+    }
+    /// Rebuilds the pipeline for `pass` of a rebuilt render graph, whose
+    /// render pass or sample count may differ. Does nothing when neither
+    /// changed or the pipeline was never created. Must be called between
+    /// frames.
+    pub fn rebuild_for_pass(&mut self, render_graph: &RenderGraph, pass: &str) {
+        let render_pass = render_graph
+            .render_pass(pass)
+            .unwrap_or_else(|| panic!("No render pass named {}", pass));
+        let samples = render_graph.samples(pass);
+        if self.graphic_pipeline == vk::Pipeline::null()
+            || (self.config_info.render_pass == render_pass && self.config_info.samples == samples)
+        {
+            return;
+        }
+        self.config_info.render_pass = render_pass;
+        self.config_info.samples = samples;
+        let vert_code = mem::take(&mut self.vert_code);
+        let frag_code = mem::take(&mut self.frag_code);
+        self.build(vert_code, frag_code)
+            .unwrap_or_else(|error| panic!("Failed to rebuild graphics pipeline: {}", error));
+    }
+    /// Recompiles the shaders from disk when one of them changed and swaps in
+    /// a new `vk::Pipeline`. Must be called between frames. A shader that
+    /// fails to compile or a pipeline that fails to build, e.g. because the
+    /// stages' interfaces no longer match, is logged and the current
+    /// pipeline is kept.
+    pub fn reload_if_changed(&mut self) {
+        let changed = match self.shader_watcher {
+            Some(ref mut shader_watcher) => shader_watcher.poll(),
+            None => false,
+        };
+        if !changed {
+            return;
+        }
+        let vert_code = self.shaders[0].compile_from_disk();
+        let frag_code = self.shaders[1].compile_from_disk();
+        match (vert_code, frag_code) {
+            (Ok(vert_code), Ok(frag_code)) => {
+                unsafe {
+                    self.core.logical_device.device_wait_idle().unwrap();
+                }
+                match self.build(vert_code, frag_code) {
+                    Ok(()) => println!(
+                        "Reloaded shaders {} and {}",
+                        self.shaders[0].path, self.shaders[1].path
+                    ),
+                    Err(error) => eprintln!(
+                        "Failed to rebuild pipeline for {} and {}, keeping the old one: {}",
+                        self.shaders[0].path, self.shaders[1].path, error
+                    ),
+                }
+            }
+            (Err(error), _) | (_, Err(error)) => {
+                eprintln!(
+                    "Failed to reload shaders, keeping the old pipeline:\n{}",
+                    error
+                );
+            }
+        }
+    }
+    /// Creates a pipeline from the SPIR-V and replaces the current one with
+    /// it. On failure nothing is replaced and the current pipeline stays
+    /// usable.
+    fn build(&mut self, vert_code: Vec<u32>, frag_code: Vec<u32>) -> Result<(), vk::Result> {
+        let config_info = &self.config_info;
+        let vert_shader_module = self.create_shader_mode(&vert_code)?;
+        let frag_shader_module = match self.create_shader_mode(&frag_code) {
+            Ok(frag_shader_module) => frag_shader_module,
+            Err(error) => {
+                unsafe {
+                    self.core
+                        .logical_device
+                        .destroy_shader_module(vert_shader_module, None);
+                }
+                return Err(error);
+            }
+        };
         let mut shader_stage: Vec<vk::PipelineShaderStageCreateInfo> = vec![];
         shader_stage.push(
             vk::PipelineShaderStageCreateInfo::builder()
                 .stage(vk::ShaderStageFlags::VERTEX)
-                .module(self.vert_shader_module)
+                .module(vert_shader_module)
                 .name(unsafe { CStr::from_bytes_with_nul_unchecked(b"main\0") })
                 .build(),
         );
         shader_stage.push(
             vk::PipelineShaderStageCreateInfo::builder()
                 .stage(vk::ShaderStageFlags::FRAGMENT)
-                .module(self.frag_shader_module)
+                .module(frag_shader_module)
                 .name(unsafe { CStr::from_bytes_with_nul_unchecked(b"main\0") })
                 .build(),
         );
         let vertex_input_info = vk::PipelineVertexInputStateCreateInfo::builder()
-            .vertex_binding_descriptions(&binding_descriptions)
-            .vertex_attribute_descriptions(&attribute_descriptions)
+            .vertex_binding_descriptions(&self.binding_descriptions)
+            .vertex_attribute_descriptions(&self.attribute_descriptions)
+            .build();
+        let input_assembly_state = config_info.input_assembly_state();
+        let viewport_state = vk::PipelineViewportStateCreateInfo::builder()
+            .viewport_count(1)
+            .scissor_count(1)
+            .build();
+        let rasterization_state = config_info.rasterization_state();
+        let multisample_state = config_info.multisample_state();
+        let depth_stencil_state = config_info.depth_stencil_state();
+        let color_blend_attachments = config_info.color_blend_attachments();
+        let color_blend_state = vk::PipelineColorBlendStateCreateInfo::builder()
+            .logic_op_enable(false)
+            .logic_op(vk::LogicOp::COPY)
+            .attachments(&color_blend_attachments)
+            .blend_constants([0.0, 0.0, 0.0, 0.0])
+            .build();
+        let dynamic_state = vk::PipelineDynamicStateCreateInfo::builder()
+            .dynamic_states(&config_info.dynamic_states)
             .build();
         let pipeline_info = vk::GraphicsPipelineCreateInfo::builder()
             .stages(&shader_stage)
             .vertex_input_state(&vertex_input_info)
-            .input_assembly_state(&config_info.input_assembly_state)
-            .viewport_state(&config_info.viewport_state)
-            .rasterization_state(&config_info.rasterization_state)
-            .multisample_state(&config_info.multisample_state)
-            .depth_stencil_state(&config_info.depth_stencil_state)
-            .color_blend_state(&config_info.color_blend_state)
-            .dynamic_state(&config_info.dynamic_state)
+            .input_assembly_state(&input_assembly_state)
+            .viewport_state(&viewport_state)
+            .rasterization_state(&rasterization_state)
+            .multisample_state(&multisample_state)
+            .depth_stencil_state(&depth_stencil_state)
+            .color_blend_state(&color_blend_state)
+            .dynamic_state(&dynamic_state)
             .layout(config_info.pipeline_layout)
             .render_pass(config_info.render_pass)
             .subpass(config_info.subpass)
             .base_pipeline_index(-1)
             .base_pipeline_handle(vk::Pipeline::null())
             .build();
-        self.graphic_pipeline = unsafe {
-            self.core
-                .logical_device
-                .create_graphics_pipelines(vk::PipelineCache::null(), &[pipeline_info], None)
-                .expect("Failed to create graphics pipeline")[0]
+        let graphic_pipeline = unsafe {
+            self.core.logical_device.create_graphics_pipelines(
+                self.core.pipeline_cache.handle,
+                &[pipeline_info],
+                None,
+            )
         };
-    }
-    fn create_shader_mode(&mut self, shader_code: &[u8]) -> vk::ShaderModule {
-        let create_info = vk::ShaderModuleCreateInfo {
-            p_code: shader_code.as_ptr() as *const _,
-            code_size: shader_code.len() as _,
-            ..Default::default()
+        let graphic_pipeline = match graphic_pipeline {
+            Ok(pipelines) => pipelines[0],
+            Err((_, error)) => {
+                unsafe {
+                    self.core
+                        .logical_device
+                        .destroy_shader_module(vert_shader_module, None);
+                    self.core
+                        .logical_device
+                        .destroy_shader_module(frag_shader_module, None);
+                }
+                return Err(error);
+            }
         };
+        self.destroy();
+        self.graphic_pipeline = graphic_pipeline;
+        self.vert_shader_module = vert_shader_module;
+        self.frag_shader_module = frag_shader_module;
+        self.vert_code = vert_code;
+        self.frag_code = frag_code;
+        Ok(())
+    }
+    fn create_shader_mode(&self, shader_code: &[u32]) -> Result<vk::ShaderModule, vk::Result> {
+        let create_info = vk::ShaderModuleCreateInfo::builder()
+            .code(shader_code)
+            .build();
         unsafe {
             self.core
                 .logical_device
                 .create_shader_module(&create_info, None)
-                .expect("Failed to create shader module")
         }
     }
-}
-fn read_shader_code(file_path: &'static str) -> Vec<u8> {
-    let mut file = std::fs::File::open(file_path).expect("Failed to open shader file");
-    let mut code = Vec::new();
-    file.read_to_end(&mut code)
-        .expect("Failed to read shader file");
-    code
-}
-
-impl Drop for Pipeline {
-    fn drop(&mut self) {
+    fn destroy(&mut self) {
         unsafe {
             self.core
                 .logical_device
@@ -118,4 +253,10 @@ impl Drop for Pipeline {
                 .destroy_pipeline(self.graphic_pipeline, None);
         }
     }
-}
\ No newline at end of file
+}
+
+impl Drop for Pipeline {
+    fn drop(&mut self) {
+        self.destroy();
+    }
+}
@@ -1,119 +1,324 @@
+use crate::renderer::render_graph::RenderGraph;
 use ash::vk;
-use std::default::Default;
+
+/// How a colour attachment combines the fragment with what is already there.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum BlendMode {
+    Opaque,
+    Alpha,
+    Additive,
+    /// Writes no colour at all, for passes that only test depth.
+    Masked,
+}
+impl BlendMode {
+    pub fn attachment_state(&self) -> vk::PipelineColorBlendAttachmentState {
+        let (blend_enable, src_color_blend_factor, dst_color_blend_factor) = match self {
+            BlendMode::Opaque | BlendMode::Masked => {
+                (vk::FALSE, vk::BlendFactor::ONE, vk::BlendFactor::ZERO)
+            }
+            BlendMode::Alpha => (
+                vk::TRUE,
+                vk::BlendFactor::SRC_ALPHA,
+                vk::BlendFactor::ONE_MINUS_SRC_ALPHA,
+            ),
+            BlendMode::Additive => (vk::TRUE, vk::BlendFactor::SRC_ALPHA, vk::BlendFactor::ONE),
+        };
+        vk::PipelineColorBlendAttachmentState {
+            blend_enable,
+            src_color_blend_factor,
+            dst_color_blend_factor,
+            color_blend_op: vk::BlendOp::ADD,
+            src_alpha_blend_factor: vk::BlendFactor::ONE,
+            dst_alpha_blend_factor: vk::BlendFactor::ONE_MINUS_SRC_ALPHA,
+            alpha_blend_op: vk::BlendOp::ADD,
+            color_write_mask: match self {
+                BlendMode::Masked => vk::ColorComponentFlags::empty(),
+                _ => {
+                    vk::ColorComponentFlags::R
+                        | vk::ColorComponentFlags::G
+                        | vk::ColorComponentFlags::B
+                        | vk::ColorComponentFlags::A
+                }
+            },
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct DepthBias {
+    pub constant_factor: f32,
+    pub clamp: f32,
+    pub slope_factor: f32,
+}
+
+/// The standard ways a single pipeline description gets specialised.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum PipelineVariant {
+    /// Solid geometry with back-face culling and depth writes.
+    Opaque,
+    /// Alpha-tested geometry; the shader discards, so both sides are drawn.
+    Cutout,
+    /// Alpha-blended geometry that tests against but does not write depth.
+    Translucent,
+    /// Translucent, with both sides drawn so the surface is also seen from
+    /// under water. Shaded with the water shader.
+    Water,
+    /// Triangle edges only, for debugging meshes.
+    Wireframe,
+    /// Line lists one pixel wide, for debug shapes.
+    Lines,
+    /// Depth with no colour attachments and a depth bias, for shadow maps.
+    /// Both sides are drawn so plants cast shadows.
+    DepthOnly,
+    /// Drawn on the far plane after the opaque world, only where nothing
+    /// else was; writes no depth and draws both sides.
+    Background,
+    /// One triangle over a whole pass without a depth attachment, for
+    /// post-processing.
+    FullScreen,
+}
+
+/// A plain-data description of a graphics pipeline. Nothing in here points
+/// into itself, so it can be cloned and moved freely; the Vulkan create
+/// infos are assembled from it when the pipeline is built.
+#[derive(Debug, Clone)]
 pub struct PipelineConfig {
-    pub viewport_state: vk::PipelineViewportStateCreateInfo,
-    pub input_assembly_state: vk::PipelineInputAssemblyStateCreateInfo,
-    pub rasterization_state: vk::PipelineRasterizationStateCreateInfo,
-    pub multisample_state: vk::PipelineMultisampleStateCreateInfo,
-    pub color_blend_state: vk::PipelineColorBlendStateCreateInfo,
-    color_blend_attachment: vk::PipelineColorBlendAttachmentState,
-    pub depth_stencil_state: vk::PipelineDepthStencilStateCreateInfo,
-    pub dynamic_state: vk::PipelineDynamicStateCreateInfo,
-    pub dynamic_state_enables: Vec<vk::DynamicState>,
+    pub topology: vk::PrimitiveTopology,
+    pub polygon_mode: vk::PolygonMode,
+    pub cull_mode: vk::CullModeFlags,
+    pub front_face: vk::FrontFace,
+    pub line_width: f32,
+    pub depth_test: bool,
+    pub depth_write: bool,
+    pub depth_compare_op: vk::CompareOp,
+    pub depth_bias: Option<DepthBias>,
+    pub color_attachments: Vec<BlendMode>,
+    pub samples: vk::SampleCountFlags,
+    pub dynamic_states: Vec<vk::DynamicState>,
     pub pipeline_layout: vk::PipelineLayout,
     pub render_pass: vk::RenderPass,
     pub subpass: u32,
 }
 impl PipelineConfig {
-    pub fn init(&mut self) {
-        self.viewport_state = vk::PipelineViewportStateCreateInfo::builder()
-            .viewport_count(1)
-            .scissor_count(1)
-            .build();
-        self.input_assembly_state = vk::PipelineInputAssemblyStateCreateInfo::builder()
-            .topology(vk::PrimitiveTopology::TRIANGLE_LIST)
+    /// An opaque triangle-list pipeline drawing both sides, with one colour
+    /// attachment, depth testing and a dynamic viewport and scissor. Every
+    /// variant sets its own cull mode.
+    pub fn new(render_pass: vk::RenderPass, pipeline_layout: vk::PipelineLayout) -> Self {
+        PipelineConfig {
+            topology: vk::PrimitiveTopology::TRIANGLE_LIST,
+            polygon_mode: vk::PolygonMode::FILL,
+            cull_mode: vk::CullModeFlags::NONE,
+            front_face: vk::FrontFace::COUNTER_CLOCKWISE,
+            line_width: 1.0,
+            depth_test: true,
+            depth_write: true,
+            depth_compare_op: vk::CompareOp::LESS,
+            depth_bias: None,
+            color_attachments: vec![BlendMode::Opaque],
+            samples: vk::SampleCountFlags::TYPE_1,
+            dynamic_states: vec![vk::DynamicState::VIEWPORT, vk::DynamicState::SCISSOR],
+            pipeline_layout,
+            render_pass,
+            subpass: 0,
+        }
+    }
+    /// `new` for a pipeline drawing in `pass` of `render_graph`, with the
+    /// pass's sample count.
+    pub fn for_pass(
+        render_graph: &RenderGraph,
+        pass: &str,
+        pipeline_layout: vk::PipelineLayout,
+    ) -> Self {
+        let render_pass = render_graph
+            .render_pass(pass)
+            .unwrap_or_else(|| panic!("No render pass named {}", pass));
+        PipelineConfig::new(render_pass, pipeline_layout).samples(render_graph.samples(pass))
+    }
+    pub fn topology(mut self, topology: vk::PrimitiveTopology) -> Self {
+        self.topology = topology;
+        self
+    }
+    pub fn polygon_mode(mut self, polygon_mode: vk::PolygonMode) -> Self {
+        self.polygon_mode = polygon_mode;
+        self
+    }
+    pub fn cull_mode(mut self, cull_mode: vk::CullModeFlags) -> Self {
+        self.cull_mode = cull_mode;
+        self
+    }
+    pub fn line_width(mut self, line_width: f32) -> Self {
+        self.line_width = line_width;
+        self
+    }
+    pub fn depth_test(mut self, depth_test: bool) -> Self {
+        self.depth_test = depth_test;
+        self
+    }
+    pub fn depth_write(mut self, depth_write: bool) -> Self {
+        self.depth_write = depth_write;
+        self
+    }
+    pub fn depth_compare_op(mut self, depth_compare_op: vk::CompareOp) -> Self {
+        self.depth_compare_op = depth_compare_op;
+        self
+    }
+    pub fn depth_bias(mut self, constant_factor: f32, slope_factor: f32, clamp: f32) -> Self {
+        self.depth_bias = Some(DepthBias {
+            constant_factor,
+            clamp,
+            slope_factor,
+        });
+        self
+    }
+    /// Sets the blend mode of every colour attachment, one entry each.
+    pub fn color_attachments(mut self, color_attachments: &[BlendMode]) -> Self {
+        self.color_attachments = color_attachments.to_vec();
+        self
+    }
+    pub fn blend_mode(mut self, blend_mode: BlendMode) -> Self {
+        for attachment in self.color_attachments.iter_mut() {
+            *attachment = blend_mode;
+        }
+        self
+    }
+    pub fn samples(mut self, samples: vk::SampleCountFlags) -> Self {
+        self.samples = samples;
+        self
+    }
+    /// Derives one of the standard variants from this description, keeping
+    /// its render pass, layout, attachments and sample count.
+    pub fn variant(&self, variant: PipelineVariant) -> Self {
+        let config = self.clone();
+        match variant {
+            PipelineVariant::Opaque => config.cull_mode(vk::CullModeFlags::BACK),
+            PipelineVariant::Cutout => config.cull_mode(vk::CullModeFlags::NONE),
+            PipelineVariant::Translucent => config
+                .depth_write(false)
+                .blend_mode(BlendMode::Alpha)
+                .cull_mode(vk::CullModeFlags::BACK),
+            PipelineVariant::Water => config
+                .variant(PipelineVariant::Translucent)
+                .cull_mode(vk::CullModeFlags::NONE),
+            PipelineVariant::Wireframe => config
+                .polygon_mode(vk::PolygonMode::LINE)
+                .cull_mode(vk::CullModeFlags::NONE),
+            PipelineVariant::Lines => config
+                .topology(vk::PrimitiveTopology::LINE_LIST)
+                .cull_mode(vk::CullModeFlags::NONE)
+                .line_width(1.0),
+            PipelineVariant::DepthOnly => config
+                .color_attachments(&[])
+                .cull_mode(vk::CullModeFlags::NONE)
+                .depth_bias(1.25, 1.75, 0.0),
+            PipelineVariant::Background => config
+                .depth_write(false)
+                .depth_compare_op(vk::CompareOp::LESS_OR_EQUAL)
+                .cull_mode(vk::CullModeFlags::NONE),
+            PipelineVariant::FullScreen => config
+                .depth_test(false)
+                .depth_write(false)
+                .cull_mode(vk::CullModeFlags::NONE),
+        }
+    }
+
+    pub fn input_assembly_state(&self) -> vk::PipelineInputAssemblyStateCreateInfo {
+        vk::PipelineInputAssemblyStateCreateInfo::builder()
+            .topology(self.topology)
             .primitive_restart_enable(false)
-            .build();
-        self.rasterization_state = vk::PipelineRasterizationStateCreateInfo::builder()
+            .build()
+    }
+    pub fn rasterization_state(&self) -> vk::PipelineRasterizationStateCreateInfo {
+        let depth_bias = self.depth_bias.unwrap_or(DepthBias {
+            constant_factor: 0.0,
+            clamp: 0.0,
+            slope_factor: 0.0,
+        });
+        vk::PipelineRasterizationStateCreateInfo::builder()
             .depth_clamp_enable(false)
             .rasterizer_discard_enable(false)
-            .polygon_mode(vk::PolygonMode::FILL)
-            .line_width(1.0)
-            .cull_mode(vk::CullModeFlags::NONE)
-            .front_face(vk::FrontFace::CLOCKWISE)
-            .depth_bias_enable(true)
-            .depth_bias_constant_factor(0.0)
-            .depth_bias_clamp(0.0)
-            .depth_bias_slope_factor(0.0)
-            .build();
-        self.multisample_state = vk::PipelineMultisampleStateCreateInfo::builder()
-            .rasterization_samples(vk::SampleCountFlags::TYPE_1)
+            .polygon_mode(self.polygon_mode)
+            .line_width(self.line_width)
+            .cull_mode(self.cull_mode)
+            .front_face(self.front_face)
+            .depth_bias_enable(self.depth_bias.is_some())
+            .depth_bias_constant_factor(depth_bias.constant_factor)
+            .depth_bias_clamp(depth_bias.clamp)
+            .depth_bias_slope_factor(depth_bias.slope_factor)
+            .build()
+    }
+    pub fn multisample_state(&self) -> vk::PipelineMultisampleStateCreateInfo {
+        vk::PipelineMultisampleStateCreateInfo::builder()
+            .rasterization_samples(self.samples)
             .sample_shading_enable(false)
             .min_sample_shading(1.0)
             .alpha_to_coverage_enable(false)
             .alpha_to_one_enable(false)
+            .build()
+    }
+    pub fn color_blend_attachments(&self) -> Vec<vk::PipelineColorBlendAttachmentState> {
+        self.color_attachments
+            .iter()
+            .map(|blend_mode| blend_mode.attachment_state())
+            .collect()
+    }
+    pub fn depth_stencil_state(&self) -> vk::PipelineDepthStencilStateCreateInfo {
+        let stencil_op = vk::StencilOpState::builder()
+            .fail_op(vk::StencilOp::KEEP)
+            .pass_op(vk::StencilOp::KEEP)
+            .depth_fail_op(vk::StencilOp::KEEP)
+            .compare_op(vk::CompareOp::ALWAYS)
+            .compare_mask(0)
+            .write_mask(0)
+            .reference(0)
             .build();
-        self.color_blend_attachment = vk::PipelineColorBlendAttachmentState {
-            blend_enable: vk::FALSE,
-            src_color_blend_factor: vk::BlendFactor::ONE,
-            dst_color_blend_factor: vk::BlendFactor::ZERO,
-            color_blend_op: vk::BlendOp::ADD,
-            src_alpha_blend_factor: vk::BlendFactor::ONE,
-            dst_alpha_blend_factor: vk::BlendFactor::ZERO,
-            alpha_blend_op: vk::BlendOp::ADD,
-            color_write_mask: vk::ColorComponentFlags::R
-                | vk::ColorComponentFlags::G
-                | vk::ColorComponentFlags::B
-                | vk::ColorComponentFlags::A,
-        };
-        self.color_blend_state = vk::PipelineColorBlendStateCreateInfo {
-            logic_op_enable: vk::FALSE,
-            logic_op: vk::LogicOp::COPY,
-            attachment_count: 1,
-            p_attachments: &self.color_blend_attachment,
-            blend_constants: [0.0, 0.0, 0.0, 0.0],
-            ..Default::default()
-        };
-        self.depth_stencil_state = vk::PipelineDepthStencilStateCreateInfo::builder()
-            .depth_test_enable(true)
-            .depth_write_enable(true)
-            .depth_compare_op(vk::CompareOp::LESS)
+        vk::PipelineDepthStencilStateCreateInfo::builder()
+            .depth_test_enable(self.depth_test)
+            .depth_write_enable(self.depth_write)
+            .depth_compare_op(self.depth_compare_op)
             .depth_bounds_test_enable(false)
             .stencil_test_enable(false)
-            .front(
-                vk::StencilOpState::builder()
-                    .fail_op(vk::StencilOp::KEEP)
-                    .pass_op(vk::StencilOp::KEEP)
-                    .depth_fail_op(vk::StencilOp::KEEP)
-                    .compare_op(vk::CompareOp::ALWAYS)
-                    .compare_mask(0)
-                    .write_mask(0)
-                    .reference(0)
-                    .build(),
-            )
-            .back(
-                vk::StencilOpState::builder()
-                    .fail_op(vk::StencilOp::KEEP)
-                    .pass_op(vk::StencilOp::KEEP)
-                    .depth_fail_op(vk::StencilOp::KEEP)
-                    .compare_op(vk::CompareOp::ALWAYS)
-                    .compare_mask(0)
-                    .write_mask(0)
-                    .reference(0)
-                    .build(),
-            )
-            .build();
-        self.dynamic_state_enables = vec![vk::DynamicState::VIEWPORT, vk::DynamicState::SCISSOR];
-        self.dynamic_state = vk::PipelineDynamicStateCreateInfo::builder()
-            .dynamic_states(&[vk::DynamicState::VIEWPORT, vk::DynamicState::SCISSOR])
-            .build();
+            .front(stencil_op)
+            .back(stencil_op)
+            .build()
     }
 }
-impl Default for PipelineConfig {
-    fn default() -> Self {
-        PipelineConfig {
-            viewport_state: vk::PipelineViewportStateCreateInfo::default(),
-            input_assembly_state: vk::PipelineInputAssemblyStateCreateInfo::default(),
-            rasterization_state: vk::PipelineRasterizationStateCreateInfo::default(),
-            multisample_state: vk::PipelineMultisampleStateCreateInfo::default(),
-            color_blend_state: vk::PipelineColorBlendStateCreateInfo::default(),
-            color_blend_attachment: vk::PipelineColorBlendAttachmentState::default(),
-            depth_stencil_state: vk::PipelineDepthStencilStateCreateInfo::default(),
-            dynamic_state: vk::PipelineDynamicStateCreateInfo::default(),
-            dynamic_state_enables: Vec::new(),
-            pipeline_layout: vk::PipelineLayout::default(),
-            render_pass: vk::RenderPass::null(),
-            subpass: 0,
-        }
+
+#[cfg(test)]
+mod pipeline_config_tests {
+    use super::*;
+    #[test]
+    fn variants_share_one_description() {
+        let opaque = PipelineConfig::new(vk::RenderPass::null(), vk::PipelineLayout::null())
+            .color_attachments(&[BlendMode::Opaque, BlendMode::Opaque])
+            .samples(vk::SampleCountFlags::TYPE_4);
+        assert_eq!(opaque.cull_mode, vk::CullModeFlags::NONE);
+        assert_eq!(
+            opaque.variant(PipelineVariant::Opaque).cull_mode,
+            vk::CullModeFlags::BACK
+        );
+        let translucent = opaque.variant(PipelineVariant::Translucent);
+        assert!(!translucent.depth_write && translucent.depth_test);
+        assert_eq!(translucent.color_attachments, vec![BlendMode::Alpha; 2]);
+        assert_eq!(translucent.samples, vk::SampleCountFlags::TYPE_4);
+        let water = opaque.variant(PipelineVariant::Water);
+        assert!(!water.depth_write && water.cull_mode == vk::CullModeFlags::NONE);
+        assert_eq!(water.color_attachments, translucent.color_attachments);
+        let wireframe = opaque.variant(PipelineVariant::Wireframe);
+        assert_eq!(wireframe.polygon_mode, vk::PolygonMode::LINE);
+        assert_eq!(wireframe.cull_mode, vk::CullModeFlags::NONE);
+        let lines = opaque.variant(PipelineVariant::Lines);
+        assert_eq!(lines.topology, vk::PrimitiveTopology::LINE_LIST);
+        assert_eq!(lines.cull_mode, vk::CullModeFlags::NONE);
+        assert_eq!(lines.line_width, 1.0);
+        assert_eq!(opaque.variant(PipelineVariant::Cutout).cull_mode, vk::CullModeFlags::NONE);
+        let depth_only = opaque.variant(PipelineVariant::DepthOnly);
+        assert!(depth_only.color_attachments.is_empty());
+        assert!(depth_only.depth_write && depth_only.depth_bias.is_some());
+        let background = opaque.variant(PipelineVariant::Background);
+        assert!(background.depth_test && !background.depth_write);
+        assert_eq!(background.depth_compare_op, vk::CompareOp::LESS_OR_EQUAL);
+        let full_screen = opaque.variant(PipelineVariant::FullScreen);
+        assert!(!full_screen.depth_test && !full_screen.depth_write);
+        assert_eq!(full_screen.cull_mode, vk::CullModeFlags::NONE);
     }
 }
@@ -0,0 +1,197 @@
+use ash::vk;
+use naga::back::spv;
+use naga::front::glsl;
+use naga::valid::{Capabilities, ValidationFlags, Validator};
+use std::path::{Path, PathBuf};
+use std::time::{Duration, Instant, SystemTime};
+
+/// A GLSL shader embedded in the binary, so the game does not depend on the
+/// working directory or on a prebuilt `.spv` file.
+#[derive(Debug, Clone, Copy)]
+pub struct ShaderSource {
+    pub path: &'static str,
+    pub stage: vk::ShaderStageFlags,
+    pub source: &'static str,
+    /// Where the source lives in the checkout, watched for hot reloading.
+    pub disk_path: &'static str,
+}
+
+macro_rules! shader_source {
+    ($file:literal, $stage:expr) => {
+        ShaderSource {
+            path: concat!("shaders/", $file),
+            stage: $stage,
+            source: include_str!(concat!("../../../shaders/", $file)),
+            disk_path: concat!(env!("CARGO_MANIFEST_DIR"), "/shaders/", $file),
+        }
+    };
+}
+
+pub const MAIN_VERT: ShaderSource = shader_source!("shader.vert", vk::ShaderStageFlags::VERTEX);
+pub const MAIN_FRAG: ShaderSource = shader_source!("shader.frag", vk::ShaderStageFlags::FRAGMENT);
+pub const CUTOUT_FRAG: ShaderSource = shader_source!("cutout.frag", vk::ShaderStageFlags::FRAGMENT);
+pub const WATER_FRAG: ShaderSource = shader_source!("water.frag", vk::ShaderStageFlags::FRAGMENT);
+pub const DEBUG_VIEW_FRAG: ShaderSource =
+    shader_source!("debug_view.frag", vk::ShaderStageFlags::FRAGMENT);
+pub const OCCLUSION_BOX_VERT: ShaderSource =
+    shader_source!("occlusion_box.vert", vk::ShaderStageFlags::VERTEX);
+pub const OCCLUSION_BOX_FRAG: ShaderSource =
+    shader_source!("occlusion_box.frag", vk::ShaderStageFlags::FRAGMENT);
+pub const ENTITY_VERT: ShaderSource = shader_source!("entity.vert", vk::ShaderStageFlags::VERTEX);
+pub const ENTITY_FRAG: ShaderSource = shader_source!("entity.frag", vk::ShaderStageFlags::FRAGMENT);
+pub const DEBUG_LINES_VERT: ShaderSource =
+    shader_source!("debug_lines.vert", vk::ShaderStageFlags::VERTEX);
+pub const DEBUG_LINES_FRAG: ShaderSource =
+    shader_source!("debug_lines.frag", vk::ShaderStageFlags::FRAGMENT);
+pub const SHADOW_VERT: ShaderSource = shader_source!("shadow.vert", vk::ShaderStageFlags::VERTEX);
+pub const SHADOW_FRAG: ShaderSource = shader_source!("shadow.frag", vk::ShaderStageFlags::FRAGMENT);
+pub const SKY_VERT: ShaderSource = shader_source!("sky.vert", vk::ShaderStageFlags::VERTEX);
+pub const SKY_FRAG: ShaderSource = shader_source!("sky.frag", vk::ShaderStageFlags::FRAGMENT);
+pub const CELESTIAL_VERT: ShaderSource =
+    shader_source!("celestial.vert", vk::ShaderStageFlags::VERTEX);
+pub const CELESTIAL_FRAG: ShaderSource =
+    shader_source!("celestial.frag", vk::ShaderStageFlags::FRAGMENT);
+pub const CLOUD_VERT: ShaderSource = shader_source!("cloud.vert", vk::ShaderStageFlags::VERTEX);
+pub const CLOUD_FRAG: ShaderSource = shader_source!("cloud.frag", vk::ShaderStageFlags::FRAGMENT);
+pub const PARTICLE_VERT: ShaderSource =
+    shader_source!("particle.vert", vk::ShaderStageFlags::VERTEX);
+pub const PARTICLE_FRAG: ShaderSource =
+    shader_source!("particle.frag", vk::ShaderStageFlags::FRAGMENT);
+pub const POST_VERT: ShaderSource = shader_source!("post.vert", vk::ShaderStageFlags::VERTEX);
+pub const POST_COPY_FRAG: ShaderSource =
+    shader_source!("post_copy.frag", vk::ShaderStageFlags::FRAGMENT);
+pub const TONE_MAPPING_FRAG: ShaderSource =
+    shader_source!("tone_mapping.frag", vk::ShaderStageFlags::FRAGMENT);
+pub const GAMMA_FRAG: ShaderSource = shader_source!("gamma.frag", vk::ShaderStageFlags::FRAGMENT);
+pub const FXAA_FRAG: ShaderSource = shader_source!("fxaa.frag", vk::ShaderStageFlags::FRAGMENT);
+pub const VIGNETTE_FRAG: ShaderSource =
+    shader_source!("vignette.frag", vk::ShaderStageFlags::FRAGMENT);
+pub const UNDERWATER_TINT_FRAG: ShaderSource =
+    shader_source!("underwater_tint.frag", vk::ShaderStageFlags::FRAGMENT);
+pub const SSAO_FRAG: ShaderSource = shader_source!("ssao.frag", vk::ShaderStageFlags::FRAGMENT);
+pub const SSAO_BLUR_FRAG: ShaderSource =
+    shader_source!("ssao_blur.frag", vk::ShaderStageFlags::FRAGMENT);
+pub const UI_VERT: ShaderSource = shader_source!("ui.vert", vk::ShaderStageFlags::VERTEX);
+pub const UI_FRAG: ShaderSource = shader_source!("ui.frag", vk::ShaderStageFlags::FRAGMENT);
+pub const CHUNK_CULLING_COMP: ShaderSource =
+    shader_source!("shader.comp", vk::ShaderStageFlags::COMPUTE);
+pub const PARTICLES_COMP: ShaderSource =
+    shader_source!("particles.comp", vk::ShaderStageFlags::COMPUTE);
+
+impl ShaderSource {
+    pub fn compile(&self) -> Result<Vec<u32>, String> {
+        compile_glsl(self.path, self.source, self.stage)
+    }
+    /// Compiles the current file on disk, falling back to the embedded
+    /// source when the checkout is not available.
+    pub fn compile_from_disk(&self) -> Result<Vec<u32>, String> {
+        match std::fs::read_to_string(self.disk_path) {
+            Ok(source) => compile_glsl(self.path, &source, self.stage),
+            Err(_) => self.compile(),
+        }
+    }
+}
+
+const SHADER_POLL_INTERVAL: Duration = Duration::from_millis(500);
+
+/// Polls the modification times of shader sources on disk.
+pub struct ShaderWatcher {
+    files: Vec<(PathBuf, Option<SystemTime>)>,
+    last_poll: Instant,
+}
+impl ShaderWatcher {
+    /// Returns `None` in release builds or when none of the sources exist on
+    /// disk, e.g. when the binary runs outside the checkout.
+    pub fn new(shaders: &[ShaderSource]) -> Option<Self> {
+        if !cfg!(debug_assertions) {
+            return None;
+        }
+        let files: Vec<(PathBuf, Option<SystemTime>)> = shaders
+            .iter()
+            .map(|shader| PathBuf::from(shader.disk_path))
+            .filter(|path| path.exists())
+            .map(|path| {
+                let modified = modified_time(&path);
+                (path, modified)
+            })
+            .collect();
+        if files.is_empty() {
+            return None;
+        }
+        Some(ShaderWatcher {
+            files,
+            last_poll: Instant::now(),
+        })
+    }
+    /// Returns true once for every batch of changes.
+    pub fn poll(&mut self) -> bool {
+        if self.last_poll.elapsed() < SHADER_POLL_INTERVAL {
+            return false;
+        }
+        self.last_poll = Instant::now();
+        let mut changed = false;
+        for (path, last_modified) in self.files.iter_mut() {
+            let modified = modified_time(path);
+            if modified != *last_modified {
+                *last_modified = modified;
+                changed = true;
+            }
+        }
+        changed
+    }
+}
+fn modified_time(path: &Path) -> Option<SystemTime> {
+    std::fs::metadata(path)
+        .and_then(|metadata| metadata.modified())
+        .ok()
+}
+
+/// Compiles GLSL to SPIR-V words. Errors are formatted as
+/// `path:line:column: message` so they can be printed as they are.
+pub fn compile_glsl(path: &str, source: &str, stage: vk::ShaderStageFlags) -> Result<Vec<u32>, String> {
+    let naga_stage = match stage {
+        vk::ShaderStageFlags::VERTEX => naga::ShaderStage::Vertex,
+        vk::ShaderStageFlags::FRAGMENT => naga::ShaderStage::Fragment,
+        vk::ShaderStageFlags::COMPUTE => naga::ShaderStage::Compute,
+        _ => return Err(format!("{}: unsupported shader stage {:?}", path, stage)),
+    };
+    let module = glsl::Parser::default()
+        .parse(&glsl::Options::from(naga_stage), source)
+        .map_err(|errors| {
+            errors
+                .iter()
+                .map(|error| {
+                    let location = error.meta.location(source);
+                    format!(
+                        "{}:{}:{}: {}",
+                        path, location.line_number, location.line_position, error.kind
+                    )
+                })
+                .collect::<Vec<String>>()
+                .join("\n")
+        })?;
+    let module_info = Validator::new(ValidationFlags::all(), Capabilities::all())
+        .validate(&module)
+        .map_err(|error| error.emit_to_string_with_path(source, path))?;
+    spv::write_vec(&module, &module_info, &spv::Options::default(), None)
+        .map_err(|error| format!("{}: {}", path, error))
+}
+
+#[cfg(test)]
+mod shader_tests {
+    use super::*;
+    #[test]
+    fn embedded_shaders_compile() {
+        for shader in [MAIN_VERT, MAIN_FRAG, CUTOUT_FRAG, WATER_FRAG, DEBUG_VIEW_FRAG, OCCLUSION_BOX_VERT, OCCLUSION_BOX_FRAG, ENTITY_VERT, ENTITY_FRAG, DEBUG_LINES_VERT, DEBUG_LINES_FRAG, SHADOW_VERT, SHADOW_FRAG, SKY_VERT, SKY_FRAG, CELESTIAL_VERT, CELESTIAL_FRAG, CLOUD_VERT, CLOUD_FRAG, PARTICLE_VERT, PARTICLE_FRAG, POST_VERT, POST_COPY_FRAG, TONE_MAPPING_FRAG, GAMMA_FRAG, FXAA_FRAG, VIGNETTE_FRAG, UNDERWATER_TINT_FRAG, SSAO_FRAG, SSAO_BLUR_FRAG, UI_VERT, UI_FRAG, CHUNK_CULLING_COMP, PARTICLES_COMP] {
+            if let Err(error) = shader.compile() {
+                panic!("{}", error);
+            }
+        }
+    }
+    #[test]
+    fn errors_point_at_the_source_line() {
+        let source = "#version 450\nvoid main(){\n    undefined_value = 1.0;\n}\n";
+        let error = compile_glsl("broken.frag", source, vk::ShaderStageFlags::FRAGMENT).unwrap_err();
+        assert!(error.starts_with("broken.frag:3:"), "{}", error);
+    }
+}
@@ -0,0 +1,262 @@
+use super::pipeline::shader::{
+    ShaderSource, FXAA_FRAG, GAMMA_FRAG, POST_COPY_FRAG, POST_VERT, TONE_MAPPING_FRAG,
+    UNDERWATER_TINT_FRAG, VIGNETTE_FRAG,
+};
+use super::pipeline::{Pipeline, PipelineConfig, PipelineVariant};
+use super::{FogMode, FrameInfo, RenderStage, RenderSystem};
+use crate::core::Core;
+use crate::game::settings::{PostEffect, Settings};
+use crate::memory::descriptor::Descriptor;
+use crate::renderer::render_graph::RenderGraph;
+use ash::vk;
+use nalgebra_glm as glm;
+use std::any::Any;
+use std::mem;
+use std::rc::Rc;
+
+/// The format the world is drawn in and the post-process steps hand on to
+/// each other. Floating point, so light brighter than the screen can show
+/// survives until tone mapping.
+pub const SCENE_FORMAT: vk::Format = vk::Format::R16G16B16A16_SFLOAT;
+/// The gamma the display decodes with.
+const DISPLAY_GAMMA: f32 = 2.2;
+/// How much darker the very corners of the screen get.
+const VIGNETTE_STRENGTH: f32 = 0.35;
+/// How far colours are filtered towards the water's hue under water.
+const UNDERWATER_TINT_STRENGTH: f32 = 0.6;
+
+/// The render graph pass `effect` draws in.
+pub fn pass_name(effect: PostEffect) -> &'static str {
+    match effect {
+        PostEffect::ToneMapping => "tone_mapping",
+        PostEffect::Gamma => "gamma",
+        PostEffect::Fxaa => "fxaa",
+        PostEffect::Vignette => "vignette",
+        PostEffect::UnderwaterTint => "underwater_tint",
+    }
+}
+
+fn fragment_shader(effect: PostEffect) -> ShaderSource {
+    match effect {
+        PostEffect::ToneMapping => TONE_MAPPING_FRAG,
+        PostEffect::Gamma => GAMMA_FRAG,
+        PostEffect::Fxaa => FXAA_FRAG,
+        PostEffect::Vignette => VIGNETTE_FRAG,
+        PostEffect::UnderwaterTint => UNDERWATER_TINT_FRAG,
+    }
+}
+
+/// Laid out as `Push` in the post-process shaders.
+#[derive(Debug, Clone, Copy)]
+#[repr(C)]
+struct PostPush {
+    /// What each effect is tuned by, see its shader.
+    params: glm::Vec4,
+    tint: glm::Vec4,
+}
+
+/// One step of the post-process chain: a full-screen pass that samples the
+/// step before it, or the scene, and writes the input of the next, or the
+/// swap chain image. A switched-off step copies its input through, so the
+/// graph's passes and images stay the same whatever is enabled.
+pub struct PostProcessSystem {
+    core: Rc<Core>,
+    effect: PostEffect,
+    descriptor: Descriptor,
+    sampler: vk::Sampler,
+    pipeline_layout: vk::PipelineLayout,
+    pipeline: Pipeline,
+    copy_pipeline: Pipeline,
+    enabled: bool,
+    exposure: f32,
+}
+impl PostProcessSystem {
+    pub fn new(
+        core: Rc<Core>,
+        render_graph: &RenderGraph,
+        effect: PostEffect,
+        settings: &Settings,
+    ) -> Self {
+        let pass = pass_name(effect);
+        let descriptor = Descriptor::new(
+            core.clone(),
+            &[
+                vk::DescriptorSetLayoutBinding::builder()
+                    .binding(0)
+                    .descriptor_type(vk::DescriptorType::SAMPLED_IMAGE)
+                    .descriptor_count(1)
+                    .stage_flags(vk::ShaderStageFlags::FRAGMENT)
+                    .build(),
+                vk::DescriptorSetLayoutBinding::builder()
+                    .binding(1)
+                    .descriptor_type(vk::DescriptorType::SAMPLER)
+                    .descriptor_count(1)
+                    .stage_flags(vk::ShaderStageFlags::FRAGMENT)
+                    .build(),
+            ],
+        );
+        let sampler_info = vk::SamplerCreateInfo::builder()
+            .mag_filter(vk::Filter::LINEAR)
+            .min_filter(vk::Filter::LINEAR)
+            .mipmap_mode(vk::SamplerMipmapMode::NEAREST)
+            .address_mode_u(vk::SamplerAddressMode::CLAMP_TO_EDGE)
+            .address_mode_v(vk::SamplerAddressMode::CLAMP_TO_EDGE)
+            .address_mode_w(vk::SamplerAddressMode::CLAMP_TO_EDGE)
+            .min_lod(0.0)
+            .max_lod(0.0)
+            .unnormalized_coordinates(false)
+            .build();
+        let sampler = unsafe {
+            core.logical_device
+                .create_sampler(&sampler_info, None)
+                .expect("Failed to create post-process sampler")
+        };
+        descriptor.write_image(
+            1,
+            vk::DescriptorType::SAMPLER,
+            vk::ImageView::null(),
+            sampler,
+            vk::ImageLayout::UNDEFINED,
+        );
+        let set_layouts = [descriptor.set_layout];
+        let push_constant_ranges = [vk::PushConstantRange::builder()
+            .stage_flags(vk::ShaderStageFlags::FRAGMENT)
+            .offset(0)
+            .size(mem::size_of::<PostPush>() as u32)
+            .build()];
+        let pipeline_layout_info = vk::PipelineLayoutCreateInfo::builder()
+            .set_layouts(&set_layouts)
+            .push_constant_ranges(&push_constant_ranges)
+            .build();
+        let pipeline_layout = unsafe {
+            core.logical_device
+                .create_pipeline_layout(&pipeline_layout_info, None)
+                .expect("Failed to create pipeline layout")
+        };
+        let config = PipelineConfig::for_pass(render_graph, pass, pipeline_layout)
+            .variant(PipelineVariant::FullScreen);
+        let mut pipeline = Pipeline::new(core.clone());
+        pipeline.create_graphic_pipeline(
+            &POST_VERT,
+            &fragment_shader(effect),
+            config.clone(),
+            &[],
+            &[],
+        );
+        let mut copy_pipeline = Pipeline::new(core.clone());
+        copy_pipeline.create_graphic_pipeline(&POST_VERT, &POST_COPY_FRAG, config, &[], &[]);
+        let mut post_process_system = PostProcessSystem {
+            core,
+            effect,
+            descriptor,
+            sampler,
+            pipeline_layout,
+            pipeline,
+            copy_pipeline,
+            enabled: true,
+            exposure: 1.0,
+        };
+        post_process_system.write_input(render_graph);
+        post_process_system.apply_settings(settings);
+        post_process_system
+    }
+    pub fn apply_settings(&mut self, settings: &Settings) {
+        self.enabled = settings.post_effect(self.effect);
+        self.exposure = settings.exposure;
+    }
+    fn write_input(&self, render_graph: &RenderGraph) {
+        let input = render_graph.sampled_images(pass_name(self.effect))[0];
+        self.descriptor.write_image(
+            0,
+            vk::DescriptorType::SAMPLED_IMAGE,
+            render_graph.image_view(input),
+            vk::Sampler::null(),
+            vk::ImageLayout::SHADER_READ_ONLY_OPTIMAL,
+        );
+    }
+    /// Whether the effect changes anything this frame.
+    fn is_active(&self, frame: &FrameInfo) -> bool {
+        match self.effect {
+            PostEffect::UnderwaterTint => {
+                self.enabled && matches!(frame.fog.mode, FogMode::Exponential { .. })
+            }
+            _ => self.enabled,
+        }
+    }
+}
+impl RenderSystem for PostProcessSystem {
+    fn stage(&self) -> RenderStage {
+        RenderStage::PostProcess
+    }
+    fn pass(&self) -> &'static str {
+        pass_name(self.effect)
+    }
+    fn render(&mut self, frame: &mut FrameInfo) {
+        let pipeline = if self.is_active(frame) {
+            &self.pipeline
+        } else {
+            &self.copy_pipeline
+        };
+        let params = match self.effect {
+            PostEffect::ToneMapping => glm::vec4(self.exposure, 0.0, 0.0, 0.0),
+            PostEffect::Gamma => glm::vec4(DISPLAY_GAMMA, 0.0, 0.0, 0.0),
+            PostEffect::Fxaa => glm::vec4(0.0, 0.0, 0.0, 0.0),
+            PostEffect::Vignette => glm::vec4(VIGNETTE_STRENGTH, 0.0, 0.0, 0.0),
+            PostEffect::UnderwaterTint => glm::vec4(UNDERWATER_TINT_STRENGTH, frame.time, 0.0, 0.0),
+        };
+        let push = PostPush {
+            params,
+            tint: frame.fog.color.push(1.0),
+        };
+        let device = &self.core.logical_device;
+        unsafe {
+            device.cmd_bind_pipeline(
+                frame.command_buffer,
+                vk::PipelineBindPoint::GRAPHICS,
+                pipeline.graphic_pipeline,
+            );
+            device.cmd_bind_descriptor_sets(
+                frame.command_buffer,
+                vk::PipelineBindPoint::GRAPHICS,
+                self.pipeline_layout,
+                0,
+                &[self.descriptor.set],
+                &[],
+            );
+            device.cmd_push_constants(
+                frame.command_buffer,
+                self.pipeline_layout,
+                vk::ShaderStageFlags::FRAGMENT,
+                0,
+                std::slice::from_raw_parts(
+                    &push as *const PostPush as *const u8,
+                    mem::size_of::<PostPush>(),
+                ),
+            );
+            device.cmd_draw(frame.command_buffer, 3, 1, 0, 0);
+        }
+    }
+    fn render_graph_changed(&mut self, render_graph: &RenderGraph) {
+        let pass = pass_name(self.effect);
+        self.pipeline.rebuild_for_pass(render_graph, pass);
+        self.copy_pipeline.rebuild_for_pass(render_graph, pass);
+        self.write_input(render_graph);
+    }
+    fn reload_shaders(&mut self) {
+        self.pipeline.reload_if_changed();
+        self.copy_pipeline.reload_if_changed();
+    }
+    fn as_any_mut(&mut self) -> &mut dyn Any {
+        self
+    }
+}
+impl Drop for PostProcessSystem {
+    fn drop(&mut self) {
+        unsafe {
+            self.core.logical_device.destroy_sampler(self.sampler, None);
+            self.core
+                .logical_device
+                .destroy_pipeline_layout(self.pipeline_layout, None);
+        }
+    }
+}
@@ -0,0 +1,379 @@
+use super::ssao_system::SSAO_BLUR_PASS;
+use super::{CameraInfo, Fog, FogMode, Lighting};
+use crate::core::Core;
+use crate::memory::descriptor::Descriptor;
+use crate::memory::{AllocationType, Memory};
+use crate::renderer::render_graph::{RenderGraph, SHADOW_PASS};
+use ash::vk;
+use nalgebra_glm as glm;
+use std::mem;
+use std::rc::Rc;
+
+/// Number of shadow cascades, laid side by side in one depth atlas.
+pub const CASCADE_COUNT: usize = 3;
+/// Width and height of one cascade in texels.
+pub const CASCADE_SIZE: u32 = 1024;
+pub const SHADOW_MAP_FORMAT: vk::Format = vk::Format::D32_SFLOAT;
+/// How far from the camera shadows are drawn.
+pub const SHADOW_DISTANCE: f32 = 160.0;
+/// Blend between logarithmic (1) and even (0) cascade splits.
+const SPLIT_LAMBDA: f32 = 0.75;
+/// How far past a cascade, towards the sun, casters are still drawn.
+const CASTER_MARGIN: f32 = 96.0;
+
+/// The far view-space depth of each cascade, from `near` out to `far`.
+pub fn cascade_splits(near: f32, far: f32) -> [f32; CASCADE_COUNT] {
+    let mut splits = [0.0; CASCADE_COUNT];
+    for (cascade, split) in splits.iter_mut().enumerate() {
+        let t = (cascade + 1) as f32 / CASCADE_COUNT as f32;
+        let logarithmic = near * (far / near).powf(t);
+        let even = near + (far - near) * t;
+        *split = SPLIT_LAMBDA * logarithmic + (1.0 - SPLIT_LAMBDA) * even;
+    }
+    splits
+}
+
+/// The world-space corners of the slice of the camera frustum between the
+/// view depths `near` and `far`.
+fn frustum_slice_corners(camera: &CameraInfo, near: f32, far: f32) -> [glm::Vec3; 8] {
+    let inverse = glm::inverse(&camera.proj_view);
+    // depth of a view distance under perspective_lh_zo
+    let ndc_depth = |distance: f32| {
+        camera.far * (distance - camera.near) / (distance * (camera.far - camera.near))
+    };
+    let mut corners = [glm::vec3(0.0, 0.0, 0.0); 8];
+    for (index, corner) in corners.iter_mut().enumerate() {
+        let x = if index & 1 == 0 { -1.0 } else { 1.0 };
+        let y = if index & 2 == 0 { -1.0 } else { 1.0 };
+        let z = if index & 4 == 0 {
+            ndc_depth(near)
+        } else {
+            ndc_depth(far)
+        };
+        let world = inverse * glm::vec4(x, y, z, 1.0);
+        *corner = world.xyz() / world.w;
+    }
+    corners
+}
+
+/// An orthographic light matrix looking along `-sun_direction` that covers
+/// the bounding sphere of `corners`. The sphere keeps the cascade the same
+/// size as the camera turns, and snapping it to whole texels keeps shadow
+/// edges from shimmering as the camera moves.
+pub fn cascade_matrix(corners: &[glm::Vec3; 8], sun_direction: &glm::Vec3) -> glm::Mat4 {
+    let center = corners
+        .iter()
+        .fold(glm::vec3(0.0, 0.0, 0.0), |sum, corner| sum + corner)
+        / 8.0;
+    let radius = corners
+        .iter()
+        .map(|corner| glm::distance(corner, &center))
+        .fold(0.0_f32, f32::max)
+        .ceil();
+    let up = if sun_direction.y.abs() > 0.99 {
+        glm::vec3(0.0, 0.0, 1.0)
+    } else {
+        glm::vec3(0.0, 1.0, 0.0)
+    };
+    let eye = center + glm::normalize(sun_direction) * (radius + CASTER_MARGIN);
+    let view = glm::look_at_lh(&eye, &center, &up);
+    let mut projection = glm::ortho_lh_zo(
+        -radius,
+        radius,
+        -radius,
+        radius,
+        0.0,
+        2.0 * radius + CASTER_MARGIN,
+    );
+    let half_size = CASCADE_SIZE as f32 / 2.0;
+    let origin = (projection * view * glm::vec4(0.0, 0.0, 0.0, 1.0)).xy() * half_size;
+    let offset = (origin.map(|c| c.round()) - origin) / half_size;
+    projection[(0, 3)] += offset.x;
+    projection[(1, 3)] += offset.y;
+    projection * view
+}
+
+/// What the terrain shaders need to look up shadows, light and fog the
+/// world, laid out as `Shadows` in `shader.frag` (std140).
+#[derive(Debug, Clone, Copy)]
+#[repr(C)]
+pub struct ShadowUniform {
+    pub light_proj_view: [glm::Mat4; CASCADE_COUNT],
+    /// Far view depth of each cascade in `xyz`.
+    pub splits: glm::Vec4,
+    /// Towards the sun, normalised.
+    pub sun_direction: glm::Vec4,
+    pub camera_position: glm::Vec4,
+    pub camera_forward: glm::Vec4,
+    /// `Lighting::ambient` and `Lighting::sunlight` in `xy`.
+    pub light: glm::Vec4,
+    /// `x` is 0 for linear fog from `y` to `z`, 1 for exponential fog of
+    /// density `y`.
+    pub fog: glm::Vec4,
+    pub fog_color: glm::Vec4,
+    /// `FrameInfo::time` in `x`.
+    pub time: glm::Vec4,
+}
+impl ShadowUniform {
+    pub fn new(camera: &CameraInfo, lighting: &Lighting, fog: &Fog, time: f32) -> Self {
+        let sun_direction = &lighting.sun_direction;
+        let splits = cascade_splits(camera.near, SHADOW_DISTANCE);
+        let mut light_proj_view = [glm::Mat4::identity(); CASCADE_COUNT];
+        let mut near = camera.near;
+        for (cascade, far) in splits.iter().enumerate() {
+            let corners = frustum_slice_corners(camera, near, *far);
+            light_proj_view[cascade] = cascade_matrix(&corners, sun_direction);
+            near = *far;
+        }
+        ShadowUniform {
+            light_proj_view,
+            splits: glm::vec4(splits[0], splits[1], splits[2], 0.0),
+            sun_direction: glm::normalize(sun_direction).push(0.0),
+            camera_position: camera.position.push(1.0),
+            camera_forward: camera.forward.push(0.0),
+            light: glm::vec4(lighting.ambient, lighting.sunlight, 0.0, 0.0),
+            fog: match fog.mode {
+                FogMode::Linear { start, end } => glm::vec4(0.0, start, end, 0.0),
+                FogMode::Exponential { density } => glm::vec4(1.0, density, 0.0, 0.0),
+            },
+            fog_color: fog.color.push(1.0),
+            time: glm::vec4(time, 0.0, 0.0, 0.0),
+        }
+    }
+}
+
+/// The cascaded shadow map as the terrain shaders sample it: a uniform
+/// buffer with the light matrices and the depth atlas with a comparison
+/// sampler, in one descriptor set. The set also holds the blurred ambient
+/// occlusion the terrain shaders darken their light by. The images
+/// themselves belong to the render graph.
+pub struct ShadowMap {
+    core: Rc<Core>,
+    pub descriptor: Descriptor,
+    sampler: vk::Sampler,
+    occlusion_sampler: vk::Sampler,
+    uniform_buffer: usize,
+}
+impl ShadowMap {
+    pub fn new(core: Rc<Core>, memory: &mut Memory, render_graph: &RenderGraph) -> Self {
+        let descriptor = Descriptor::new(
+            core.clone(),
+            &[
+                vk::DescriptorSetLayoutBinding::builder()
+                    .binding(0)
+                    .descriptor_type(vk::DescriptorType::UNIFORM_BUFFER)
+                    .descriptor_count(1)
+                    .stage_flags(vk::ShaderStageFlags::FRAGMENT)
+                    .build(),
+                vk::DescriptorSetLayoutBinding::builder()
+                    .binding(1)
+                    .descriptor_type(vk::DescriptorType::SAMPLED_IMAGE)
+                    .descriptor_count(1)
+                    .stage_flags(vk::ShaderStageFlags::FRAGMENT)
+                    .build(),
+                vk::DescriptorSetLayoutBinding::builder()
+                    .binding(2)
+                    .descriptor_type(vk::DescriptorType::SAMPLER)
+                    .descriptor_count(1)
+                    .stage_flags(vk::ShaderStageFlags::FRAGMENT)
+                    .build(),
+                vk::DescriptorSetLayoutBinding::builder()
+                    .binding(3)
+                    .descriptor_type(vk::DescriptorType::SAMPLED_IMAGE)
+                    .descriptor_count(1)
+                    .stage_flags(vk::ShaderStageFlags::FRAGMENT)
+                    .build(),
+                vk::DescriptorSetLayoutBinding::builder()
+                    .binding(4)
+                    .descriptor_type(vk::DescriptorType::SAMPLER)
+                    .descriptor_count(1)
+                    .stage_flags(vk::ShaderStageFlags::FRAGMENT)
+                    .build(),
+            ],
+        );
+        let uniform_size = mem::size_of::<ShadowUniform>() as vk::DeviceSize;
+        let uniform_buffer = memory.create_buffer(
+            uniform_size,
+            AllocationType::Buffer,
+            vk::BufferUsageFlags::UNIFORM_BUFFER,
+            vk::MemoryPropertyFlags::HOST_VISIBLE,
+        );
+        let sampler_info = vk::SamplerCreateInfo::builder()
+            .mag_filter(vk::Filter::LINEAR)
+            .min_filter(vk::Filter::LINEAR)
+            .mipmap_mode(vk::SamplerMipmapMode::NEAREST)
+            .address_mode_u(vk::SamplerAddressMode::CLAMP_TO_EDGE)
+            .address_mode_v(vk::SamplerAddressMode::CLAMP_TO_EDGE)
+            .address_mode_w(vk::SamplerAddressMode::CLAMP_TO_EDGE)
+            .compare_enable(true)
+            .compare_op(vk::CompareOp::LESS_OR_EQUAL)
+            .min_lod(0.0)
+            .max_lod(0.0)
+            .border_color(vk::BorderColor::FLOAT_OPAQUE_WHITE)
+            .unnormalized_coordinates(false)
+            .build();
+        let sampler = unsafe {
+            core.logical_device
+                .create_sampler(&sampler_info, None)
+                .expect("Failed to create shadow sampler")
+        };
+        // the terrain shaders fetch single texels, so nothing is filtered
+        let occlusion_sampler_info = vk::SamplerCreateInfo::builder()
+            .mag_filter(vk::Filter::NEAREST)
+            .min_filter(vk::Filter::NEAREST)
+            .address_mode_u(vk::SamplerAddressMode::CLAMP_TO_EDGE)
+            .address_mode_v(vk::SamplerAddressMode::CLAMP_TO_EDGE)
+            .address_mode_w(vk::SamplerAddressMode::CLAMP_TO_EDGE)
+            .build();
+        let occlusion_sampler = unsafe {
+            core.logical_device
+                .create_sampler(&occlusion_sampler_info, None)
+                .expect("Failed to create ambient occlusion sampler")
+        };
+        descriptor.write_buffer(
+            0,
+            vk::DescriptorType::UNIFORM_BUFFER,
+            memory.buffers[uniform_buffer].handle,
+            0,
+            uniform_size,
+        );
+        descriptor.write_image(
+            2,
+            vk::DescriptorType::SAMPLER,
+            vk::ImageView::null(),
+            sampler,
+            vk::ImageLayout::UNDEFINED,
+        );
+        descriptor.write_image(
+            4,
+            vk::DescriptorType::SAMPLER,
+            vk::ImageView::null(),
+            occlusion_sampler,
+            vk::ImageLayout::UNDEFINED,
+        );
+        let shadow_map = ShadowMap {
+            core,
+            descriptor,
+            sampler,
+            occlusion_sampler,
+            uniform_buffer,
+        };
+        shadow_map.write_images(render_graph);
+        shadow_map
+    }
+    /// Points the descriptor at the depth atlas the shadow pass of
+    /// `render_graph` draws and at the blurred ambient occlusion. Must be
+    /// called again whenever the graph's images change.
+    pub fn write_images(&self, render_graph: &RenderGraph) {
+        let atlas = render_graph
+            .depth_image(SHADOW_PASS)
+            .expect("The shadow pass has no depth attachment");
+        self.descriptor.write_image(
+            1,
+            vk::DescriptorType::SAMPLED_IMAGE,
+            render_graph.image_view(atlas),
+            vk::Sampler::null(),
+            vk::ImageLayout::SHADER_READ_ONLY_OPTIMAL,
+        );
+        let occlusion = render_graph
+            .color_image(SSAO_BLUR_PASS)
+            .expect("The SSAO blur pass has no colour attachment");
+        self.descriptor.write_image(
+            3,
+            vk::DescriptorType::SAMPLED_IMAGE,
+            render_graph.image_view(occlusion),
+            vk::Sampler::null(),
+            vk::ImageLayout::SHADER_READ_ONLY_OPTIMAL,
+        );
+    }
+    /// Must only be called while no frame that reads the uniform is in
+    /// flight, e.g. from `RenderSystem::prepare`.
+    pub fn update(&self, memory: &mut Memory, uniform: &ShadowUniform) {
+        memory.copy_memory(
+            None,
+            self.uniform_buffer,
+            0,
+            mem::size_of::<ShadowUniform>() as vk::DeviceSize,
+            uniform as *const ShadowUniform as *const u8,
+        );
+    }
+}
+impl Drop for ShadowMap {
+    fn drop(&mut self) {
+        unsafe {
+            self.core.logical_device.destroy_sampler(self.sampler, None);
+            self.core
+                .logical_device
+                .destroy_sampler(self.occlusion_sampler, None);
+        }
+    }
+}
+
+#[cfg(test)]
+mod shadow_map_tests {
+    use super::*;
+    fn camera() -> CameraInfo {
+        let position = glm::vec3(10.0, 40.0, 10.0);
+        let forward = glm::normalize(&glm::vec3(1.0, -0.3, 1.0));
+        let projection = glm::perspective_lh_zo(16.0 / 9.0, 50_f32.to_radians(), 0.1, 1000.0);
+        let view = glm::look_at_lh(&position, &(position + forward), &glm::vec3(0.0, 1.0, 0.0));
+        CameraInfo {
+            proj_view: projection * view,
+            projection,
+            position,
+            forward,
+            near: 0.1,
+            far: 1000.0,
+        }
+    }
+    #[test]
+    fn splits_grow_towards_the_shadow_distance() {
+        let splits = cascade_splits(0.1, SHADOW_DISTANCE);
+        assert!(splits[0] > 0.1);
+        assert!(splits.windows(2).all(|pair| pair[0] < pair[1]));
+        assert!((splits[CASCADE_COUNT - 1] - SHADOW_DISTANCE).abs() < 1e-3);
+    }
+    #[test]
+    fn slice_corners_lie_at_the_split_depths() {
+        let camera = camera();
+        let corners = frustum_slice_corners(&camera, 5.0, 20.0);
+        for (index, corner) in corners.iter().enumerate() {
+            let depth = glm::dot(&(corner - camera.position), &camera.forward);
+            let expected = if index & 4 == 0 { 5.0 } else { 20.0 };
+            assert!((depth - expected).abs() < 1e-2, "{} {}", depth, expected);
+        }
+    }
+    #[test]
+    fn each_cascade_contains_its_slice() {
+        let camera = camera();
+        let lighting = Lighting {
+            sun_direction: glm::vec3(0.4, 1.0, 0.2),
+            ambient: 0.6,
+            sunlight: 1.0,
+            daylight: 1.0,
+            sky_zenith: glm::vec3(0.2, 0.4, 0.9),
+            sky_horizon: glm::vec3(0.6, 0.8, 0.9),
+        };
+        let fog = Fog {
+            mode: FogMode::Linear {
+                start: 100.0,
+                end: 160.0,
+            },
+            color: lighting.sky_horizon,
+        };
+        let uniform = ShadowUniform::new(&camera, &lighting, &fog, 0.0);
+        let mut near = camera.near;
+        for cascade in 0..CASCADE_COUNT {
+            let far = uniform.splits[cascade];
+            for corner in frustum_slice_corners(&camera, near, far).iter() {
+                let light = uniform.light_proj_view[cascade] * corner.push(1.0);
+                let ndc = light.xyz() / light.w;
+                // texel snapping may shift the cascade by up to one texel
+                let edge = 1.0 + 2.0 / CASCADE_SIZE as f32;
+                assert!(ndc.x.abs() <= edge && ndc.y.abs() <= edge, "{:?}", ndc);
+                assert!(ndc.z >= 0.0 && ndc.z <= 1.0, "{:?}", ndc);
+            }
+            near = far;
+        }
+    }
+}
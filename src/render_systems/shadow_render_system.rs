@@ -0,0 +1,181 @@
+use super::block_material::BlockMaterial;
+use super::pipeline::shader::{SHADOW_FRAG, SHADOW_VERT};
+use super::pipeline::{Pipeline, PipelineConfig, PipelineVariant};
+use super::shadow_map::{ShadowMap, ShadowUniform, CASCADE_COUNT, CASCADE_SIZE};
+use super::{FrameInfo, RenderStage, RenderSystem};
+use crate::core::Core;
+use crate::game::game_objects::Vertex;
+use crate::memory::mesh_pool::MeshPool;
+use crate::renderer::render_graph::{RenderGraph, SHADOW_PASS};
+use ash::vk;
+use nalgebra_glm as glm;
+use std::any::Any;
+use std::mem;
+use std::rc::Rc;
+
+/// A mesh drawn in full into every cascade.
+#[derive(Debug, Clone, Copy)]
+pub struct ShadowCaster {
+    pub mesh_pool: MeshPool,
+    pub index_count: u32,
+}
+
+/// Renders the block world from the sun into each cascade of the shadow
+/// atlas, and keeps the shadow uniform the terrain shaders read up to date.
+pub struct ShadowRenderSystem {
+    core: Rc<Core>,
+    pipeline_layout: vk::PipelineLayout,
+    pipeline: Pipeline,
+    block_material: Rc<BlockMaterial>,
+    shadow_map: Rc<ShadowMap>,
+    casters: Vec<ShadowCaster>,
+    light_proj_view: [glm::Mat4; CASCADE_COUNT],
+}
+impl ShadowRenderSystem {
+    pub fn new(
+        core: Rc<Core>,
+        render_graph: &RenderGraph,
+        block_material: Rc<BlockMaterial>,
+        shadow_map: Rc<ShadowMap>,
+        casters: Vec<ShadowCaster>,
+    ) -> Self {
+        let set_layouts = [block_material.descriptor.set_layout];
+        let push_constant_ranges = [vk::PushConstantRange::builder()
+            .stage_flags(vk::ShaderStageFlags::VERTEX)
+            .offset(0)
+            .size(mem::size_of::<glm::Mat4>() as u32)
+            .build()];
+        let pipeline_layout_info = vk::PipelineLayoutCreateInfo::builder()
+            .set_layouts(&set_layouts)
+            .push_constant_ranges(&push_constant_ranges)
+            .build();
+        let pipeline_layout = unsafe {
+            core.logical_device
+                .create_pipeline_layout(&pipeline_layout_info, None)
+                .expect("Failed to create pipeline layout")
+        };
+        let mut pipeline = Pipeline::new(core.clone());
+        pipeline.create_graphic_pipeline(
+            &SHADOW_VERT,
+            &SHADOW_FRAG,
+            PipelineConfig::for_pass(render_graph, SHADOW_PASS, pipeline_layout)
+                .variant(PipelineVariant::DepthOnly),
+            &[Vertex::get_binding_description()],
+            &Vertex::get_attribute_descriptions(),
+        );
+        ShadowRenderSystem {
+            core,
+            pipeline_layout,
+            pipeline,
+            block_material,
+            shadow_map,
+            casters,
+            light_proj_view: [glm::Mat4::identity(); CASCADE_COUNT],
+        }
+    }
+}
+impl RenderSystem for ShadowRenderSystem {
+    fn stage(&self) -> RenderStage {
+        RenderStage::Terrain
+    }
+    fn pass(&self) -> &'static str {
+        SHADOW_PASS
+    }
+    fn prepare(&mut self, frame: &mut FrameInfo) {
+        let uniform = ShadowUniform::new(&frame.camera, &frame.lighting, &frame.fog, frame.time);
+        self.light_proj_view = uniform.light_proj_view;
+        self.shadow_map.update(frame.memory, &uniform);
+    }
+    fn render(&mut self, frame: &mut FrameInfo) {
+        let device = &self.core.logical_device;
+        let command_buffer = frame.command_buffer;
+        unsafe {
+            device.cmd_bind_pipeline(
+                command_buffer,
+                vk::PipelineBindPoint::GRAPHICS,
+                self.pipeline.graphic_pipeline,
+            );
+            device.cmd_bind_descriptor_sets(
+                command_buffer,
+                vk::PipelineBindPoint::GRAPHICS,
+                self.pipeline_layout,
+                0,
+                &[self.block_material.descriptor.set],
+                &[],
+            );
+        }
+        for (cascade, light_proj_view) in self.light_proj_view.iter().enumerate() {
+            let area = vk::Rect2D {
+                offset: vk::Offset2D {
+                    x: (cascade as u32 * CASCADE_SIZE) as i32,
+                    y: 0,
+                },
+                extent: vk::Extent2D {
+                    width: CASCADE_SIZE,
+                    height: CASCADE_SIZE,
+                },
+            };
+            let viewport = vk::Viewport::builder()
+                .x(area.offset.x as f32)
+                .y(0.0)
+                .width(CASCADE_SIZE as f32)
+                .height(CASCADE_SIZE as f32)
+                .min_depth(0.0)
+                .max_depth(1.0)
+                .build();
+            let push_constant = unsafe {
+                std::slice::from_raw_parts(
+                    light_proj_view as *const glm::Mat4 as *const u8,
+                    mem::size_of::<glm::Mat4>(),
+                )
+            };
+            unsafe {
+                device.cmd_set_viewport(command_buffer, 0, &[viewport]);
+                device.cmd_set_scissor(command_buffer, 0, &[area]);
+                device.cmd_push_constants(
+                    command_buffer,
+                    self.pipeline_layout,
+                    vk::ShaderStageFlags::VERTEX,
+                    0,
+                    push_constant,
+                );
+            }
+            for caster in self.casters.iter() {
+                unsafe {
+                    device.cmd_bind_vertex_buffers(
+                        command_buffer,
+                        0,
+                        &[frame.memory.buffers[caster.mesh_pool.vertex_buffer].handle],
+                        &[0],
+                    );
+                    device.cmd_bind_index_buffer(
+                        command_buffer,
+                        frame.memory.buffers[caster.mesh_pool.index_buffer].handle,
+                        0,
+                        vk::IndexType::UINT32,
+                    );
+                    device.cmd_draw_indexed(command_buffer, caster.index_count, 1, 0, 0, 0);
+                }
+            }
+        }
+    }
+    fn reload_shaders(&mut self) {
+        self.pipeline.reload_if_changed();
+    }
+    fn render_graph_changed(&mut self, render_graph: &RenderGraph) {
+        self.pipeline.rebuild_for_pass(render_graph, SHADOW_PASS);
+        self.shadow_map.write_images(render_graph);
+    }
+    fn as_any_mut(&mut self) -> &mut dyn Any {
+        self
+    }
+}
+impl Drop for ShadowRenderSystem {
+    fn drop(&mut self) {
+        unsafe {
+            self.core
+                .logical_device
+                .destroy_pipeline_layout(self.pipeline_layout, None);
+        }
+    }
+}
@@ -0,0 +1,175 @@
+use super::pipeline::shader::{CELESTIAL_FRAG, CELESTIAL_VERT, SKY_FRAG, SKY_VERT};
+use super::pipeline::{BlendMode, Pipeline, PipelineConfig, PipelineVariant};
+use super::{FogMode, FrameInfo, RenderStage, RenderSystem};
+use crate::core::Core;
+use crate::renderer::render_graph::{RenderGraph, MAIN_PASS};
+use ash::vk;
+use nalgebra_glm as glm;
+use std::any::Any;
+use std::mem;
+use std::rc::Rc;
+
+/// Laid out as `Push` in `sky.frag`.
+#[derive(Debug, Clone, Copy)]
+#[repr(C)]
+struct SkyPush {
+    inverse_proj_view: glm::Mat4,
+    camera_position: glm::Vec4,
+    /// Towards the sun in `xyz`, `Lighting::daylight` in `w`.
+    sun_direction: glm::Vec4,
+    zenith_color: glm::Vec4,
+    horizon_color: glm::Vec4,
+}
+
+/// Laid out as `Push` in `celestial.vert`.
+#[derive(Debug, Clone, Copy)]
+#[repr(C)]
+struct CelestialPush {
+    proj_view: glm::Mat4,
+    camera_position: glm::Vec4,
+    sun_direction: glm::Vec4,
+}
+
+fn as_bytes<T>(value: &T) -> &[u8] {
+    unsafe { std::slice::from_raw_parts(value as *const T as *const u8, mem::size_of::<T>()) }
+}
+
+/// Fills the background with a sky gradient lit by `Lighting`, stars at
+/// night, and the sun and moon, after the opaque world so only uncovered
+/// pixels are shaded. Inside exponential fog, such as under water, only the
+/// fog colour is seen.
+pub struct SkyRenderSystem {
+    core: Rc<Core>,
+    pipeline_layout: vk::PipelineLayout,
+    sky_pipeline: Pipeline,
+    celestial_pipeline: Pipeline,
+}
+impl SkyRenderSystem {
+    pub fn new(core: Rc<Core>, render_graph: &RenderGraph) -> Self {
+        let push_constant_ranges = [vk::PushConstantRange::builder()
+            .stage_flags(vk::ShaderStageFlags::VERTEX | vk::ShaderStageFlags::FRAGMENT)
+            .offset(0)
+            .size(mem::size_of::<SkyPush>().max(mem::size_of::<CelestialPush>()) as u32)
+            .build()];
+        let pipeline_layout_info = vk::PipelineLayoutCreateInfo::builder()
+            .push_constant_ranges(&push_constant_ranges)
+            .build();
+        let pipeline_layout = unsafe {
+            core.logical_device
+                .create_pipeline_layout(&pipeline_layout_info, None)
+                .expect("Failed to create pipeline layout")
+        };
+        let config = PipelineConfig::for_pass(render_graph, MAIN_PASS, pipeline_layout)
+            .variant(PipelineVariant::Background);
+        let mut sky_pipeline = Pipeline::new(core.clone());
+        sky_pipeline.create_graphic_pipeline(&SKY_VERT, &SKY_FRAG, config.clone(), &[], &[]);
+        let mut celestial_pipeline = Pipeline::new(core.clone());
+        celestial_pipeline.create_graphic_pipeline(
+            &CELESTIAL_VERT,
+            &CELESTIAL_FRAG,
+            config
+                .topology(vk::PrimitiveTopology::TRIANGLE_STRIP)
+                .blend_mode(BlendMode::Additive),
+            &[],
+            &[],
+        );
+        SkyRenderSystem {
+            core,
+            pipeline_layout,
+            sky_pipeline,
+            celestial_pipeline,
+        }
+    }
+    fn draw(
+        &self,
+        command_buffer: vk::CommandBuffer,
+        pipeline: &Pipeline,
+        push: &[u8],
+        vertex_count: u32,
+        instance_count: u32,
+    ) {
+        let device = &self.core.logical_device;
+        unsafe {
+            device.cmd_bind_pipeline(
+                command_buffer,
+                vk::PipelineBindPoint::GRAPHICS,
+                pipeline.graphic_pipeline,
+            );
+            device.cmd_push_constants(
+                command_buffer,
+                self.pipeline_layout,
+                vk::ShaderStageFlags::VERTEX | vk::ShaderStageFlags::FRAGMENT,
+                0,
+                push,
+            );
+            device.cmd_draw(command_buffer, vertex_count, instance_count, 0, 0);
+        }
+    }
+}
+impl RenderSystem for SkyRenderSystem {
+    fn stage(&self) -> RenderStage {
+        RenderStage::Sky
+    }
+    fn render(&mut self, frame: &mut FrameInfo) {
+        let lighting = &frame.lighting;
+        let camera_position = frame.camera.position.push(1.0);
+        let fogged = matches!(frame.fog.mode, FogMode::Exponential { .. });
+        let (zenith, horizon, daylight) = if fogged {
+            // full daylight hides the stars
+            (frame.fog.color, frame.fog.color, 1.0)
+        } else {
+            (lighting.sky_zenith, lighting.sky_horizon, lighting.daylight)
+        };
+        let sun_direction = lighting.sun_direction.push(daylight);
+        let sky_push = SkyPush {
+            inverse_proj_view: glm::inverse(&frame.camera.proj_view),
+            camera_position,
+            sun_direction,
+            zenith_color: zenith.push(1.0),
+            horizon_color: horizon.push(1.0),
+        };
+        self.draw(
+            frame.command_buffer,
+            &self.sky_pipeline,
+            as_bytes(&sky_push),
+            3,
+            1,
+        );
+        if fogged {
+            return;
+        }
+        let celestial_push = CelestialPush {
+            proj_view: frame.camera.proj_view,
+            camera_position,
+            sun_direction,
+        };
+        self.draw(
+            frame.command_buffer,
+            &self.celestial_pipeline,
+            as_bytes(&celestial_push),
+            4,
+            2,
+        );
+    }
+    fn reload_shaders(&mut self) {
+        self.sky_pipeline.reload_if_changed();
+        self.celestial_pipeline.reload_if_changed();
+    }
+    fn render_graph_changed(&mut self, render_graph: &RenderGraph) {
+        self.sky_pipeline.rebuild_for_pass(render_graph, MAIN_PASS);
+        self.celestial_pipeline
+            .rebuild_for_pass(render_graph, MAIN_PASS);
+    }
+    fn as_any_mut(&mut self) -> &mut dyn Any {
+        self
+    }
+}
+impl Drop for SkyRenderSystem {
+    fn drop(&mut self) {
+        unsafe {
+            self.core
+                .logical_device
+                .destroy_pipeline_layout(self.pipeline_layout, None);
+        }
+    }
+}
@@ -0,0 +1,384 @@
+use super::block_material::BlockMaterial;
+use super::pipeline::shader::{
+    ShaderSource, POST_VERT, SHADOW_FRAG, SHADOW_VERT, SSAO_BLUR_FRAG, SSAO_FRAG,
+};
+use super::pipeline::{Pipeline, PipelineConfig, PipelineVariant};
+use super::shadow_render_system::ShadowCaster;
+use super::{FrameInfo, RenderStage, RenderSystem};
+use crate::core::Core;
+use crate::game::game_objects::Vertex;
+use crate::game::settings::Settings;
+use crate::memory::descriptor::Descriptor;
+use crate::renderer::render_graph::RenderGraph;
+use ash::vk;
+use nalgebra_glm as glm;
+use std::any::Any;
+use std::mem;
+use std::rc::Rc;
+
+/// Names the pass that draws the depth ambient occlusion is computed from.
+pub const SSAO_DEPTH_PASS: &str = "ssao_depth";
+/// Names the pass that computes the ambient occlusion.
+pub const SSAO_PASS: &str = "ssao";
+/// Names the pass that blurs it for the terrain shaders.
+pub const SSAO_BLUR_PASS: &str = "ssao_blur";
+/// How open each pixel is, from 0 when fully occluded to 1. The passes are
+/// cleared to 1, so nothing is darkened while SSAO is off.
+pub const SSAO_FORMAT: vk::Format = vk::Format::R8_UNORM;
+/// The power the openness is raised to, darkening creases further.
+const SSAO_STRENGTH: f32 = 1.5;
+
+/// Draws the shadow casters from the camera into a depth image of their
+/// own for the SSAO pass. The main pass's depth comes too late, since the
+/// terrain shaders already need the occlusion, and may be multisampled.
+pub struct SsaoDepthSystem {
+    core: Rc<Core>,
+    pipeline_layout: vk::PipelineLayout,
+    pipeline: Pipeline,
+    block_material: Rc<BlockMaterial>,
+    casters: Vec<ShadowCaster>,
+    enabled: bool,
+}
+impl SsaoDepthSystem {
+    pub fn new(
+        core: Rc<Core>,
+        render_graph: &RenderGraph,
+        block_material: Rc<BlockMaterial>,
+        casters: Vec<ShadowCaster>,
+        settings: &Settings,
+    ) -> Self {
+        let set_layouts = [block_material.descriptor.set_layout];
+        let push_constant_ranges = [vk::PushConstantRange::builder()
+            .stage_flags(vk::ShaderStageFlags::VERTEX)
+            .offset(0)
+            .size(mem::size_of::<glm::Mat4>() as u32)
+            .build()];
+        let pipeline_layout_info = vk::PipelineLayoutCreateInfo::builder()
+            .set_layouts(&set_layouts)
+            .push_constant_ranges(&push_constant_ranges)
+            .build();
+        let pipeline_layout = unsafe {
+            core.logical_device
+                .create_pipeline_layout(&pipeline_layout_info, None)
+                .expect("Failed to create pipeline layout")
+        };
+        // the shadow shaders alpha test leaves; unlike the shadow pass no
+        // depth bias, so positions are reconstructed where they are drawn
+        let mut pipeline = Pipeline::new(core.clone());
+        pipeline.create_graphic_pipeline(
+            &SHADOW_VERT,
+            &SHADOW_FRAG,
+            PipelineConfig::for_pass(render_graph, SSAO_DEPTH_PASS, pipeline_layout)
+                .color_attachments(&[])
+                .cull_mode(vk::CullModeFlags::NONE),
+            &[Vertex::get_binding_description()],
+            &Vertex::get_attribute_descriptions(),
+        );
+        SsaoDepthSystem {
+            core,
+            pipeline_layout,
+            pipeline,
+            block_material,
+            casters,
+            enabled: settings.ssao,
+        }
+    }
+    pub fn apply_settings(&mut self, settings: &Settings) {
+        self.enabled = settings.ssao;
+    }
+}
+impl RenderSystem for SsaoDepthSystem {
+    fn stage(&self) -> RenderStage {
+        RenderStage::Terrain
+    }
+    fn pass(&self) -> &'static str {
+        SSAO_DEPTH_PASS
+    }
+    fn render(&mut self, frame: &mut FrameInfo) {
+        if !self.enabled {
+            return;
+        }
+        let device = &self.core.logical_device;
+        let command_buffer = frame.command_buffer;
+        let proj_view = frame.camera.proj_view;
+        unsafe {
+            device.cmd_bind_pipeline(
+                command_buffer,
+                vk::PipelineBindPoint::GRAPHICS,
+                self.pipeline.graphic_pipeline,
+            );
+            device.cmd_bind_descriptor_sets(
+                command_buffer,
+                vk::PipelineBindPoint::GRAPHICS,
+                self.pipeline_layout,
+                0,
+                &[self.block_material.descriptor.set],
+                &[],
+            );
+            device.cmd_push_constants(
+                command_buffer,
+                self.pipeline_layout,
+                vk::ShaderStageFlags::VERTEX,
+                0,
+                std::slice::from_raw_parts(
+                    &proj_view as *const glm::Mat4 as *const u8,
+                    mem::size_of::<glm::Mat4>(),
+                ),
+            );
+        }
+        for caster in self.casters.iter() {
+            unsafe {
+                device.cmd_bind_vertex_buffers(
+                    command_buffer,
+                    0,
+                    &[frame.memory.buffers[caster.mesh_pool.vertex_buffer].handle],
+                    &[0],
+                );
+                device.cmd_bind_index_buffer(
+                    command_buffer,
+                    frame.memory.buffers[caster.mesh_pool.index_buffer].handle,
+                    0,
+                    vk::IndexType::UINT32,
+                );
+                device.cmd_draw_indexed(command_buffer, caster.index_count, 1, 0, 0, 0);
+            }
+        }
+    }
+    fn render_graph_changed(&mut self, render_graph: &RenderGraph) {
+        self.pipeline
+            .rebuild_for_pass(render_graph, SSAO_DEPTH_PASS);
+    }
+    fn reload_shaders(&mut self) {
+        self.pipeline.reload_if_changed();
+    }
+    fn as_any_mut(&mut self) -> &mut dyn Any {
+        self
+    }
+}
+impl Drop for SsaoDepthSystem {
+    fn drop(&mut self) {
+        unsafe {
+            self.core
+                .logical_device
+                .destroy_pipeline_layout(self.pipeline_layout, None);
+        }
+    }
+}
+
+/// One of the full-screen SSAO passes.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SsaoStep {
+    /// Samples a hemisphere over every pixel of the depth image.
+    Occlusion,
+    /// Smooths the noisy occlusion.
+    Blur,
+}
+impl SsaoStep {
+    fn pass(&self) -> &'static str {
+        match self {
+            SsaoStep::Occlusion => SSAO_PASS,
+            SsaoStep::Blur => SSAO_BLUR_PASS,
+        }
+    }
+    fn fragment_shader(&self) -> ShaderSource {
+        match self {
+            SsaoStep::Occlusion => SSAO_FRAG,
+            SsaoStep::Blur => SSAO_BLUR_FRAG,
+        }
+    }
+}
+
+/// Laid out as `Push` in `ssao.frag`.
+#[derive(Debug, Clone, Copy)]
+#[repr(C)]
+struct SsaoPush {
+    projection: glm::Mat4,
+    /// Radius, near plane, far plane and strength.
+    params: glm::Vec4,
+}
+
+/// Computes or blurs the ambient occlusion, sampling the image the pass
+/// before wrote. While SSAO is off it draws nothing and leaves its target
+/// cleared to fully open.
+pub struct SsaoSystem {
+    core: Rc<Core>,
+    step: SsaoStep,
+    descriptor: Descriptor,
+    sampler: vk::Sampler,
+    pipeline_layout: vk::PipelineLayout,
+    pipeline: Pipeline,
+    enabled: bool,
+    /// How far around each surface occluders are looked for, in blocks.
+    radius: f32,
+}
+impl SsaoSystem {
+    pub fn new(
+        core: Rc<Core>,
+        render_graph: &RenderGraph,
+        step: SsaoStep,
+        settings: &Settings,
+    ) -> Self {
+        let descriptor = Descriptor::new(
+            core.clone(),
+            &[
+                vk::DescriptorSetLayoutBinding::builder()
+                    .binding(0)
+                    .descriptor_type(vk::DescriptorType::SAMPLED_IMAGE)
+                    .descriptor_count(1)
+                    .stage_flags(vk::ShaderStageFlags::FRAGMENT)
+                    .build(),
+                vk::DescriptorSetLayoutBinding::builder()
+                    .binding(1)
+                    .descriptor_type(vk::DescriptorType::SAMPLER)
+                    .descriptor_count(1)
+                    .stage_flags(vk::ShaderStageFlags::FRAGMENT)
+                    .build(),
+            ],
+        );
+        // depth formats need not support linear filtering
+        let sampler_info = vk::SamplerCreateInfo::builder()
+            .mag_filter(vk::Filter::NEAREST)
+            .min_filter(vk::Filter::NEAREST)
+            .mipmap_mode(vk::SamplerMipmapMode::NEAREST)
+            .address_mode_u(vk::SamplerAddressMode::CLAMP_TO_EDGE)
+            .address_mode_v(vk::SamplerAddressMode::CLAMP_TO_EDGE)
+            .address_mode_w(vk::SamplerAddressMode::CLAMP_TO_EDGE)
+            .min_lod(0.0)
+            .max_lod(0.0)
+            .unnormalized_coordinates(false)
+            .build();
+        let sampler = unsafe {
+            core.logical_device
+                .create_sampler(&sampler_info, None)
+                .expect("Failed to create SSAO sampler")
+        };
+        descriptor.write_image(
+            1,
+            vk::DescriptorType::SAMPLER,
+            vk::ImageView::null(),
+            sampler,
+            vk::ImageLayout::UNDEFINED,
+        );
+        let set_layouts = [descriptor.set_layout];
+        let push_constant_ranges = [vk::PushConstantRange::builder()
+            .stage_flags(vk::ShaderStageFlags::FRAGMENT)
+            .offset(0)
+            .size(mem::size_of::<SsaoPush>() as u32)
+            .build()];
+        let pipeline_layout_info = vk::PipelineLayoutCreateInfo::builder()
+            .set_layouts(&set_layouts)
+            .push_constant_ranges(&push_constant_ranges)
+            .build();
+        let pipeline_layout = unsafe {
+            core.logical_device
+                .create_pipeline_layout(&pipeline_layout_info, None)
+                .expect("Failed to create pipeline layout")
+        };
+        let mut pipeline = Pipeline::new(core.clone());
+        pipeline.create_graphic_pipeline(
+            &POST_VERT,
+            &step.fragment_shader(),
+            PipelineConfig::for_pass(render_graph, step.pass(), pipeline_layout)
+                .variant(PipelineVariant::FullScreen),
+            &[],
+            &[],
+        );
+        let mut ssao_system = SsaoSystem {
+            core,
+            step,
+            descriptor,
+            sampler,
+            pipeline_layout,
+            pipeline,
+            enabled: true,
+            radius: 1.0,
+        };
+        ssao_system.write_input(render_graph);
+        ssao_system.apply_settings(settings);
+        ssao_system
+    }
+    pub fn apply_settings(&mut self, settings: &Settings) {
+        self.enabled = settings.ssao;
+        self.radius = settings.ssao_radius;
+    }
+    fn write_input(&self, render_graph: &RenderGraph) {
+        let input = render_graph.sampled_images(self.step.pass())[0];
+        self.descriptor.write_image(
+            0,
+            vk::DescriptorType::SAMPLED_IMAGE,
+            render_graph.image_view(input),
+            vk::Sampler::null(),
+            vk::ImageLayout::SHADER_READ_ONLY_OPTIMAL,
+        );
+    }
+}
+impl RenderSystem for SsaoSystem {
+    fn stage(&self) -> RenderStage {
+        RenderStage::PostProcess
+    }
+    fn pass(&self) -> &'static str {
+        self.step.pass()
+    }
+    fn render(&mut self, frame: &mut FrameInfo) {
+        if !self.enabled {
+            return;
+        }
+        let push = SsaoPush {
+            projection: frame.camera.projection,
+            params: glm::vec4(
+                self.radius,
+                frame.camera.near,
+                frame.camera.far,
+                SSAO_STRENGTH,
+            ),
+        };
+        let device = &self.core.logical_device;
+        unsafe {
+            device.cmd_bind_pipeline(
+                frame.command_buffer,
+                vk::PipelineBindPoint::GRAPHICS,
+                self.pipeline.graphic_pipeline,
+            );
+            device.cmd_bind_descriptor_sets(
+                frame.command_buffer,
+                vk::PipelineBindPoint::GRAPHICS,
+                self.pipeline_layout,
+                0,
+                &[self.descriptor.set],
+                &[],
+            );
+            device.cmd_push_constants(
+                frame.command_buffer,
+                self.pipeline_layout,
+                vk::ShaderStageFlags::FRAGMENT,
+                0,
+                std::slice::from_raw_parts(
+                    &push as *const SsaoPush as *const u8,
+                    mem::size_of::<SsaoPush>(),
+                ),
+            );
+            device.cmd_draw(frame.command_buffer, 3, 1, 0, 0);
+        }
+    }
+    fn render_graph_changed(&mut self, render_graph: &RenderGraph) {
+        self.pipeline
+            .rebuild_for_pass(render_graph, self.step.pass());
+        self.write_input(render_graph);
+    }
+    fn reload_shaders(&mut self) {
+        self.pipeline.reload_if_changed();
+    }
+    fn as_any_mut(&mut self) -> &mut dyn Any {
+        self
+    }
+}
+impl Drop for SsaoSystem {
+    fn drop(&mut self) {
+        unsafe {
+            self.core.logical_device.destroy_sampler(self.sampler, None);
+            self.core
+                .logical_device
+                .destroy_pipeline_layout(self.pipeline_layout, None);
+        }
+    }
+}
@@ -0,0 +1,233 @@
+use super::block_material::BlockMaterial;
+use super::culling_system::{ChunkBounds, CullingSystem};
+use super::main_render_system::PushConstant;
+use super::occlusion_system::OcclusionSystem;
+use super::pipeline::PipelineVariant;
+use super::shadow_map::ShadowMap;
+use super::shadow_render_system::ShadowCaster;
+use super::{DebugView, FrameInfo, MainRenderSystem, RenderStage, RenderSystem};
+use crate::core::Core;
+use crate::game::game_objects::Vertex;
+use crate::game::world::mesher::ChunkMeshRange;
+use crate::memory::mesh_pool::MeshPool;
+use crate::memory::Memory;
+use crate::renderer::render_graph::RenderGraph;
+use ash::vk;
+use std::any::Any;
+use std::rc::Rc;
+
+/// Chunks this close to the camera are never occlusion culled, since their
+/// proxy box may be clipped by the near plane.
+const OCCLUSION_CAMERA_MARGIN: f32 = 1.0;
+
+/// Draws the block world: the packed chunk meshes, textured from the block
+/// texture array, culled by the frustum, cave visibility and occlusion
+/// queries, on the GPU when the device allows it.
+pub struct TerrainRenderSystem {
+    core: Rc<Core>,
+    main_render_system: MainRenderSystem,
+    culling_system: Option<CullingSystem>,
+    occlusion_system: OcclusionSystem,
+    block_material: Rc<BlockMaterial>,
+    shadow_map: Rc<ShadowMap>,
+    mesh_pool: MeshPool,
+    chunk_meshes: Vec<ChunkMeshRange>,
+    /// Sections reachable from the camera this frame, per `World::chunks`.
+    visible_sections: Vec<bool>,
+}
+impl TerrainRenderSystem {
+    #[allow(clippy::too_many_arguments)]
+    pub fn new(
+        core: Rc<Core>,
+        memory: &mut Memory,
+        block_material: Rc<BlockMaterial>,
+        shadow_map: Rc<ShadowMap>,
+        render_graph: &RenderGraph,
+        vertices: &[Vertex],
+        indices: &[u32],
+        chunk_meshes: Vec<ChunkMeshRange>,
+        section_count: usize,
+    ) -> Self {
+        let main_render_system = MainRenderSystem::new(
+            core.clone(),
+            render_graph,
+            &Vertex::get_attribute_descriptions(),
+            &vec![Vertex::get_binding_description()],
+            &[
+                block_material.descriptor.set_layout,
+                shadow_map.descriptor.set_layout,
+            ],
+            PipelineVariant::Opaque,
+        );
+        let mesh_pool = memory.create_mesh_pool(vertices, indices);
+        let culling_system = if core.draw_indirect_count.is_some() {
+            let chunk_bounds: Vec<ChunkBounds> = chunk_meshes
+                .iter()
+                .map(|chunk_mesh| {
+                    ChunkBounds::new(
+                        chunk_mesh.aabb.min,
+                        chunk_mesh.aabb.max,
+                        chunk_mesh.first_index,
+                        chunk_mesh.index_count,
+                        chunk_mesh.chunk_index as u32,
+                    )
+                })
+                .collect();
+            Some(CullingSystem::new(core.clone(), memory, &chunk_bounds))
+        } else {
+            println!("VK_KHR_draw_indirect_count is not supported, culling chunks on the CPU");
+            None
+        };
+        let occlusion_system =
+            OcclusionSystem::new(core.clone(), render_graph, chunk_meshes.len() as u32);
+        TerrainRenderSystem {
+            core,
+            main_render_system,
+            culling_system,
+            occlusion_system,
+            block_material,
+            shadow_map,
+            mesh_pool,
+            chunk_meshes,
+            visible_sections: vec![true; section_count],
+        }
+    }
+    /// The whole terrain mesh, for the shadow pass.
+    pub fn shadow_caster(&self) -> ShadowCaster {
+        let last = self.chunk_meshes.last();
+        ShadowCaster {
+            mesh_pool: self.mesh_pool,
+            index_count: last.map_or(0, |mesh| mesh.first_index + mesh.index_count),
+        }
+    }
+    /// Sets the result of cave culling for the next frame.
+    pub fn set_visible_sections(&mut self, visible_sections: Vec<bool>) {
+        self.visible_sections = visible_sections;
+    }
+    fn is_occluded(&self, chunk: usize, frame: &FrameInfo) -> bool {
+        if !self.visible_sections[self.chunk_meshes[chunk].chunk_index] {
+            return true;
+        }
+        !self.occlusion_system.is_visible(chunk)
+            && !self.chunk_meshes[chunk]
+                .aabb
+                .contains_point(&frame.camera.position, OCCLUSION_CAMERA_MARGIN)
+    }
+}
+impl RenderSystem for TerrainRenderSystem {
+    fn stage(&self) -> RenderStage {
+        RenderStage::Terrain
+    }
+    fn prepare(&mut self, frame: &mut FrameInfo) {
+        self.occlusion_system
+            .begin_frame(frame.command_buffer, frame.frame_index);
+        if let Some(ref culling_system) = self.culling_system {
+            for chunk in 0..self.chunk_meshes.len() {
+                let occluded = self.is_occluded(chunk, frame);
+                culling_system.set_occluded(frame.memory, frame.frame_index, chunk, occluded);
+            }
+            // this frame's buffers were last used MAX_FRAMES_IN_FLIGHT frames
+            // ago, and the renderer has waited on that frame's fence, so its
+            // draw count is ready and its chunk buffer is free to write
+            let drawn = culling_system.drawn_count(frame.memory, frame.frame_index);
+            frame.culling_stats.drawn += drawn;
+            frame.culling_stats.culled += culling_system.chunk_count() - drawn;
+            culling_system.dispatch(
+                frame.command_buffer,
+                frame.memory,
+                frame.frame_index,
+                frame.frustum.planes,
+            );
+        }
+    }
+    fn render(&mut self, frame: &mut FrameInfo) {
+        let command_buffer = frame.command_buffer;
+        let push = PushConstant::new(frame.camera.proj_view, frame.camera.near, frame.camera.far);
+        self.main_render_system.bind(&command_buffer, push);
+        self.main_render_system.bind_descriptor_sets(
+            &command_buffer,
+            &[
+                self.block_material.descriptor.set,
+                self.shadow_map.descriptor.set,
+            ],
+        );
+        unsafe {
+            self.core.logical_device.cmd_bind_vertex_buffers(
+                command_buffer,
+                0,
+                &[frame.memory.buffers[self.mesh_pool.vertex_buffer].handle],
+                &[0],
+            );
+            self.core.logical_device.cmd_bind_index_buffer(
+                command_buffer,
+                frame.memory.buffers[self.mesh_pool.index_buffer].handle,
+                0,
+                vk::IndexType::UINT32,
+            );
+        }
+        match self.culling_system {
+            Some(ref culling_system) => {
+                culling_system.draw(command_buffer, frame.memory, frame.frame_index)
+            }
+            None => {
+                for (chunk, chunk_mesh) in self.chunk_meshes.iter().enumerate() {
+                    if !frame.frustum.intersects_aabb(&chunk_mesh.aabb)
+                        || self.is_occluded(chunk, frame)
+                    {
+                        frame.culling_stats.culled += 1;
+                        continue;
+                    }
+                    frame.culling_stats.drawn += 1;
+                    self.main_render_system.push_constants(
+                        &command_buffer,
+                        PushConstant {
+                            draw_id: chunk_mesh.chunk_index as u32,
+                            ..push
+                        },
+                    );
+                    unsafe {
+                        self.core.logical_device.cmd_draw_indexed(
+                            command_buffer,
+                            chunk_mesh.index_count,
+                            1,
+                            chunk_mesh.first_index,
+                            0,
+                            0,
+                        );
+                    }
+                }
+            }
+        }
+        // test every chunk in the frustum, hidden or not, against the depth
+        // just drawn; the results arrive MAX_FRAMES_IN_FLIGHT frames later
+        self.occlusion_system.bind(command_buffer);
+        for (chunk, chunk_mesh) in self.chunk_meshes.iter().enumerate() {
+            if frame.frustum.intersects_aabb(&chunk_mesh.aabb) {
+                self.occlusion_system.query_box(
+                    command_buffer,
+                    frame.frame_index,
+                    chunk,
+                    &chunk_mesh.aabb,
+                    &frame.camera.proj_view,
+                );
+            }
+        }
+    }
+    fn reload_shaders(&mut self) {
+        self.main_render_system.reload_shaders();
+        self.occlusion_system.reload_shaders();
+        if let Some(ref mut culling_system) = self.culling_system {
+            culling_system.reload_shaders();
+        }
+    }
+    fn render_graph_changed(&mut self, render_graph: &RenderGraph) {
+        self.main_render_system.render_graph_changed(render_graph);
+        self.occlusion_system.render_graph_changed(render_graph);
+    }
+    fn set_debug_view(&mut self, debug_view: DebugView) {
+        self.main_render_system.set_debug_view(debug_view);
+    }
+    fn as_any_mut(&mut self) -> &mut dyn Any {
+        self
+    }
+}
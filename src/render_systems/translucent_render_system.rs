@@ -0,0 +1,219 @@
+use super::block_material::BlockMaterial;
+use super::main_render_system::PushConstant;
+use super::pipeline::PipelineVariant;
+use super::shadow_map::ShadowMap;
+use super::{DebugView, FrameInfo, MainRenderSystem, RenderStage, RenderSystem};
+use crate::core::Core;
+use crate::game::game_objects::Vertex;
+use crate::game::world::mesher::{back_to_front_indices, TranslucentMesh};
+use crate::memory::mesh_pool::MeshPool;
+use crate::memory::{AllocationType, Memory};
+use crate::renderer::render_graph::RenderGraph;
+use crate::MAX_FRAMES_IN_FLIGHT;
+use ash::vk;
+use nalgebra_glm as glm;
+use std::any::Any;
+use std::mem;
+use std::rc::Rc;
+
+/// How far the camera moves before translucent faces are sorted again.
+const RESORT_DISTANCE: f32 = 0.5;
+
+/// One set of translucent faces with its own pipeline, sorted on its own.
+struct TranslucentLayer {
+    main_render_system: MainRenderSystem,
+    /// `None` when the world has no such faces.
+    mesh_pool: Option<MeshPool>,
+    /// One sorted index buffer per frame in flight, so sorting never
+    /// rewrites indices another frame may still be drawing from. The first
+    /// is the mesh pool's.
+    index_buffers: Vec<usize>,
+    face_centers: Vec<glm::Vec3>,
+    /// Camera position each index buffer was last sorted for.
+    sorted_for: Vec<Option<glm::Vec3>>,
+}
+impl TranslucentLayer {
+    fn new(
+        core: Rc<Core>,
+        memory: &mut Memory,
+        render_graph: &RenderGraph,
+        descriptor_set_layouts: &[vk::DescriptorSetLayout],
+        variant: PipelineVariant,
+        mesh: TranslucentMesh,
+    ) -> Self {
+        let main_render_system = MainRenderSystem::new(
+            core,
+            render_graph,
+            &Vertex::get_attribute_descriptions(),
+            &vec![Vertex::get_binding_description()],
+            descriptor_set_layouts,
+            variant,
+        );
+        let mut index_buffers = Vec::new();
+        let mesh_pool = if mesh.face_centers.is_empty() {
+            None
+        } else {
+            let indices = back_to_front_indices(&mesh.face_centers, &glm::vec3(0.0, 0.0, 0.0));
+            let mesh_pool = memory.create_mesh_pool(&mesh.vertices, &indices);
+            index_buffers.push(mesh_pool.index_buffer);
+            // the other frames' copies are filled by their first sort
+            for _ in 1..MAX_FRAMES_IN_FLIGHT {
+                index_buffers.push(memory.create_buffer(
+                    mem::size_of_val(indices.as_slice()) as vk::DeviceSize,
+                    AllocationType::Buffer,
+                    vk::BufferUsageFlags::INDEX_BUFFER,
+                    vk::MemoryPropertyFlags::HOST_VISIBLE,
+                ));
+            }
+            Some(mesh_pool)
+        };
+        TranslucentLayer {
+            main_render_system,
+            mesh_pool,
+            index_buffers,
+            face_centers: mesh.face_centers,
+            sorted_for: vec![None; MAX_FRAMES_IN_FLIGHT as usize],
+        }
+    }
+    fn index_count(&self) -> u32 {
+        self.face_centers.len() as u32 * 6
+    }
+    /// Sorts `frame_index`'s index buffer, which the renderer has waited
+    /// for that frame's last use of before preparing it again.
+    fn sort(&mut self, memory: &mut Memory, frame_index: u32, eye: glm::Vec3) {
+        let index_buffer = match self.index_buffers.get(frame_index as usize) {
+            Some(&index_buffer) => index_buffer,
+            None => return,
+        };
+        let sorted_for = &mut self.sorted_for[frame_index as usize];
+        if let Some(sorted_for) = *sorted_for {
+            if glm::distance(&sorted_for, &eye) < RESORT_DISTANCE {
+                return;
+            }
+        }
+        let indices = back_to_front_indices(&self.face_centers, &eye);
+        memory.copy_memory(
+            None,
+            index_buffer,
+            0,
+            (indices.len() * mem::size_of::<u32>()) as vk::DeviceSize,
+            indices.as_ptr() as *const u8,
+        );
+        *sorted_for = Some(eye);
+    }
+    fn render(
+        &mut self,
+        core: &Core,
+        frame: &mut FrameInfo,
+        descriptor_sets: &[vk::DescriptorSet],
+    ) {
+        let mesh_pool = match self.mesh_pool {
+            Some(mesh_pool) => mesh_pool,
+            None => return,
+        };
+        let command_buffer = frame.command_buffer;
+        let push = PushConstant::new(frame.camera.proj_view, frame.camera.near, frame.camera.far);
+        self.main_render_system.bind(&command_buffer, push);
+        self.main_render_system
+            .bind_descriptor_sets(&command_buffer, descriptor_sets);
+        unsafe {
+            core.logical_device.cmd_bind_vertex_buffers(
+                command_buffer,
+                0,
+                &[frame.memory.buffers[mesh_pool.vertex_buffer].handle],
+                &[0],
+            );
+            core.logical_device.cmd_bind_index_buffer(
+                command_buffer,
+                frame.memory.buffers[self.index_buffers[frame.frame_index as usize]].handle,
+                0,
+                vk::IndexType::UINT32,
+            );
+            core.logical_device
+                .cmd_draw_indexed(command_buffer, self.index_count(), 1, 0, 0, 0);
+        }
+    }
+}
+
+/// Draws water, then glass and ice, after the opaque world, blended over
+/// it without writing depth, farthest face first within each. Water has a
+/// shader of its own.
+pub struct TranslucentRenderSystem {
+    core: Rc<Core>,
+    block_material: Rc<BlockMaterial>,
+    shadow_map: Rc<ShadowMap>,
+    water: TranslucentLayer,
+    others: TranslucentLayer,
+}
+impl TranslucentRenderSystem {
+    pub fn new(
+        core: Rc<Core>,
+        memory: &mut Memory,
+        block_material: Rc<BlockMaterial>,
+        shadow_map: Rc<ShadowMap>,
+        render_graph: &RenderGraph,
+        water: TranslucentMesh,
+        others: TranslucentMesh,
+    ) -> Self {
+        let descriptor_set_layouts = [
+            block_material.descriptor.set_layout,
+            shadow_map.descriptor.set_layout,
+        ];
+        let mut layer = |variant: PipelineVariant, mesh: TranslucentMesh| {
+            TranslucentLayer::new(
+                core.clone(),
+                memory,
+                render_graph,
+                &descriptor_set_layouts,
+                variant,
+                mesh,
+            )
+        };
+        let water = layer(PipelineVariant::Water, water);
+        let others = layer(PipelineVariant::Translucent, others);
+        TranslucentRenderSystem {
+            core,
+            block_material,
+            shadow_map,
+            water,
+            others,
+        }
+    }
+}
+impl RenderSystem for TranslucentRenderSystem {
+    fn stage(&self) -> RenderStage {
+        RenderStage::Translucent
+    }
+    fn prepare(&mut self, frame: &mut FrameInfo) {
+        let eye = frame.camera.position;
+        self.water.sort(frame.memory, frame.frame_index, eye);
+        self.others.sort(frame.memory, frame.frame_index, eye);
+    }
+    fn render(&mut self, frame: &mut FrameInfo) {
+        let descriptor_sets = [
+            self.block_material.descriptor.set,
+            self.shadow_map.descriptor.set,
+        ];
+        self.water.render(&self.core, frame, &descriptor_sets);
+        self.others.render(&self.core, frame, &descriptor_sets);
+    }
+    fn reload_shaders(&mut self) {
+        self.water.main_render_system.reload_shaders();
+        self.others.main_render_system.reload_shaders();
+    }
+    fn render_graph_changed(&mut self, render_graph: &RenderGraph) {
+        self.water
+            .main_render_system
+            .render_graph_changed(render_graph);
+        self.others
+            .main_render_system
+            .render_graph_changed(render_graph);
+    }
+    fn set_debug_view(&mut self, debug_view: DebugView) {
+        self.water.main_render_system.set_debug_view(debug_view);
+        self.others.main_render_system.set_debug_view(debug_view);
+    }
+    fn as_any_mut(&mut self) -> &mut dyn Any {
+        self
+    }
+}
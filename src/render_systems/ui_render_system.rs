@@ -0,0 +1,416 @@
+use super::pipeline::shader::{UI_FRAG, UI_VERT};
+use super::pipeline::{BlendMode, Pipeline, PipelineConfig, PipelineVariant};
+use super::post_process_system::pass_name;
+use super::{FrameInfo, RenderStage, RenderSystem};
+use crate::command::Command;
+use crate::core::Core;
+use crate::game::font::{atlas_pixels, glyph_cell, ATLAS_GLYPHS, ATLAS_SIZE, GLYPH_SIZE};
+use crate::game::game_objects::camera::Camera;
+use crate::game::settings::PostEffect;
+use crate::memory::descriptor::Descriptor;
+use crate::memory::texture::{TextureArray, TextureFilter};
+use crate::memory::{AllocationType, Memory};
+use crate::offset_of;
+use crate::renderer::render_graph::RenderGraph;
+use crate::MAX_FRAMES_IN_FLIGHT;
+use ash::vk;
+use nalgebra_glm as glm;
+use std::any::Any;
+use std::mem;
+use std::rc::Rc;
+
+/// Glyphs the vertex buffers have room for each frame, shadows included.
+/// Text past this is dropped.
+const MAX_GLYPHS: usize = 4096;
+const VERTICES_PER_GLYPH: usize = 6;
+/// Font pixels from the top of one line of text to the next.
+const LINE_HEIGHT: u32 = GLYPH_SIZE + 2;
+
+/// The pass the UI is drawn in: the last post-process pass, which writes
+/// the swap chain image, so text is not tone mapped or blurred by FXAA.
+fn ui_pass() -> &'static str {
+    pass_name(PostEffect::ALL[PostEffect::ALL.len() - 1])
+}
+
+/// A corner of a glyph quad, in pixels from the top left of the screen.
+#[derive(Debug, Clone, Copy, PartialEq)]
+#[repr(C)]
+pub struct UiVertex {
+    pub position: glm::Vec2,
+    pub uv: glm::Vec2,
+    pub color: glm::Vec4,
+}
+impl UiVertex {
+    pub fn get_binding_description() -> vk::VertexInputBindingDescription {
+        vk::VertexInputBindingDescription::builder()
+            .binding(0)
+            .stride(mem::size_of::<UiVertex>() as u32)
+            .input_rate(vk::VertexInputRate::VERTEX)
+            .build()
+    }
+    pub fn get_attribute_descriptions() -> Vec<vk::VertexInputAttributeDescription> {
+        vec![
+            vk::VertexInputAttributeDescription::builder()
+                .binding(0)
+                .location(0)
+                .format(vk::Format::R32G32_SFLOAT)
+                .offset(offset_of!(UiVertex, position) as u32)
+                .build(),
+            vk::VertexInputAttributeDescription::builder()
+                .binding(0)
+                .location(1)
+                .format(vk::Format::R32G32_SFLOAT)
+                .offset(offset_of!(UiVertex, uv) as u32)
+                .build(),
+            vk::VertexInputAttributeDescription::builder()
+                .binding(0)
+                .location(2)
+                .format(vk::Format::R32G32B32A32_SFLOAT)
+                .offset(offset_of!(UiVertex, color) as u32)
+                .build(),
+        ]
+    }
+}
+
+/// How a run of text is drawn.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct TextStyle {
+    pub color: glm::Vec4,
+    /// Screen pixels per font pixel. Whole numbers keep glyphs sharp.
+    pub scale: f32,
+    /// Colour of a copy drawn one font pixel down and to the right, behind
+    /// the text, so it stays readable over bright sky.
+    pub shadow: Option<glm::Vec4>,
+}
+impl Default for TextStyle {
+    fn default() -> Self {
+        TextStyle {
+            color: glm::vec4(1.0, 1.0, 1.0, 1.0),
+            scale: 2.0,
+            shadow: Some(glm::vec4(0.0, 0.0, 0.0, 0.75)),
+        }
+    }
+}
+
+/// Lays `text` out as glyph quads, six vertices each, from `position`, its
+/// top left corner in pixels. `\n` starts a new line and whitespace only
+/// advances. Every shadow comes before the text so the text covers them.
+pub fn layout_text(text: &str, position: glm::Vec2, style: &TextStyle) -> Vec<UiVertex> {
+    let glyph_size = GLYPH_SIZE as f32 * style.scale;
+    let mut glyphs = vec![];
+    for (line, characters) in text.lines().enumerate() {
+        let top = position.y + (line as u32 * LINE_HEIGHT) as f32 * style.scale;
+        for (column, character) in characters.chars().enumerate() {
+            if character.is_whitespace() {
+                continue;
+            }
+            let left = position.x + column as f32 * glyph_size;
+            glyphs.push((glm::vec2(left, top), glyph_cell(character)));
+        }
+    }
+    let shadow = style
+        .shadow
+        .map(|color| (glm::vec2(style.scale, style.scale), color));
+    let mut vertices = Vec::with_capacity(glyphs.len() * VERTICES_PER_GLYPH * 2);
+    for (offset, color) in shadow
+        .into_iter()
+        .chain([(glm::vec2(0.0, 0.0), style.color)])
+    {
+        for (corner, (column, row)) in glyphs.iter() {
+            let top_left = corner + offset;
+            let bottom_right = top_left + glm::vec2(glyph_size, glyph_size);
+            let uv_top_left = glm::vec2(*column as f32, *row as f32) / ATLAS_GLYPHS as f32;
+            let uv_bottom_right =
+                glm::vec2(*column as f32 + 1.0, *row as f32 + 1.0) / ATLAS_GLYPHS as f32;
+            let vertex = |x: bool, y: bool| UiVertex {
+                position: glm::vec2(
+                    if x { bottom_right.x } else { top_left.x },
+                    if y { bottom_right.y } else { top_left.y },
+                ),
+                uv: glm::vec2(
+                    if x { uv_bottom_right.x } else { uv_top_left.x },
+                    if y { uv_bottom_right.y } else { uv_top_left.y },
+                ),
+                color,
+            };
+            vertices.extend([
+                vertex(false, false),
+                vertex(false, true),
+                vertex(true, true),
+                vertex(false, false),
+                vertex(true, true),
+                vertex(true, false),
+            ]);
+        }
+    }
+    vertices
+}
+
+/// Draws screen-space text from a bitmap font atlas over the finished
+/// frame. Text queued with `draw_text` stays on screen until `clear_text`;
+/// every frame it is copied into that frame's vertex buffer and drawn in
+/// one call, with an orthographic projection of one unit per pixel.
+pub struct UiRenderSystem {
+    core: Rc<Core>,
+    /// Owns the image and sampler `descriptor` points at.
+    _font: TextureArray,
+    descriptor: Descriptor,
+    pipeline_layout: vk::PipelineLayout,
+    pipeline: Pipeline,
+    /// One host-visible vertex buffer per frame in flight, so the buffer a
+    /// frame still being drawn reads is never overwritten.
+    vertex_buffers: Vec<usize>,
+    camera: Camera,
+    vertices: Vec<UiVertex>,
+}
+impl UiRenderSystem {
+    pub fn new(
+        core: Rc<Core>,
+        memory: &mut Memory,
+        command: &Command,
+        render_graph: &RenderGraph,
+    ) -> Self {
+        let descriptor = Descriptor::new(
+            core.clone(),
+            &[
+                vk::DescriptorSetLayoutBinding::builder()
+                    .binding(0)
+                    .descriptor_type(vk::DescriptorType::SAMPLED_IMAGE)
+                    .descriptor_count(1)
+                    .stage_flags(vk::ShaderStageFlags::FRAGMENT)
+                    .build(),
+                vk::DescriptorSetLayoutBinding::builder()
+                    .binding(1)
+                    .descriptor_type(vk::DescriptorType::SAMPLER)
+                    .descriptor_count(1)
+                    .stage_flags(vk::ShaderStageFlags::FRAGMENT)
+                    .build(),
+            ],
+        );
+        let font = TextureArray::new(
+            core.clone(),
+            memory,
+            command,
+            ATLAS_SIZE,
+            ATLAS_SIZE,
+            &[atlas_pixels()],
+            // font pixels stay square blocks at any scale
+            TextureFilter::Nearest,
+        );
+        descriptor.write_image(
+            0,
+            vk::DescriptorType::SAMPLED_IMAGE,
+            memory.images[font.image_index].view,
+            vk::Sampler::null(),
+            vk::ImageLayout::SHADER_READ_ONLY_OPTIMAL,
+        );
+        descriptor.write_image(
+            1,
+            vk::DescriptorType::SAMPLER,
+            vk::ImageView::null(),
+            font.sampler,
+            vk::ImageLayout::UNDEFINED,
+        );
+        let set_layouts = [descriptor.set_layout];
+        let push_constant_ranges = [vk::PushConstantRange::builder()
+            .stage_flags(vk::ShaderStageFlags::VERTEX)
+            .offset(0)
+            .size(mem::size_of::<glm::Mat4>() as u32)
+            .build()];
+        let pipeline_layout_info = vk::PipelineLayoutCreateInfo::builder()
+            .set_layouts(&set_layouts)
+            .push_constant_ranges(&push_constant_ranges)
+            .build();
+        let pipeline_layout = unsafe {
+            core.logical_device
+                .create_pipeline_layout(&pipeline_layout_info, None)
+                .expect("Failed to create pipeline layout")
+        };
+        let mut pipeline = Pipeline::new(core.clone());
+        pipeline.create_graphic_pipeline(
+            &UI_VERT,
+            &UI_FRAG,
+            PipelineConfig::for_pass(render_graph, ui_pass(), pipeline_layout)
+                .variant(PipelineVariant::FullScreen)
+                .blend_mode(BlendMode::Alpha),
+            &[UiVertex::get_binding_description()],
+            &UiVertex::get_attribute_descriptions(),
+        );
+        let vertex_buffers = (0..MAX_FRAMES_IN_FLIGHT)
+            .map(|_| {
+                memory.create_buffer(
+                    (MAX_GLYPHS * VERTICES_PER_GLYPH * mem::size_of::<UiVertex>())
+                        as vk::DeviceSize,
+                    AllocationType::Buffer,
+                    vk::BufferUsageFlags::VERTEX_BUFFER,
+                    vk::MemoryPropertyFlags::HOST_VISIBLE,
+                )
+            })
+            .collect();
+        let mut ui_render_system = UiRenderSystem {
+            core,
+            _font: font,
+            descriptor,
+            pipeline_layout,
+            pipeline,
+            vertex_buffers,
+            camera: Camera::new(),
+            vertices: vec![],
+        };
+        ui_render_system.set_extent(render_graph.extent(ui_pass()));
+        ui_render_system
+    }
+    /// Queues `text` with its top left corner at `position`, in pixels
+    /// from the top left of the screen.
+    pub fn draw_text(&mut self, text: &str, position: glm::Vec2, style: &TextStyle) {
+        let room = MAX_GLYPHS * VERTICES_PER_GLYPH - self.vertices.len();
+        let vertices = layout_text(text, position, style);
+        self.vertices
+            .extend_from_slice(&vertices[..vertices.len().min(room)]);
+    }
+    pub fn clear_text(&mut self) {
+        self.vertices.clear();
+    }
+    fn set_extent(&mut self, extent: vk::Extent2D) {
+        // bottom and top are swapped from the usual, since Vulkan's y axis
+        // points down the screen like the pixel rows
+        self.camera.set_orthographic_projection(
+            0.0,
+            extent.width as f32,
+            0.0,
+            extent.height as f32,
+            -1.0,
+            1.0,
+        );
+    }
+}
+impl RenderSystem for UiRenderSystem {
+    fn stage(&self) -> RenderStage {
+        RenderStage::Ui
+    }
+    fn pass(&self) -> &'static str {
+        ui_pass()
+    }
+    fn render(&mut self, frame: &mut FrameInfo) {
+        if self.vertices.is_empty() {
+            return;
+        }
+        let vertex_buffer = self.vertex_buffers[frame.frame_index as usize];
+        frame.memory.copy_memory(
+            None,
+            vertex_buffer,
+            0,
+            mem::size_of_val(self.vertices.as_slice()) as vk::DeviceSize,
+            self.vertices.as_ptr() as *const u8,
+        );
+        let projection = self.camera.projection;
+        let device = &self.core.logical_device;
+        let command_buffer = frame.command_buffer;
+        unsafe {
+            device.cmd_bind_pipeline(
+                command_buffer,
+                vk::PipelineBindPoint::GRAPHICS,
+                self.pipeline.graphic_pipeline,
+            );
+            device.cmd_bind_descriptor_sets(
+                command_buffer,
+                vk::PipelineBindPoint::GRAPHICS,
+                self.pipeline_layout,
+                0,
+                &[self.descriptor.set],
+                &[],
+            );
+            device.cmd_push_constants(
+                command_buffer,
+                self.pipeline_layout,
+                vk::ShaderStageFlags::VERTEX,
+                0,
+                std::slice::from_raw_parts(
+                    &projection as *const glm::Mat4 as *const u8,
+                    mem::size_of::<glm::Mat4>(),
+                ),
+            );
+            device.cmd_bind_vertex_buffers(
+                command_buffer,
+                0,
+                &[frame.memory.buffers[vertex_buffer].handle],
+                &[0],
+            );
+            device.cmd_draw(command_buffer, self.vertices.len() as u32, 1, 0, 0);
+        }
+    }
+    fn render_graph_changed(&mut self, render_graph: &RenderGraph) {
+        self.pipeline.rebuild_for_pass(render_graph, ui_pass());
+        self.set_extent(render_graph.extent(ui_pass()));
+    }
+    fn reload_shaders(&mut self) {
+        self.pipeline.reload_if_changed();
+    }
+    fn as_any_mut(&mut self) -> &mut dyn Any {
+        self
+    }
+}
+impl Drop for UiRenderSystem {
+    fn drop(&mut self) {
+        unsafe {
+            self.core
+                .logical_device
+                .destroy_pipeline_layout(self.pipeline_layout, None);
+        }
+    }
+}
+
+#[cfg(test)]
+mod ui_render_system_tests {
+    use super::*;
+    #[test]
+    fn text_is_laid_out_in_rows_of_glyph_cells() {
+        let style = TextStyle {
+            shadow: None,
+            ..TextStyle::default()
+        };
+        let vertices = layout_text("Hi y\nA", glm::vec2(10.0, 20.0), &style);
+        // the space only advances
+        assert_eq!(vertices.len(), 4 * VERTICES_PER_GLYPH);
+        let glyph_size = GLYPH_SIZE as f32 * style.scale;
+        let top_lefts: Vec<glm::Vec2> = vertices
+            .chunks(VERTICES_PER_GLYPH)
+            .map(|quad| quad[0].position)
+            .collect();
+        assert_eq!(
+            top_lefts,
+            vec![
+                glm::vec2(10.0, 20.0),
+                glm::vec2(10.0 + glyph_size, 20.0),
+                glm::vec2(10.0 + 3.0 * glyph_size, 20.0),
+                glm::vec2(10.0, 20.0 + LINE_HEIGHT as f32 * style.scale),
+            ]
+        );
+        // 'A' is in column 1, row 4 of the atlas
+        let a = &vertices[3 * VERTICES_PER_GLYPH..];
+        assert_eq!(a[0].uv, glm::vec2(1.0, 4.0) / ATLAS_GLYPHS as f32);
+        assert_eq!(a[2].uv, glm::vec2(2.0, 5.0) / ATLAS_GLYPHS as f32);
+        assert_eq!(
+            a[2].position - a[0].position,
+            glm::vec2(glyph_size, glyph_size)
+        );
+    }
+    #[test]
+    fn shadows_are_drawn_first_and_offset_by_a_font_pixel() {
+        let style = TextStyle::default();
+        let vertices = layout_text("ok", glm::vec2(0.0, 0.0), &style);
+        assert_eq!(vertices.len(), 4 * VERTICES_PER_GLYPH);
+        let (shadows, text) = vertices.split_at(2 * VERTICES_PER_GLYPH);
+        assert!(shadows
+            .iter()
+            .all(|vertex| Some(vertex.color) == style.shadow));
+        assert!(text.iter().all(|vertex| vertex.color == style.color));
+        for (shadow, glyph) in shadows.iter().zip(text) {
+            assert_eq!(
+                shadow.position - glyph.position,
+                glm::vec2(style.scale, style.scale)
+            );
+            assert_eq!(shadow.uv, glyph.uv);
+        }
+    }
+}
@@ -1,28 +1,63 @@
+pub mod render_graph;
 mod swap_chain;
 use crate::command::Command;
 use crate::core::Core;
+use crate::game::game_objects::frustum::{CullingStats, Frustum};
+use crate::memory::Memory;
+use crate::render_systems::{CameraInfo, FrameInfo, RenderSystems};
 use ash::vk;
+use crate::game::settings::PostEffect;
+use crate::render_systems::post_process_system::{pass_name, SCENE_FORMAT};
+use crate::render_systems::shadow_map::{CASCADE_COUNT, CASCADE_SIZE, SHADOW_MAP_FORMAT};
+use crate::render_systems::ssao_system::{SSAO_BLUR_PASS, SSAO_DEPTH_PASS, SSAO_FORMAT, SSAO_PASS};
+use crate::render_systems::{Fog, Lighting};
+use render_graph::{
+    clamp_sample_count, ImageDesc, ImageId, ImageSize, LoadOp, PassDesc, RenderGraph,
+    RenderGraphBuilder, MAIN_PASS, SHADOW_PASS,
+};
 use std::rc::Rc;
+use std::time;
 use swap_chain::SwapChain;
 pub struct Renderer {
     pub swap_chain: SwapChain,
+    pub render_graph: RenderGraph,
+    /// The cascaded shadow atlas, sampled by the main pass.
+    pub shadow_map: ImageId,
+    /// The HDR colour the world is drawn to, read by the post-process chain.
+    pub scene: ImageId,
+    /// How many samples the world is drawn with before it is resolved into
+    /// `scene`.
+    pub samples: vk::SampleCountFlags,
     is_frame_started: bool,
     core: Rc<Core>,
     pub command: Command,
     pub current_frame_index: u32,
     pub current_image_index: u32,
+    pub render_systems: RenderSystems,
+    /// When the renderer was created, the zero of `FrameInfo::time`.
+    started: time::Instant,
 }
 impl Renderer {
-    pub fn new(core: Rc<Core>, window_extent: vk::Extent2D) -> Self {
+    /// `samples` is clamped to what the device supports.
+    pub fn new(core: Rc<Core>, window_extent: vk::Extent2D, samples: u32) -> Self {
+        let samples = clamp_sample_count(samples, core.supported_sample_counts());
         let swap_chain = SwapChain::new(core.clone(), &window_extent, None);
+        let (render_graph, shadow_map, scene) =
+            build_render_graph(core.clone(), &swap_chain, samples);
         let command = Command::new(&core);
         Renderer {
             core,
             swap_chain: swap_chain,
+            render_graph,
+            shadow_map,
+            scene,
+            samples,
             is_frame_started: false,
             command,
             current_frame_index: 0,
             current_image_index: 0,
+            render_systems: RenderSystems::default(),
+            started: time::Instant::now(),
         }
     }
     pub fn recreate_swap_chain(&mut self, window_extent: vk::Extent2D) {
@@ -35,9 +70,29 @@ impl Renderer {
             Some(self.swap_chain.swap_chain),
         );
         self.swap_chain = new;
+        self.render_graph.resize(&self.swap_chain);
+        self.render_systems.render_graph_changed(&self.render_graph);
     }
-    pub fn get_render_pass(&self) -> &vk::RenderPass {
-        self.swap_chain.get_render_pass()
+    /// Draws the world with `samples` samples from the next frame on,
+    /// clamped to what the device supports. Recreates the swap chain and
+    /// rebuilds the render graph, and with it every pipeline drawing in it.
+    pub fn set_samples(&mut self, samples: u32, window_extent: vk::Extent2D) {
+        unsafe {
+            self.core.logical_device.device_wait_idle().unwrap();
+        }
+        self.samples = clamp_sample_count(samples, self.core.supported_sample_counts());
+        let new = SwapChain::new(
+            self.core.clone(),
+            &window_extent,
+            Some(self.swap_chain.swap_chain),
+        );
+        self.swap_chain = new;
+        let (render_graph, shadow_map, scene) =
+            build_render_graph(self.core.clone(), &self.swap_chain, self.samples);
+        self.render_graph = render_graph;
+        self.shadow_map = shadow_map;
+        self.scene = scene;
+        self.render_systems.render_graph_changed(&self.render_graph);
     }
     pub fn get_current_command_buffer(&self) -> vk::CommandBuffer {
         self.command.command_buffers[self.current_frame_index as usize]
@@ -97,76 +152,51 @@ impl Renderer {
         self.current_frame_index =
             (self.current_frame_index + 1) % swap_chain::MAX_FRAMES_IN_FLIGHT as u32;
     }
-    pub fn begin_render_pass(&mut self, command_buffer: vk::CommandBuffer) {
-        assert!(
-            self.is_frame_started,
-            "Can't call begin_render_pass if frame is not in progress"
-        );
-
-        assert!(
-            command_buffer == self.get_current_command_buffer(),
-            "Can't begin render pass on command buffer from a different frame"
-        );
-        let render_pass_info = vk::RenderPassBeginInfo::builder()
-            .render_pass(*self.get_render_pass())
-            .framebuffer(self.swap_chain.frame_buffers[self.current_image_index as usize])
-            .render_area(vk::Rect2D {
-                offset: vk::Offset2D { x: 0, y: 0 },
-                extent: self.swap_chain.swap_chain_extent,
-            })
-            .clear_values(&[
-                vk::ClearValue {
-                    color: vk::ClearColorValue {
-                        float32: [0.060, 0.014, 0.700, 1.0],
-                    },
-                },
-                vk::ClearValue {
-                    depth_stencil: vk::ClearDepthStencilValue {
-                        depth: 1.0,
-                        stencil: 0,
-                    },
-                },
-            ]);
-        unsafe {
-            self.core.logical_device.cmd_begin_render_pass(
-                command_buffer,
-                &render_pass_info,
-                vk::SubpassContents::INLINE,
-            );
+    /// Records and submits one frame, running the render systems of each
+    /// render graph pass in stage order. Returns `None` when the swap chain
+    /// is out of date.
+    pub fn draw_frame(
+        &mut self,
+        camera: CameraInfo,
+        lighting: Lighting,
+        fog: Fog,
+        memory: &mut Memory,
+    ) -> Option<CullingStats> {
+        self.render_systems.reload_shaders();
+        let command_buffer = self.begin_frame();
+        if command_buffer == vk::CommandBuffer::null() {
+            return None;
         }
-        let viewport = vk::Viewport::builder()
-            .x(0.0)
-            .y(0.0)
-            .width(self.swap_chain.swap_chain_extent.width as f32)
-            .height(self.swap_chain.swap_chain_extent.height as f32)
-            .min_depth(0.0)
-            .max_depth(1.0)
-            .build();
-        let scissor = vk::Rect2D::builder()
-            .offset(vk::Offset2D { x: 0, y: 0 })
-            .extent(self.swap_chain.swap_chain_extent)
-            .build();
-        unsafe {
-            self.core
-                .logical_device
-                .cmd_set_viewport(command_buffer, 0, &[viewport]);
-            self.core
-                .logical_device
-                .cmd_set_scissor(command_buffer, 0, &[scissor]);
-        }
-    }
-    pub fn end_render_pass(&mut self, command_buffer: vk::CommandBuffer) {
-        assert!(
-            self.is_frame_started,
-            "Can't call end_render_pass if frame is not in progress"
-        );
-        assert!(
-            command_buffer == self.get_current_command_buffer(),
-            "Can't begin render pass on command buffer from a different frame"
+        let mut frame = FrameInfo {
+            command_buffer,
+            frame_index: self.current_frame_index,
+            camera,
+            frustum: Frustum::from_matrix(&camera.proj_view),
+            lighting,
+            fog,
+            time: self.started.elapsed().as_secs_f32(),
+            memory,
+            culling_stats: CullingStats::default(),
+        };
+        let color = fog.color;
+        self.render_graph.set_clear_value(
+            MAIN_PASS,
+            self.scene,
+            vk::ClearValue {
+                color: vk::ClearColorValue {
+                    float32: [color.x, color.y, color.z, 1.0],
+                },
+            },
         );
-        unsafe {
-            self.core.logical_device.cmd_end_render_pass(command_buffer);
-        }
+        self.render_systems.prepare(&mut frame);
+        let render_systems = &mut self.render_systems;
+        self.render_graph
+            .execute(command_buffer, self.current_image_index, |pass, _| {
+                render_systems.render(&mut frame, pass);
+            });
+        let culling_stats = frame.culling_stats;
+        self.end_frame();
+        Some(culling_stats)
     }
 }
 
@@ -182,3 +212,137 @@ impl Drop for Renderer {
         }
     }
 }
+
+/// The passes of a frame: the sun's shadow atlas, a depth prepass from the
+/// camera with the ambient occlusion computed and blurred from it, the
+/// world in HDR, then one pass per post-process effect, ping-ponging between two images until
+/// the last writes the swap chain image. With more than one sample the
+/// world is drawn multisampled and resolved into the scene colour. Returns
+/// the shadow atlas and the scene colour too.
+fn build_render_graph(
+    core: Rc<Core>,
+    swap_chain: &SwapChain,
+    samples: vk::SampleCountFlags,
+) -> (RenderGraph, ImageId, ImageId) {
+    let depth_format = core.find_supported_format(
+        vec![
+            vk::Format::D32_SFLOAT,
+            vk::Format::D32_SFLOAT_S8_UINT,
+            vk::Format::D24_UNORM_S8_UINT,
+        ],
+        vk::ImageTiling::OPTIMAL,
+        vk::FormatFeatureFlags::DEPTH_STENCIL_ATTACHMENT,
+    );
+    let mut builder = RenderGraphBuilder::default();
+    let shadow_map = builder.create_image(ImageDesc {
+        format: SHADOW_MAP_FORMAT,
+        size: ImageSize::Fixed(CASCADE_SIZE * CASCADE_COUNT as u32, CASCADE_SIZE),
+        aspect: vk::ImageAspectFlags::DEPTH,
+        samples: vk::SampleCountFlags::TYPE_1,
+    });
+    builder.add_pass(PassDesc::new(SHADOW_PASS).depth(
+        shadow_map,
+        LoadOp::Clear(vk::ClearValue {
+            depth_stencil: vk::ClearDepthStencilValue {
+                depth: 1.0,
+                stencil: 0,
+            },
+        }),
+    ));
+    let depth_clear = LoadOp::Clear(vk::ClearValue {
+        depth_stencil: vk::ClearDepthStencilValue {
+            depth: 1.0,
+            stencil: 0,
+        },
+    });
+    let ssao_depth = builder.create_image(ImageDesc {
+        format: depth_format,
+        size: ImageSize::Swapchain,
+        aspect: vk::ImageAspectFlags::DEPTH,
+        samples: vk::SampleCountFlags::TYPE_1,
+    });
+    builder.add_pass(PassDesc::new(SSAO_DEPTH_PASS).depth(ssao_depth, depth_clear));
+    let ao_desc = ImageDesc {
+        format: SSAO_FORMAT,
+        size: ImageSize::Swapchain,
+        aspect: vk::ImageAspectFlags::COLOR,
+        samples: vk::SampleCountFlags::TYPE_1,
+    };
+    // cleared fully open, which is all that is left when SSAO is off
+    let ao_clear = LoadOp::Clear(vk::ClearValue {
+        color: vk::ClearColorValue {
+            float32: [1.0, 1.0, 1.0, 1.0],
+        },
+    });
+    let ao = builder.create_image(ao_desc);
+    builder.add_pass(
+        PassDesc::new(SSAO_PASS)
+            .color(ao, ao_clear)
+            .sample(ssao_depth),
+    );
+    let blurred_ao = builder.create_image(ao_desc);
+    builder.add_pass(
+        PassDesc::new(SSAO_BLUR_PASS)
+            .color(blurred_ao, ao_clear)
+            .sample(ao),
+    );
+    let scene_desc = ImageDesc {
+        format: SCENE_FORMAT,
+        size: ImageSize::Swapchain,
+        aspect: vk::ImageAspectFlags::COLOR,
+        samples: vk::SampleCountFlags::TYPE_1,
+    };
+    let scene = builder.create_image(scene_desc);
+    let depth = builder.create_image(ImageDesc {
+        format: depth_format,
+        size: ImageSize::Swapchain,
+        aspect: vk::ImageAspectFlags::DEPTH,
+        samples,
+    });
+    let scene_clear = LoadOp::Clear(vk::ClearValue {
+        color: vk::ClearColorValue {
+            float32: [0.060, 0.014, 0.700, 1.0],
+        },
+    });
+    let main_pass = if samples == vk::SampleCountFlags::TYPE_1 {
+        PassDesc::new(MAIN_PASS).color(scene, scene_clear)
+    } else {
+        let multisampled = builder.create_image(ImageDesc {
+            samples,
+            ..scene_desc
+        });
+        PassDesc::new(MAIN_PASS)
+            .color(multisampled, scene_clear)
+            .resolve(scene)
+    };
+    builder.add_pass(
+        main_pass
+            .depth(depth, depth_clear)
+            .sample(shadow_map)
+            .sample(blurred_ao),
+    );
+    let post_targets = [0, 1].map(|_| builder.create_image(scene_desc));
+    let backbuffer = builder.backbuffer();
+    let mut input = scene;
+    for (index, effect) in PostEffect::ALL.iter().enumerate() {
+        let output = if index + 1 == PostEffect::ALL.len() {
+            backbuffer
+        } else {
+            post_targets[index % 2]
+        };
+        builder.add_pass(
+            PassDesc::new(pass_name(*effect))
+                .color(
+                    output,
+                    LoadOp::Clear(vk::ClearValue {
+                        color: vk::ClearColorValue {
+                            float32: [0.0, 0.0, 0.0, 1.0],
+                        },
+                    }),
+                )
+                .sample(input),
+        );
+        input = output;
+    }
+    (builder.build(core, swap_chain), shadow_map, scene)
+}
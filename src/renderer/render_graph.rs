@@ -0,0 +1,1091 @@
+use super::swap_chain::SwapChain;
+use crate::core::Core;
+use ash::vk;
+use std::rc::Rc;
+
+/// Names the pass the world is drawn in.
+pub const MAIN_PASS: &str = "main";
+/// Names the pass that renders the sun's shadow map.
+pub const SHADOW_PASS: &str = "shadow";
+
+/// An image known to a render graph.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct ImageId(usize);
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ImageSize {
+    /// Follows the swap chain, so the image is recreated on resize.
+    Swapchain,
+    Fixed(u32, u32),
+}
+
+/// A transient image the graph allocates and owns.
+#[derive(Debug, Clone, Copy)]
+pub struct ImageDesc {
+    pub format: vk::Format,
+    pub size: ImageSize,
+    pub aspect: vk::ImageAspectFlags,
+    /// Multisampled images can only be drawn to and resolved, not sampled.
+    pub samples: vk::SampleCountFlags,
+}
+
+/// The highest sample count up to `requested` that `supported` allows,
+/// falling back to single sampling.
+pub fn clamp_sample_count(requested: u32, supported: vk::SampleCountFlags) -> vk::SampleCountFlags {
+    [
+        vk::SampleCountFlags::TYPE_8,
+        vk::SampleCountFlags::TYPE_4,
+        vk::SampleCountFlags::TYPE_2,
+    ]
+    .into_iter()
+    .find(|samples| samples.as_raw() <= requested && supported.contains(*samples))
+    .unwrap_or(vk::SampleCountFlags::TYPE_1)
+}
+
+#[derive(Debug, Clone, Copy)]
+enum GraphImage {
+    /// The swap chain image acquired for this frame.
+    Backbuffer,
+    Transient(ImageDesc),
+}
+
+#[allow(dead_code)]
+#[derive(Clone, Copy)]
+pub enum LoadOp {
+    Clear(vk::ClearValue),
+    Load,
+}
+
+#[derive(Clone, Copy)]
+pub struct Attachment {
+    pub image: ImageId,
+    pub load: LoadOp,
+    /// Where a multisampled colour attachment is resolved to at the end of
+    /// the pass.
+    pub resolve: Option<ImageId>,
+}
+
+/// One render pass of the graph and the images it touches. The graph
+/// derives render passes, framebuffers, layouts and barriers from these.
+#[derive(Clone)]
+pub struct PassDesc {
+    pub name: &'static str,
+    pub colors: Vec<Attachment>,
+    pub depth: Option<Attachment>,
+    /// Images read in shaders, e.g. a shadow map or the scene colour.
+    pub sampled: Vec<ImageId>,
+}
+impl PassDesc {
+    pub fn new(name: &'static str) -> Self {
+        PassDesc {
+            name,
+            colors: vec![],
+            depth: None,
+            sampled: vec![],
+        }
+    }
+    pub fn color(mut self, image: ImageId, load: LoadOp) -> Self {
+        self.colors.push(Attachment {
+            image,
+            load,
+            resolve: None,
+        });
+        self
+    }
+    /// Resolves the colour attachment added last into `image`.
+    pub fn resolve(mut self, image: ImageId) -> Self {
+        let attachment = self
+            .colors
+            .last_mut()
+            .expect("Only colour attachments can be resolved");
+        attachment.resolve = Some(image);
+        self
+    }
+    pub fn depth(mut self, image: ImageId, load: LoadOp) -> Self {
+        self.depth = Some(Attachment {
+            image,
+            load,
+            resolve: None,
+        });
+        self
+    }
+    pub fn sample(mut self, image: ImageId) -> Self {
+        self.sampled.push(image);
+        self
+    }
+    fn attachments(&self) -> impl Iterator<Item = &Attachment> {
+        self.colors.iter().chain(self.depth.iter())
+    }
+    fn resolves(&self) -> impl Iterator<Item = ImageId> + '_ {
+        self.colors
+            .iter()
+            .filter_map(|attachment| attachment.resolve)
+    }
+    /// Every image in the framebuffer, in render pass attachment order:
+    /// colours, depth, then resolve targets.
+    fn attachment_images(&self) -> Vec<ImageId> {
+        self.attachments()
+            .map(|attachment| attachment.image)
+            .chain(self.resolves())
+            .collect()
+    }
+    fn uses(&self) -> Vec<(ImageId, Usage)> {
+        let colors = self
+            .colors
+            .iter()
+            .map(|attachment| (attachment.image, Usage::ColorAttachment));
+        let depth = self
+            .depth
+            .iter()
+            .map(|attachment| (attachment.image, Usage::DepthAttachment));
+        let resolves = self.resolves().map(|image| (image, Usage::ColorAttachment));
+        let sampled = self.sampled.iter().map(|image| (*image, Usage::Sampled));
+        colors.chain(depth).chain(resolves).chain(sampled).collect()
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Usage {
+    ColorAttachment,
+    DepthAttachment,
+    Sampled,
+    Present,
+}
+impl Usage {
+    fn state(&self) -> ImageState {
+        match self {
+            Usage::ColorAttachment => ImageState {
+                layout: vk::ImageLayout::COLOR_ATTACHMENT_OPTIMAL,
+                stage: vk::PipelineStageFlags::COLOR_ATTACHMENT_OUTPUT,
+                access: vk::AccessFlags::COLOR_ATTACHMENT_READ
+                    | vk::AccessFlags::COLOR_ATTACHMENT_WRITE,
+            },
+            Usage::DepthAttachment => ImageState {
+                layout: vk::ImageLayout::DEPTH_STENCIL_ATTACHMENT_OPTIMAL,
+                stage: vk::PipelineStageFlags::EARLY_FRAGMENT_TESTS
+                    | vk::PipelineStageFlags::LATE_FRAGMENT_TESTS,
+                access: vk::AccessFlags::DEPTH_STENCIL_ATTACHMENT_READ
+                    | vk::AccessFlags::DEPTH_STENCIL_ATTACHMENT_WRITE,
+            },
+            Usage::Sampled => ImageState {
+                layout: vk::ImageLayout::SHADER_READ_ONLY_OPTIMAL,
+                stage: vk::PipelineStageFlags::VERTEX_SHADER
+                    | vk::PipelineStageFlags::FRAGMENT_SHADER,
+                access: vk::AccessFlags::SHADER_READ,
+            },
+            Usage::Present => ImageState {
+                layout: vk::ImageLayout::PRESENT_SRC_KHR,
+                stage: vk::PipelineStageFlags::BOTTOM_OF_PIPE,
+                access: vk::AccessFlags::empty(),
+            },
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+struct ImageState {
+    layout: vk::ImageLayout,
+    stage: vk::PipelineStageFlags,
+    access: vk::AccessFlags,
+}
+impl ImageState {
+    fn writes(&self) -> bool {
+        self.access.intersects(
+            vk::AccessFlags::COLOR_ATTACHMENT_WRITE
+                | vk::AccessFlags::DEPTH_STENCIL_ATTACHMENT_WRITE
+                | vk::AccessFlags::SHADER_WRITE
+                | vk::AccessFlags::TRANSFER_WRITE,
+        )
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+struct ImageBarrier {
+    image: ImageId,
+    from: ImageState,
+    to: ImageState,
+}
+
+/// The barriers to record before each pass, and after the last one.
+#[derive(Debug, Default)]
+struct BarrierPlan {
+    before_pass: Vec<Vec<ImageBarrier>>,
+    after_last_pass: Vec<ImageBarrier>,
+}
+
+/// Walks the passes in order, tracking each image's layout and last
+/// access, and emits a barrier whenever the layout changes or either side
+/// writes. Every frame starts with undefined contents, except that the
+/// backbuffer's acquire is waited on at the colour output stage. Frames in
+/// flight share the transient images, so the first barrier on each one
+/// waits for its last use in the previous frame.
+fn plan_barriers(images: &[GraphImage], passes: &[PassDesc]) -> BarrierPlan {
+    let last_use = |image: ImageId| {
+        passes.iter().rev().find_map(|pass| {
+            pass.uses()
+                .into_iter()
+                .rev()
+                .find(|(used, _)| *used == image)
+                .map(|(_, usage)| usage.state())
+        })
+    };
+    let mut states: Vec<ImageState> = images
+        .iter()
+        .enumerate()
+        .map(|(index, image)| match image {
+            GraphImage::Backbuffer => ImageState {
+                layout: vk::ImageLayout::UNDEFINED,
+                stage: vk::PipelineStageFlags::COLOR_ATTACHMENT_OUTPUT,
+                access: vk::AccessFlags::empty(),
+            },
+            GraphImage::Transient(_) => match last_use(ImageId(index)) {
+                Some(previous_frame) => ImageState {
+                    layout: vk::ImageLayout::UNDEFINED,
+                    ..previous_frame
+                },
+                None => ImageState {
+                    layout: vk::ImageLayout::UNDEFINED,
+                    stage: vk::PipelineStageFlags::TOP_OF_PIPE,
+                    access: vk::AccessFlags::empty(),
+                },
+            },
+        })
+        .collect();
+    let mut transition = |image: ImageId, usage: Usage, barriers: &mut Vec<ImageBarrier>| {
+        let from = states[image.0];
+        let to = usage.state();
+        if from.layout != to.layout || from.writes() || to.writes() {
+            barriers.push(ImageBarrier { image, from, to });
+        }
+        states[image.0] = to;
+    };
+    let mut plan = BarrierPlan::default();
+    for pass in passes {
+        let mut barriers = vec![];
+        for (image, usage) in pass.uses() {
+            transition(image, usage, &mut barriers);
+        }
+        plan.before_pass.push(barriers);
+    }
+    for (index, image) in images.iter().enumerate() {
+        if let GraphImage::Backbuffer = image {
+            transition(ImageId(index), Usage::Present, &mut plan.after_last_pass);
+        }
+    }
+    plan
+}
+
+/// Collects images and passes, then builds a `RenderGraph`.
+#[derive(Default)]
+pub struct RenderGraphBuilder {
+    images: Vec<GraphImage>,
+    passes: Vec<PassDesc>,
+}
+impl RenderGraphBuilder {
+    /// The swap chain image being rendered this frame.
+    pub fn backbuffer(&mut self) -> ImageId {
+        match self
+            .images
+            .iter()
+            .position(|image| matches!(image, GraphImage::Backbuffer))
+        {
+            Some(index) => ImageId(index),
+            None => {
+                self.images.push(GraphImage::Backbuffer);
+                ImageId(self.images.len() - 1)
+            }
+        }
+    }
+    pub fn create_image(&mut self, desc: ImageDesc) -> ImageId {
+        self.images.push(GraphImage::Transient(desc));
+        ImageId(self.images.len() - 1)
+    }
+    /// Passes run in the order they are added.
+    pub fn add_pass(&mut self, pass: PassDesc) {
+        self.passes.push(pass);
+    }
+    pub fn build(self, core: Rc<Core>, swap_chain: &SwapChain) -> RenderGraph {
+        let plan = plan_barriers(&self.images, &self.passes);
+        let transient_images = self.images.iter().map(|_| None).collect();
+        let mut graph = RenderGraph {
+            core,
+            images: self.images,
+            passes: vec![],
+            plan,
+            transient_images,
+            backbuffer_images: vec![],
+            backbuffer_views: vec![],
+            backbuffer_format: swap_chain.image_format,
+            extent: swap_chain.swap_chain_extent,
+        };
+        for (pass_index, pass) in self.passes.iter().enumerate() {
+            let render_pass = graph.create_render_pass(&self.passes, pass_index);
+            graph.passes.push(CompiledPass {
+                desc: pass.clone(),
+                render_pass,
+                framebuffers: vec![],
+                extent: vk::Extent2D::default(),
+            });
+        }
+        graph.create_size_dependent(swap_chain);
+        graph
+    }
+}
+
+struct TransientImage {
+    image: vk::Image,
+    view: vk::ImageView,
+    memory: vk::DeviceMemory,
+}
+
+struct CompiledPass {
+    desc: PassDesc,
+    render_pass: vk::RenderPass,
+    /// One per swap chain image when the pass draws to the backbuffer.
+    framebuffers: Vec<vk::Framebuffer>,
+    extent: vk::Extent2D,
+}
+
+/// A fixed sequence of render passes over swap chain and transient images.
+/// Transient images exist once and are shared by the frames in flight, which
+/// may overlap on the GPU; the barriers order each frame's use of them after
+/// the previous frame's.
+pub struct RenderGraph {
+    core: Rc<Core>,
+    images: Vec<GraphImage>,
+    passes: Vec<CompiledPass>,
+    plan: BarrierPlan,
+    transient_images: Vec<Option<TransientImage>>,
+    backbuffer_images: Vec<vk::Image>,
+    backbuffer_views: Vec<vk::ImageView>,
+    backbuffer_format: vk::Format,
+    extent: vk::Extent2D,
+}
+impl RenderGraph {
+    pub fn render_pass(&self, name: &str) -> Option<vk::RenderPass> {
+        self.passes
+            .iter()
+            .find(|pass| pass.desc.name == name)
+            .map(|pass| pass.render_pass)
+    }
+    fn pass_desc(&self, pass: &str) -> &PassDesc {
+        self.passes
+            .iter()
+            .find(|compiled| compiled.desc.name == pass)
+            .map(|compiled| &compiled.desc)
+            .unwrap_or_else(|| panic!("No render pass named {}", pass))
+    }
+    /// The images `pass` reads in shaders, in the order they were added.
+    pub fn sampled_images(&self, pass: &str) -> &[ImageId] {
+        &self.pass_desc(pass).sampled
+    }
+    /// The first colour attachment `pass` draws to.
+    pub fn color_image(&self, pass: &str) -> Option<ImageId> {
+        self.pass_desc(pass)
+            .colors
+            .first()
+            .map(|attachment| attachment.image)
+    }
+    pub fn depth_image(&self, pass: &str) -> Option<ImageId> {
+        self.pass_desc(pass)
+            .depth
+            .map(|attachment| attachment.image)
+    }
+    /// The size of the images `pass` draws to.
+    pub fn extent(&self, pass: &str) -> vk::Extent2D {
+        self.passes
+            .iter()
+            .find(|compiled| compiled.desc.name == pass)
+            .map(|compiled| compiled.extent)
+            .unwrap_or_else(|| panic!("No render pass named {}", pass))
+    }
+    /// The sample count pipelines drawing in `pass` must be built with.
+    pub fn samples(&self, pass: &str) -> vk::SampleCountFlags {
+        self.pass_desc(pass)
+            .attachments()
+            .next()
+            .map_or(vk::SampleCountFlags::TYPE_1, |attachment| {
+                self.image_samples(attachment.image)
+            })
+    }
+    /// Changes what `image`, or the multisampled attachment resolved into
+    /// it, is cleared to at the start of `pass`. The attachment must already
+    /// be cleared rather than loaded, since that is baked into the render
+    /// pass.
+    pub fn set_clear_value(&mut self, pass: &str, image: ImageId, clear_value: vk::ClearValue) {
+        let pass = self
+            .passes
+            .iter_mut()
+            .find(|compiled| compiled.desc.name == pass)
+            .unwrap_or_else(|| panic!("No render pass named {}", pass));
+        let attachment = pass
+            .desc
+            .colors
+            .iter_mut()
+            .chain(pass.desc.depth.iter_mut())
+            .find(|attachment| attachment.image == image || attachment.resolve == Some(image))
+            .expect("The pass has no such attachment");
+        match attachment.load {
+            LoadOp::Clear(_) => attachment.load = LoadOp::Clear(clear_value),
+            LoadOp::Load => panic!("Cannot set the clear value of a loaded attachment"),
+        }
+    }
+    /// The view of a transient image, for descriptors that sample it.
+    /// Swap-chain-sized images get a new view when the swap chain is
+    /// recreated; fixed-size ones keep theirs for the life of the graph.
+    pub fn image_view(&self, image: ImageId) -> vk::ImageView {
+        match self.transient_images[image.0] {
+            Some(ref transient) => transient.view,
+            None => self.backbuffer_views[0],
+        }
+    }
+    /// Recreates the swap-chain-sized images and every framebuffer. Render
+    /// passes are kept, so pipelines built against them stay valid.
+    pub fn resize(&mut self, swap_chain: &SwapChain) {
+        self.destroy_size_dependent(false);
+        self.create_size_dependent(swap_chain);
+    }
+    /// Records every pass with its barriers. `record` is called inside each
+    /// render pass with the pass name.
+    pub fn execute(
+        &self,
+        command_buffer: vk::CommandBuffer,
+        image_index: u32,
+        mut record: impl FnMut(&'static str, vk::CommandBuffer),
+    ) {
+        let device = &self.core.logical_device;
+        for (pass, barriers) in self.passes.iter().zip(self.plan.before_pass.iter()) {
+            self.record_barriers(command_buffer, image_index, barriers);
+            let clear_values: Vec<vk::ClearValue> = pass
+                .desc
+                .attachments()
+                .map(|attachment| match attachment.load {
+                    LoadOp::Clear(clear_value) => clear_value,
+                    LoadOp::Load => vk::ClearValue::default(),
+                })
+                .collect();
+            let framebuffer = if pass.framebuffers.len() > 1 {
+                pass.framebuffers[image_index as usize]
+            } else {
+                pass.framebuffers[0]
+            };
+            let render_area = vk::Rect2D {
+                offset: vk::Offset2D { x: 0, y: 0 },
+                extent: pass.extent,
+            };
+            let render_pass_info = vk::RenderPassBeginInfo::builder()
+                .render_pass(pass.render_pass)
+                .framebuffer(framebuffer)
+                .render_area(render_area)
+                .clear_values(&clear_values)
+                .build();
+            let viewport = vk::Viewport::builder()
+                .x(0.0)
+                .y(0.0)
+                .width(pass.extent.width as f32)
+                .height(pass.extent.height as f32)
+                .min_depth(0.0)
+                .max_depth(1.0)
+                .build();
+            unsafe {
+                device.cmd_begin_render_pass(
+                    command_buffer,
+                    &render_pass_info,
+                    vk::SubpassContents::INLINE,
+                );
+                device.cmd_set_viewport(command_buffer, 0, &[viewport]);
+                device.cmd_set_scissor(command_buffer, 0, &[render_area]);
+            }
+            record(pass.desc.name, command_buffer);
+            unsafe {
+                device.cmd_end_render_pass(command_buffer);
+            }
+        }
+        self.record_barriers(command_buffer, image_index, &self.plan.after_last_pass);
+    }
+    fn record_barriers(
+        &self,
+        command_buffer: vk::CommandBuffer,
+        image_index: u32,
+        barriers: &[ImageBarrier],
+    ) {
+        if barriers.is_empty() {
+            return;
+        }
+        let mut src_stage = vk::PipelineStageFlags::empty();
+        let mut dst_stage = vk::PipelineStageFlags::empty();
+        let image_barriers: Vec<vk::ImageMemoryBarrier> = barriers
+            .iter()
+            .map(|barrier| {
+                src_stage |= barrier.from.stage;
+                dst_stage |= barrier.to.stage;
+                let (image, aspect_mask) = match self.images[barrier.image.0] {
+                    GraphImage::Backbuffer => (
+                        self.backbuffer_images[image_index as usize],
+                        vk::ImageAspectFlags::COLOR,
+                    ),
+                    GraphImage::Transient(desc) => (
+                        self.transient_images[barrier.image.0]
+                            .as_ref()
+                            .unwrap()
+                            .image,
+                        desc.aspect,
+                    ),
+                };
+                vk::ImageMemoryBarrier::builder()
+                    .src_access_mask(barrier.from.access)
+                    .dst_access_mask(barrier.to.access)
+                    .old_layout(barrier.from.layout)
+                    .new_layout(barrier.to.layout)
+                    .src_queue_family_index(vk::QUEUE_FAMILY_IGNORED)
+                    .dst_queue_family_index(vk::QUEUE_FAMILY_IGNORED)
+                    .image(image)
+                    .subresource_range(vk::ImageSubresourceRange {
+                        aspect_mask,
+                        base_mip_level: 0,
+                        level_count: 1,
+                        base_array_layer: 0,
+                        layer_count: 1,
+                    })
+                    .build()
+            })
+            .collect();
+        unsafe {
+            self.core.logical_device.cmd_pipeline_barrier(
+                command_buffer,
+                src_stage,
+                dst_stage,
+                vk::DependencyFlags::empty(),
+                &[],
+                &[],
+                &image_barriers,
+            );
+        }
+    }
+    fn format(&self, image: ImageId) -> vk::Format {
+        match self.images[image.0] {
+            GraphImage::Backbuffer => self.backbuffer_format,
+            GraphImage::Transient(desc) => desc.format,
+        }
+    }
+    fn image_samples(&self, image: ImageId) -> vk::SampleCountFlags {
+        match self.images[image.0] {
+            GraphImage::Backbuffer => vk::SampleCountFlags::TYPE_1,
+            GraphImage::Transient(desc) => desc.samples,
+        }
+    }
+    /// Whether a pass after `pass_index` reads `image`, or it is presented.
+    fn is_read_later(&self, image: ImageId, pass_index: usize, passes: &[PassDesc]) -> bool {
+        matches!(self.images[image.0], GraphImage::Backbuffer)
+            || passes[pass_index + 1..]
+                .iter()
+                .any(|pass| pass.uses().iter().any(|(used, _)| *used == image))
+    }
+    /// Attachments stay in the layout the barriers put them in; the graph
+    /// does all transitions itself. Resolve targets are written whole, so
+    /// they are never loaded.
+    fn create_render_pass(&self, passes: &[PassDesc], pass_index: usize) -> vk::RenderPass {
+        let pass = &passes[pass_index];
+        let describe = |image: ImageId, load: vk::AttachmentLoadOp, layout: vk::ImageLayout| {
+            vk::AttachmentDescription::builder()
+                .format(self.format(image))
+                .samples(self.image_samples(image))
+                .load_op(load)
+                .store_op(if self.is_read_later(image, pass_index, passes) {
+                    vk::AttachmentStoreOp::STORE
+                } else {
+                    vk::AttachmentStoreOp::DONT_CARE
+                })
+                .stencil_load_op(vk::AttachmentLoadOp::DONT_CARE)
+                .stencil_store_op(vk::AttachmentStoreOp::DONT_CARE)
+                .initial_layout(layout)
+                .final_layout(layout)
+                .build()
+        };
+        let load_op = |attachment: &Attachment| match attachment.load {
+            LoadOp::Clear(_) => vk::AttachmentLoadOp::CLEAR,
+            LoadOp::Load => vk::AttachmentLoadOp::LOAD,
+        };
+        let color_layout = vk::ImageLayout::COLOR_ATTACHMENT_OPTIMAL;
+        let depth_layout = vk::ImageLayout::DEPTH_STENCIL_ATTACHMENT_OPTIMAL;
+        let mut attachments: Vec<vk::AttachmentDescription> = pass
+            .colors
+            .iter()
+            .map(|attachment| describe(attachment.image, load_op(attachment), color_layout))
+            .collect();
+        let color_refs: Vec<vk::AttachmentReference> = (0..pass.colors.len())
+            .map(|index| vk::AttachmentReference {
+                attachment: index as u32,
+                layout: color_layout,
+            })
+            .collect();
+        let depth_ref = vk::AttachmentReference {
+            attachment: attachments.len() as u32,
+            layout: depth_layout,
+        };
+        if let Some(ref depth) = pass.depth {
+            attachments.push(describe(depth.image, load_op(depth), depth_layout));
+        }
+        let resolve_refs: Vec<vk::AttachmentReference> = pass
+            .colors
+            .iter()
+            .map(|attachment| match attachment.resolve {
+                Some(image) => {
+                    attachments.push(describe(
+                        image,
+                        vk::AttachmentLoadOp::DONT_CARE,
+                        color_layout,
+                    ));
+                    vk::AttachmentReference {
+                        attachment: attachments.len() as u32 - 1,
+                        layout: color_layout,
+                    }
+                }
+                None => vk::AttachmentReference {
+                    attachment: vk::ATTACHMENT_UNUSED,
+                    layout: vk::ImageLayout::UNDEFINED,
+                },
+            })
+            .collect();
+        let mut subpass = vk::SubpassDescription::builder()
+            .pipeline_bind_point(vk::PipelineBindPoint::GRAPHICS)
+            .color_attachments(&color_refs);
+        if pass.depth.is_some() {
+            subpass = subpass.depth_stencil_attachment(&depth_ref);
+        }
+        if pass.resolves().next().is_some() {
+            subpass = subpass.resolve_attachments(&resolve_refs);
+        }
+        let subpasses = [subpass.build()];
+        let create_info = vk::RenderPassCreateInfo::builder()
+            .attachments(&attachments)
+            .subpasses(&subpasses)
+            .build();
+        unsafe {
+            self.core
+                .logical_device
+                .create_render_pass(&create_info, None)
+                .expect("Failed to create render pass")
+        }
+    }
+    fn create_size_dependent(&mut self, swap_chain: &SwapChain) {
+        self.extent = swap_chain.swap_chain_extent;
+        self.backbuffer_images = swap_chain.images.clone();
+        self.backbuffer_views = swap_chain.image_views.clone();
+        for index in 0..self.images.len() {
+            if let GraphImage::Transient(desc) = self.images[index] {
+                if self.transient_images[index].is_none() {
+                    self.transient_images[index] = Some(self.create_transient_image(&desc));
+                }
+            }
+        }
+        for pass_index in 0..self.passes.len() {
+            let desc = self.passes[pass_index].desc.clone();
+            let images = desc.attachment_images();
+            let uses_backbuffer = images
+                .iter()
+                .any(|image| matches!(self.images[image.0], GraphImage::Backbuffer));
+            let extent = images
+                .first()
+                .map(|image| self.image_extent(*image))
+                .unwrap_or(self.extent);
+            let framebuffer_count = if uses_backbuffer {
+                self.backbuffer_views.len()
+            } else {
+                1
+            };
+            let framebuffers = (0..framebuffer_count)
+                .map(|image_index| {
+                    let views: Vec<vk::ImageView> = images
+                        .iter()
+                        .map(|image| match self.transient_images[image.0] {
+                            Some(ref transient) => transient.view,
+                            None => self.backbuffer_views[image_index],
+                        })
+                        .collect();
+                    let create_info = vk::FramebufferCreateInfo::builder()
+                        .render_pass(self.passes[pass_index].render_pass)
+                        .attachments(&views)
+                        .width(extent.width)
+                        .height(extent.height)
+                        .layers(1)
+                        .build();
+                    unsafe {
+                        self.core
+                            .logical_device
+                            .create_framebuffer(&create_info, None)
+                            .expect("Failed to create frame buffer")
+                    }
+                })
+                .collect();
+            self.passes[pass_index].framebuffers = framebuffers;
+            self.passes[pass_index].extent = extent;
+        }
+    }
+    fn image_extent(&self, image: ImageId) -> vk::Extent2D {
+        match self.images[image.0] {
+            GraphImage::Transient(ImageDesc {
+                size: ImageSize::Fixed(width, height),
+                ..
+            }) => vk::Extent2D { width, height },
+            _ => self.extent,
+        }
+    }
+    fn create_transient_image(&self, desc: &ImageDesc) -> TransientImage {
+        let extent = match desc.size {
+            ImageSize::Swapchain => self.extent,
+            ImageSize::Fixed(width, height) => vk::Extent2D { width, height },
+        };
+        let attachment_usage = if desc.aspect.contains(vk::ImageAspectFlags::DEPTH) {
+            vk::ImageUsageFlags::DEPTH_STENCIL_ATTACHMENT
+        } else {
+            vk::ImageUsageFlags::COLOR_ATTACHMENT
+        };
+        let usage = if desc.samples == vk::SampleCountFlags::TYPE_1 {
+            attachment_usage | vk::ImageUsageFlags::SAMPLED
+        } else {
+            attachment_usage
+        };
+        let image_create_info = vk::ImageCreateInfo::builder()
+            .image_type(vk::ImageType::TYPE_2D)
+            .extent(vk::Extent3D {
+                width: extent.width,
+                height: extent.height,
+                depth: 1,
+            })
+            .mip_levels(1)
+            .array_layers(1)
+            .format(desc.format)
+            .tiling(vk::ImageTiling::OPTIMAL)
+            .initial_layout(vk::ImageLayout::UNDEFINED)
+            .usage(usage)
+            .sharing_mode(vk::SharingMode::EXCLUSIVE)
+            .samples(desc.samples)
+            .build();
+        let device = &self.core.logical_device;
+        let image = unsafe {
+            device
+                .create_image(&image_create_info, None)
+                .expect("Failed to create image")
+        };
+        let memory_requirements = unsafe { device.get_image_memory_requirements(image) };
+        let memory_type_index = self
+            .core
+            .find_memory_type(
+                memory_requirements.memory_type_bits,
+                vk::MemoryPropertyFlags::DEVICE_LOCAL,
+            )
+            .expect("Failed to find memory for a render graph image");
+        let alloc_info = vk::MemoryAllocateInfo::builder()
+            .allocation_size(memory_requirements.size)
+            .memory_type_index(memory_type_index)
+            .build();
+        let memory = unsafe {
+            device
+                .allocate_memory(&alloc_info, None)
+                .expect("Failed to allocate image memory")
+        };
+        unsafe {
+            device
+                .bind_image_memory(image, memory, 0)
+                .expect("Failed to bind image memory");
+        }
+        let view_info = vk::ImageViewCreateInfo::builder()
+            .image(image)
+            .view_type(vk::ImageViewType::TYPE_2D)
+            .format(desc.format)
+            .subresource_range(vk::ImageSubresourceRange {
+                aspect_mask: desc.aspect,
+                base_mip_level: 0,
+                level_count: 1,
+                base_array_layer: 0,
+                layer_count: 1,
+            })
+            .build();
+        let view = unsafe {
+            device
+                .create_image_view(&view_info, None)
+                .expect("Failed to create image view")
+        };
+        TransientImage {
+            image,
+            view,
+            memory,
+        }
+    }
+    /// Destroys the framebuffers and the swap-chain-sized images, or every
+    /// image when `fixed_size` is set too.
+    fn destroy_size_dependent(&mut self, fixed_size: bool) {
+        let device = &self.core.logical_device;
+        unsafe {
+            for pass in self.passes.iter_mut() {
+                for framebuffer in pass.framebuffers.drain(..) {
+                    device.destroy_framebuffer(framebuffer, None);
+                }
+            }
+            for (image, transient) in self.images.iter().zip(self.transient_images.iter_mut()) {
+                let follows_swapchain = matches!(
+                    image,
+                    GraphImage::Transient(ImageDesc {
+                        size: ImageSize::Swapchain,
+                        ..
+                    })
+                );
+                if !follows_swapchain && !fixed_size {
+                    continue;
+                }
+                if let Some(transient) = transient.take() {
+                    device.destroy_image_view(transient.view, None);
+                    device.destroy_image(transient.image, None);
+                    device.free_memory(transient.memory, None);
+                }
+            }
+        }
+    }
+}
+impl Drop for RenderGraph {
+    fn drop(&mut self) {
+        self.destroy_size_dependent(true);
+        for pass in self.passes.iter() {
+            unsafe {
+                self.core
+                    .logical_device
+                    .destroy_render_pass(pass.render_pass, None);
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod render_graph_tests {
+    use super::*;
+    fn depth_desc() -> ImageDesc {
+        ImageDesc {
+            format: vk::Format::D32_SFLOAT,
+            size: ImageSize::Swapchain,
+            aspect: vk::ImageAspectFlags::DEPTH,
+            samples: vk::SampleCountFlags::TYPE_1,
+        }
+    }
+    #[test]
+    fn backbuffer_is_transitioned_for_drawing_and_presenting() {
+        let images = [GraphImage::Backbuffer, GraphImage::Transient(depth_desc())];
+        let passes = [PassDesc::new(MAIN_PASS)
+            .color(ImageId(0), LoadOp::Load)
+            .depth(ImageId(1), LoadOp::Load)];
+        let plan = plan_barriers(&images, &passes);
+        let before: Vec<(ImageId, vk::ImageLayout, vk::ImageLayout)> = plan.before_pass[0]
+            .iter()
+            .map(|barrier| (barrier.image, barrier.from.layout, barrier.to.layout))
+            .collect();
+        assert_eq!(
+            before,
+            vec![
+                (
+                    ImageId(0),
+                    vk::ImageLayout::UNDEFINED,
+                    vk::ImageLayout::COLOR_ATTACHMENT_OPTIMAL
+                ),
+                (
+                    ImageId(1),
+                    vk::ImageLayout::UNDEFINED,
+                    vk::ImageLayout::DEPTH_STENCIL_ATTACHMENT_OPTIMAL
+                ),
+            ]
+        );
+        assert_eq!(plan.after_last_pass.len(), 1);
+        assert_eq!(
+            plan.after_last_pass[0].to.layout,
+            vk::ImageLayout::PRESENT_SRC_KHR
+        );
+    }
+    #[test]
+    fn sampling_a_written_image_waits_for_the_writer() {
+        let images = [
+            GraphImage::Backbuffer,
+            GraphImage::Transient(depth_desc()),
+            GraphImage::Transient(depth_desc()),
+        ];
+        let passes = [
+            PassDesc::new("shadow").depth(ImageId(2), LoadOp::Load),
+            PassDesc::new(MAIN_PASS)
+                .color(ImageId(0), LoadOp::Load)
+                .depth(ImageId(1), LoadOp::Load)
+                .sample(ImageId(2)),
+        ];
+        let plan = plan_barriers(&images, &passes);
+        let shadow_read = plan.before_pass[1]
+            .iter()
+            .find(|barrier| barrier.image == ImageId(2))
+            .unwrap();
+        assert_eq!(
+            shadow_read.from.layout,
+            vk::ImageLayout::DEPTH_STENCIL_ATTACHMENT_OPTIMAL
+        );
+        assert_eq!(
+            shadow_read.to.layout,
+            vk::ImageLayout::SHADER_READ_ONLY_OPTIMAL
+        );
+        assert!(shadow_read
+            .from
+            .access
+            .contains(vk::AccessFlags::DEPTH_STENCIL_ATTACHMENT_WRITE));
+        assert!(shadow_read
+            .to
+            .stage
+            .contains(vk::PipelineStageFlags::FRAGMENT_SHADER));
+    }
+    #[test]
+    fn ping_pong_passes_wait_for_reads_before_writing() {
+        let color = ImageDesc {
+            format: vk::Format::R16G16B16A16_SFLOAT,
+            size: ImageSize::Swapchain,
+            aspect: vk::ImageAspectFlags::COLOR,
+            samples: vk::SampleCountFlags::TYPE_1,
+        };
+        let images = [
+            GraphImage::Backbuffer,
+            GraphImage::Transient(color),
+            GraphImage::Transient(color),
+        ];
+        let passes = [
+            PassDesc::new("first").color(ImageId(1), LoadOp::Load),
+            PassDesc::new("second")
+                .color(ImageId(2), LoadOp::Load)
+                .sample(ImageId(1)),
+            PassDesc::new("third")
+                .color(ImageId(1), LoadOp::Load)
+                .sample(ImageId(2)),
+            PassDesc::new("last")
+                .color(ImageId(0), LoadOp::Load)
+                .sample(ImageId(1)),
+        ];
+        let plan = plan_barriers(&images, &passes);
+        let rewrite = plan.before_pass[2]
+            .iter()
+            .find(|barrier| barrier.image == ImageId(1))
+            .unwrap();
+        assert_eq!(
+            rewrite.from.layout,
+            vk::ImageLayout::SHADER_READ_ONLY_OPTIMAL
+        );
+        assert!(rewrite
+            .from
+            .stage
+            .contains(vk::PipelineStageFlags::FRAGMENT_SHADER));
+        assert!(rewrite.to.writes());
+        let last_read = plan.before_pass[3]
+            .iter()
+            .find(|barrier| barrier.image == ImageId(1))
+            .unwrap();
+        assert!(last_read.from.writes());
+        assert_eq!(plan.after_last_pass[0].image, ImageId(0));
+    }
+    #[test]
+    fn each_frame_waits_for_the_previous_frames_last_use() {
+        let images = [
+            GraphImage::Backbuffer,
+            GraphImage::Transient(depth_desc()),
+            GraphImage::Transient(depth_desc()),
+        ];
+        let passes = [
+            PassDesc::new("shadow").depth(ImageId(2), LoadOp::Load),
+            PassDesc::new(MAIN_PASS)
+                .color(ImageId(0), LoadOp::Load)
+                .depth(ImageId(1), LoadOp::Load)
+                .sample(ImageId(2)),
+        ];
+        let plan = plan_barriers(&images, &passes);
+        let first_write = |image: ImageId, pass: usize| {
+            *plan.before_pass[pass]
+                .iter()
+                .find(|barrier| barrier.image == image)
+                .unwrap()
+        };
+        // the shadow map is rewritten only after last frame sampled it
+        let shadow = first_write(ImageId(2), 0);
+        assert_eq!(shadow.from.layout, vk::ImageLayout::UNDEFINED);
+        assert!(shadow
+            .from
+            .stage
+            .contains(vk::PipelineStageFlags::FRAGMENT_SHADER));
+        assert!(shadow.from.access.contains(vk::AccessFlags::SHADER_READ));
+        // and depth only after last frame's depth tests
+        let depth = first_write(ImageId(1), 1);
+        assert!(depth.from.writes());
+        assert!(depth
+            .from
+            .stage
+            .contains(vk::PipelineStageFlags::LATE_FRAGMENT_TESTS));
+    }
+    #[test]
+    fn repeated_reads_need_no_barrier() {
+        let images = [GraphImage::Backbuffer, GraphImage::Transient(depth_desc())];
+        let passes = [
+            PassDesc::new("first").sample(ImageId(1)),
+            PassDesc::new("second").sample(ImageId(1)),
+        ];
+        let plan = plan_barriers(&images, &passes);
+        assert_eq!(plan.before_pass[0].len(), 1);
+        assert!(plan.before_pass[1].is_empty());
+    }
+    #[test]
+    fn resolve_targets_are_written_as_colour_attachments() {
+        let multisampled = ImageDesc {
+            format: vk::Format::R16G16B16A16_SFLOAT,
+            size: ImageSize::Swapchain,
+            aspect: vk::ImageAspectFlags::COLOR,
+            samples: vk::SampleCountFlags::TYPE_4,
+        };
+        let images = [
+            GraphImage::Backbuffer,
+            GraphImage::Transient(multisampled),
+            GraphImage::Transient(ImageDesc {
+                samples: vk::SampleCountFlags::TYPE_1,
+                ..multisampled
+            }),
+        ];
+        let passes = [
+            PassDesc::new(MAIN_PASS)
+                .color(ImageId(1), LoadOp::Load)
+                .resolve(ImageId(2)),
+            PassDesc::new("post")
+                .color(ImageId(0), LoadOp::Load)
+                .sample(ImageId(2)),
+        ];
+        assert_eq!(passes[0].attachment_images(), vec![ImageId(1), ImageId(2)]);
+        let plan = plan_barriers(&images, &passes);
+        let resolve = plan.before_pass[0]
+            .iter()
+            .find(|barrier| barrier.image == ImageId(2))
+            .unwrap();
+        assert_eq!(resolve.to.layout, vk::ImageLayout::COLOR_ATTACHMENT_OPTIMAL);
+        let read = plan.before_pass[1]
+            .iter()
+            .find(|barrier| barrier.image == ImageId(2))
+            .unwrap();
+        assert!(read.from.writes());
+    }
+    #[test]
+    fn sample_counts_are_clamped_to_the_device() {
+        let supported = vk::SampleCountFlags::TYPE_1
+            | vk::SampleCountFlags::TYPE_2
+            | vk::SampleCountFlags::TYPE_4;
+        assert_eq!(
+            clamp_sample_count(8, supported),
+            vk::SampleCountFlags::TYPE_4
+        );
+        assert_eq!(
+            clamp_sample_count(2, supported),
+            vk::SampleCountFlags::TYPE_2
+        );
+        assert_eq!(
+            clamp_sample_count(1, supported),
+            vk::SampleCountFlags::TYPE_1
+        );
+        assert_eq!(
+            clamp_sample_count(4, vk::SampleCountFlags::TYPE_1),
+            vk::SampleCountFlags::TYPE_1
+        );
+    }
+}
@@ -11,15 +11,10 @@ pub struct SwapChain {
     core: Rc<Core>,
     pub swap_chain_loader: khr::Swapchain,
     pub swap_chain: vk::SwapchainKHR,
-    images: Vec<vk::Image>,
-    image_format: vk::Format,
-    image_views: Vec<vk::ImageView>,
-    render_pass: vk::RenderPass,
-    depth_images: Vec<vk::Image>,
-    depth_image_views: Vec<vk::ImageView>,
-    depth_image_memories: Vec<vk::DeviceMemory>,
+    pub images: Vec<vk::Image>,
+    pub image_format: vk::Format,
+    pub image_views: Vec<vk::ImageView>,
     pub swap_chain_extent: vk::Extent2D,
-    pub frame_buffers: Vec<vk::Framebuffer>,
     pub image_available_semaphores: Vec<vk::Semaphore>,
     pub render_finished_semaphores: Vec<vk::Semaphore>,
     pub inflight_fences: Vec<vk::Fence>,
@@ -36,12 +31,7 @@ impl SwapChain {
             images: Vec::new(),
             image_format: vk::Format::default(),
             image_views: Vec::new(),
-            render_pass: vk::RenderPass::null(),
-            depth_images: Vec::new(),
-            depth_image_views: Vec::new(),
-            depth_image_memories: Vec::new(),
             swap_chain_extent: vk::Extent2D::default(),
-            frame_buffers: Vec::new(),
             image_available_semaphores: Vec::new(),
             render_finished_semaphores: Vec::new(),
             inflight_fences: Vec::new(),
@@ -50,9 +40,6 @@ impl SwapChain {
         };
         swap_chain.create_swap_chain(window_extent,old_swap_chain);
         swap_chain.create_image_views();
-        swap_chain.create_render_pass();
-        swap_chain.create_depth_resources();
-        swap_chain.create_frame_buffer();
         swap_chain.create_sync_objects();
         swap_chain
     }
@@ -137,183 +124,6 @@ impl SwapChain {
             self.image_views.push(image_view);
         }
     }
-    fn create_render_pass(&mut self) {
-        let depth_format = self.core.find_supported_format(
-            vec![
-                vk::Format::D32_SFLOAT,
-                vk::Format::D32_SFLOAT_S8_UINT,
-                vk::Format::D24_UNORM_S8_UINT,
-            ],
-            vk::ImageTiling::OPTIMAL,
-            vk::FormatFeatureFlags::DEPTH_STENCIL_ATTACHMENT,
-        );
-        println!("depth format: {:?}", depth_format);
-        let color_attachment = vk::AttachmentDescription {
-            format: self.image_format,
-            samples: vk::SampleCountFlags::TYPE_1,
-            load_op: vk::AttachmentLoadOp::CLEAR,
-            store_op: vk::AttachmentStoreOp::STORE,
-            stencil_load_op: vk::AttachmentLoadOp::DONT_CARE,
-            stencil_store_op: vk::AttachmentStoreOp::DONT_CARE,
-            initial_layout: vk::ImageLayout::UNDEFINED,
-            final_layout: vk::ImageLayout::PRESENT_SRC_KHR,
-            ..Default::default()
-        };
-        let color_attachment_ref = vk::AttachmentReference {
-            attachment: 0,
-            layout: vk::ImageLayout::COLOR_ATTACHMENT_OPTIMAL,
-        };
-        let depth_attachment = vk::AttachmentDescription::builder()
-            .format(depth_format)
-            .samples(vk::SampleCountFlags::TYPE_1)
-            .load_op(vk::AttachmentLoadOp::CLEAR)
-            .store_op(vk::AttachmentStoreOp::DONT_CARE)
-            .stencil_load_op(vk::AttachmentLoadOp::DONT_CARE)
-            .stencil_store_op(vk::AttachmentStoreOp::DONT_CARE)
-            .initial_layout(vk::ImageLayout::UNDEFINED)
-            .final_layout(vk::ImageLayout::DEPTH_STENCIL_ATTACHMENT_OPTIMAL)
-            .build();
-        let depth_attachment_ref = vk::AttachmentReference::builder()
-            .attachment(1)
-            .layout(vk::ImageLayout::DEPTH_STENCIL_ATTACHMENT_OPTIMAL)
-            .build();
-        let subpass = vk::SubpassDescription {
-            pipeline_bind_point: vk::PipelineBindPoint::GRAPHICS,
-            p_color_attachments: &color_attachment_ref,
-            color_attachment_count: 1,
-            p_depth_stencil_attachment: &depth_attachment_ref,
-            ..Default::default()
-        };
-        let subpass_dependency = vk::SubpassDependency {
-            src_subpass: vk::SUBPASS_EXTERNAL,
-            dst_subpass: 0,
-            src_stage_mask: vk::PipelineStageFlags::COLOR_ATTACHMENT_OUTPUT
-                | vk::PipelineStageFlags::EARLY_FRAGMENT_TESTS,
-            src_access_mask: vk::AccessFlags::empty(),
-            dst_stage_mask: vk::PipelineStageFlags::COLOR_ATTACHMENT_OUTPUT
-                | vk::PipelineStageFlags::EARLY_FRAGMENT_TESTS,
-            dst_access_mask: vk::AccessFlags::COLOR_ATTACHMENT_WRITE
-                | vk::AccessFlags::DEPTH_STENCIL_ATTACHMENT_WRITE,
-            ..Default::default()
-        };
-        let create_info = vk::RenderPassCreateInfo {
-            attachment_count: 2,
-            p_attachments: [color_attachment, depth_attachment].as_ptr(),
-            subpass_count: 1,
-            p_subpasses: &subpass,
-            dependency_count: 1,
-            p_dependencies: &subpass_dependency,
-            ..Default::default()
-        };
-        self.render_pass = unsafe {
-            self.core
-                .logical_device
-                .create_render_pass(&create_info, None)
-                .expect("Failed to create render pass")
-        };
-    }
-    fn create_depth_resources(&mut self) {
-        let depth_format = self.core.find_supported_format(
-            vec![
-                vk::Format::D32_SFLOAT,
-                vk::Format::D32_SFLOAT_S8_UINT,
-                vk::Format::D24_UNORM_S8_UINT,
-            ],
-            vk::ImageTiling::OPTIMAL,
-            vk::FormatFeatureFlags::DEPTH_STENCIL_ATTACHMENT,
-        );
-        let extent = &self.swap_chain_extent;
-        for _i in 0..self.images.len() {
-            let image_create_info = vk::ImageCreateInfo::builder()
-                .image_type(vk::ImageType::TYPE_2D)
-                .extent(vk::Extent3D {
-                    width: extent.width,
-                    height: extent.height,
-                    depth: 1,
-                })
-                .mip_levels(1)
-                .array_layers(1)
-                .format(depth_format)
-                .tiling(vk::ImageTiling::OPTIMAL)
-                .initial_layout(vk::ImageLayout::UNDEFINED)
-                .usage(vk::ImageUsageFlags::DEPTH_STENCIL_ATTACHMENT)
-                .sharing_mode(vk::SharingMode::EXCLUSIVE)
-                .samples(vk::SampleCountFlags::TYPE_1)
-                .build();
-            let image = unsafe {
-                self.core
-                    .logical_device
-                    .create_image(&image_create_info, None)
-                    .expect("Failed to create image")
-            };
-            let mem_req: vk::MemoryRequirements = unsafe {
-                self.core
-                    .logical_device
-                    .get_image_memory_requirements(image)
-            };
-            println!("mem req: {:?}", mem_req);
-            let mem_index = self.core.find_memory_type(
-                mem_req.memory_type_bits,
-                vk::MemoryPropertyFlags::DEVICE_LOCAL,
-            );
-            let alloc_info = vk::MemoryAllocateInfo::builder()
-                .allocation_size(mem_req.size)
-                .memory_type_index(mem_index.unwrap())
-                .build();
-            let image_memory = unsafe {
-                self.core
-                    .logical_device
-                    .allocate_memory(&alloc_info, None)
-                    .expect("Failed to allocate image memory")
-            };
-            unsafe {
-                self.core
-                    .logical_device
-                    .bind_image_memory(image, image_memory, 0)
-                    .expect("Failed to bind")
-            };
-            self.depth_images.push(image);
-            self.depth_image_memories.push(image_memory);
-            let view_info = vk::ImageViewCreateInfo::builder()
-                .image(image)
-                .view_type(vk::ImageViewType::TYPE_2D)
-                .format(depth_format)
-                .subresource_range(vk::ImageSubresourceRange {
-                    aspect_mask: vk::ImageAspectFlags::DEPTH,
-                    base_mip_level: 0,
-                    level_count: 1,
-                    base_array_layer: 0,
-                    layer_count: 1,
-                })
-                .build();
-            let depth_image_view = unsafe {
-                self.core
-                    .logical_device
-                    .create_image_view(&view_info, None)
-                    .expect("Failed to create image view")
-            };
-            self.depth_image_views.push(depth_image_view);
-        }
-    }
-    fn create_frame_buffer(&mut self) {
-        for i in 0..self.images.len() {
-            let attachments = [self.image_views[i], self.depth_image_views[i]];
-            let create_info = vk::FramebufferCreateInfo::builder()
-                .render_pass(self.render_pass)
-                .attachments(&attachments)
-                .width(self.swap_chain_extent.width)
-                .height(self.swap_chain_extent.height)
-                .layers(1)
-                .build();
-            let frame_buffer = unsafe {
-                self.core
-                    .logical_device
-                    .create_framebuffer(&create_info, None)
-                    .expect("Failed to create frame buffer")
-            };
-            self.frame_buffers.push(frame_buffer);
-        }
-    }
     fn create_sync_objects(&mut self) {
         self.image_in_flight = vec![vk::Fence::null(); self.images.len()];
         let semaphore_info = vk::SemaphoreCreateInfo::default();
@@ -344,9 +154,6 @@ impl SwapChain {
             self.inflight_fences.push(fence);
         }
     }
-    pub fn get_render_pass(&self) -> &vk::RenderPass {
-        &self.render_pass
-    }
     pub fn acquire_next_image(&self) -> VkResult<(u32, bool)> {
         unsafe {
             self.core
@@ -415,11 +222,13 @@ impl SwapChain {
     }
 }
 
+/// Prefers a UNORM image, which keeps what the gamma pass wrote as it is;
+/// an sRGB one would encode it a second time.
 fn choose_swap_surface_format(
     available_formats: &Vec<vk::SurfaceFormatKHR>,
 ) -> vk::SurfaceFormatKHR {
     for available_format in available_formats.iter() {
-        if available_format.format == vk::Format::B8G8R8A8_SRGB
+        if available_format.format == vk::Format::B8G8R8A8_UNORM
             && available_format.color_space == vk::ColorSpaceKHR::SRGB_NONLINEAR
         {
             return available_format.clone();
@@ -460,19 +269,6 @@ impl Drop for SwapChain {
 
             self.swap_chain_loader
                 .destroy_swapchain(self.swap_chain, None);
-           for (i,depth_image) in self.depth_images.iter().enumerate() {
-                self.core.logical_device.destroy_image_view(self.depth_image_views[i], None);
-                self.core.logical_device.destroy_image(*depth_image, None);
-                self.core.logical_device.free_memory(self.depth_image_memories[i], None);
-            }
-            for framebuffer in self.frame_buffers.iter() {
-                self.core
-                    .logical_device
-                    .destroy_framebuffer(*framebuffer, None);
-            }
-            self.core
-                .logical_device
-                .destroy_render_pass(self.render_pass, None);
             for i in 0..MAX_FRAMES_IN_FLIGHT{
                 self.core.logical_device.destroy_semaphore(self.image_available_semaphores[i], None);
                 self.core.logical_device.destroy_semaphore(self.render_finished_semaphores[i], None);
@@ -0,0 +1,79 @@
+use std::ops::{Index, IndexMut};
+
+/// A `Vec` whose indices stay valid. Removing a value leaves an empty slot
+/// that the next insert reuses, instead of shifting the values after it.
+#[derive(Debug)]
+pub struct Slots<T> {
+    slots: Vec<Option<T>>,
+    free: Vec<usize>,
+}
+impl<T> Slots<T> {
+    pub fn new() -> Self {
+        Slots {
+            slots: Vec::new(),
+            free: Vec::new(),
+        }
+    }
+    /// Stores `value` in a free slot, or a new one, and returns its index.
+    pub fn insert(&mut self, value: T) -> usize {
+        match self.free.pop() {
+            Some(index) => {
+                self.slots[index] = Some(value);
+                index
+            }
+            None => {
+                self.slots.push(Some(value));
+                self.slots.len() - 1
+            }
+        }
+    }
+    /// Takes the value out of `index`, freeing the slot for reuse.
+    pub fn remove(&mut self, index: usize) -> T {
+        let value = self.slots[index]
+            .take()
+            .unwrap_or_else(|| panic!("Slot {} is already free", index));
+        self.free.push(index);
+        value
+    }
+    /// The stored values with their indices, skipping free slots.
+    pub fn iter_mut(&mut self) -> impl Iterator<Item = (usize, &mut T)> {
+        self.slots
+            .iter_mut()
+            .enumerate()
+            .filter_map(|(index, slot)| slot.as_mut().map(|value| (index, value)))
+    }
+}
+impl<T> Index<usize> for Slots<T> {
+    type Output = T;
+    fn index(&self, index: usize) -> &T {
+        self.slots[index]
+            .as_ref()
+            .unwrap_or_else(|| panic!("Slot {} is free", index))
+    }
+}
+impl<T> IndexMut<usize> for Slots<T> {
+    fn index_mut(&mut self, index: usize) -> &mut T {
+        self.slots[index]
+            .as_mut()
+            .unwrap_or_else(|| panic!("Slot {} is free", index))
+    }
+}
+
+#[cfg(test)]
+mod slots_tests {
+    use super::*;
+    #[test]
+    fn removing_keeps_other_indices() {
+        let mut slots = Slots::new();
+        let first = slots.insert("first");
+        let second = slots.insert("second");
+        let third = slots.insert("third");
+        assert_eq!(slots.remove(second), "second");
+        assert_eq!(slots[first], "first");
+        assert_eq!(slots[third], "third");
+        assert_eq!(slots.iter_mut().count(), 2);
+        // the freed slot is reused
+        assert_eq!(slots.insert("fourth"), second);
+        assert_eq!(slots[second], "fourth");
+    }
+}